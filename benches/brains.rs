@@ -0,0 +1,71 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// How long ants take to think, walking the decision trees versus running the compiled bytecode.
+// Run with `cargo bench`.
+
+use std::{hint::black_box, time::{Duration, Instant}};
+
+use ants::{ant::{Ant, Pheromones, Program}, dsl::parse_program, world::Environment};
+use glm::vec2;
+
+const THINKS: u32 = 200_000;
+
+// neither emits pheromones, they spread every time and would swamp the timing
+const SIMPLE: &str = "
+    if food > 0 and dist(home) < 1 then remember(food), forget
+    if pheromone > 2 then remember(pheromone), forget
+    always nothing
+";
+
+const ARITHMETIC: &str = "
+    if food * 2 + 1 > dist(home) / 3 - 4 or between(food, 1, 3) then remember(food + 1)
+    if approx(normalize(loc(here) - loc(home)), loc((0, 1)), 0.25) xor not food > 2 then remember(length(loc(here)) * 0.5)
+    if dot(loc(here), loc(home) + 1) > max(food, min(3, abs(food - 5))) then forget
+    always remember(memory(0) * 2 - memory(1)), forget, forget, forget
+";
+
+fn ant(program: &Program) -> Ant {
+    let mut a = Ant::new(vec2(3.0, 4.0), program.clone());
+    a.set_home(vec2(0.0, 0.0));
+    a.set_food(2.0);
+    a.set_max_speed(0.0);
+    a
+}
+
+fn time(env: &mut Environment, program: &Program) -> Duration {
+    let mut a = ant(program);
+    let start = Instant::now();
+    for _ in 0..THINKS {
+        env.think(black_box(&mut a));
+    }
+    start.elapsed()
+}
+
+fn compare(name: &str, src: &str) {
+    let tree = parse_program(src).unwrap();
+    let mut compiled = tree.clone();
+    compiled.compile().unwrap();
+
+    let mut env = Environment::new();
+    env.add(Pheromones::new(vec2(3.0, 4.5), 3.0), 2);
+
+    // warm up once so neither side pays for the first allocations
+    time(&mut env, &tree);
+    let walked = time(&mut env, &tree);
+    let ran = time(&mut env, &compiled);
+
+    let per = |d: Duration| d.as_nanos() as f64 / THINKS as f64;
+    println!(
+        "{:<12} tree walker {:>8.1} ns/think   bytecode {:>8.1} ns/think   {:.2}x",
+        name, per(walked), per(ran), per(walked) / per(ran),
+    );
+}
+
+fn main() {
+    compare("simple", SIMPLE);
+    compare("arithmetic", ARITHMETIC);
+}
@@ -5,132 +5,550 @@
 */
 
 use glm::{vec2, Vec2};
-use std::{collections::VecDeque, vec::Vec};
+use glm::{distance, dot, length, normalize};
+use std::{collections::{BTreeMap, VecDeque}, rc::Rc, vec::Vec};
 
-use crate::{shape::{BasicShape, Shape, ShapeType}, world::square_dist};
+use crate::{brain::Brain, geometry::{self, Contact}, shape::{Aabb, BasicShape, Shape, ShapeType}, vm::{self, Bytecode, CompileError}, world::{square_dist, EntityId}};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Location {
-    Home, 
-    Dest,
+    Home, // the nest the ant belongs to
+    Dest, // wherever the ant was last told to go, which doesn't exist if it never was
     Here,
     PheromoneSrc,
+    NearestWall, // closest point on the closest collider that isn't an ant
     Pos(Vec2),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Source {
     Dist(Location),
     Loc(Location),
-    Food,
+    Food, // how much food the ant is carrying
     Number(f32),
     Memory(i32),
     PheromoneStrength,
+    State, // the state a state machine brain is in, numbered from 0, NaN for any other brain
+    Bool(bool),
+    Tick, // how many steps the world has taken
+    Thing(Location), // whatever is at nearest_wall or pheromone_src, to remember and find again
+    Where(Box<Source>), // where a thing is now, NaN if it's gone
+    Register(String), // what was last remembered under a name, NaN if nothing was
+    // arithmetic works on numbers and on positions component by component, mixing the two
+    // applies the number to both components
+    Add(Box<Source>, Box<Source>),
+    Sub(Box<Source>, Box<Source>),
+    Mul(Box<Source>, Box<Source>),
+    Div(Box<Source>, Box<Source>),
+    Min(Box<Source>, Box<Source>),
+    Max(Box<Source>, Box<Source>),
+    Abs(Box<Source>),
+    // these only make sense for positions
+    Dot(Box<Source>, Box<Source>),
+    Length(Box<Source>),
+    Normalize(Box<Source>),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Condition {
     GreaterThan(Source, Source),
     LessThan(Source, Source),
     Equal(Source, Source),
+    ApproxEqual(Source, Source, f32), // within the given distance of each other
+    Between(Source, Source, Source), // the first is at least the second and at most the third
     Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Xor(Box<Condition>, Box<Condition>),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Then {
-    SetDest,
-    EmitPheromone,
-    Remember(Box<Then>),
-    Forget(Box<Then>),
+    SetDest(Location), // also plans a path there
+    EmitPheromone(Source), // of the given strength, stacks onto pheromones right where the ant is
+    Remember(Source, Box<Then>),
+    Forget(Box<Then>), // drops the oldest memory
+    Store(String, Source, Box<Then>), // remembers under a name, replacing whatever was there
+    Clear(String, Box<Then>), // forgets what's under a name
+    Nothing,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Decision {
     If(Condition, Then),
     IfHaveFood(Then),
     Always(Then),
 }
 
-#[derive(Copy, Clone, PartialEq)]
+// Which of the decisions that match get acted on each step
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Policy {
+    #[default]
+    All, // every one, in order
+    First, // only the first, so earlier decisions take priority
+    WeightedRandom, // one of them, picked with chance proportional to its weight
+}
+
+// A brain made of decisions, shared between copies until one of them changes
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub(crate) policy: Policy,
+    pub(crate) decisions: Rc<Vec<Decision>>,
+    pub(crate) weights: Rc<Vec<f32>>, // one per decision, only used by WeightedRandom
+    pub(crate) bytecode: Option<Rc<Bytecode>>, // if it's been compiled, see vm
+}
+
+impl Program {
+    pub fn new(policy: Policy, decisions: Vec<Decision>) -> Self {
+        let weights = vec![1.0; decisions.len()];
+        Program { policy, decisions: Rc::new(decisions), weights: Rc::new(weights), bytecode: None }
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn len(&self) -> usize {
+        self.decisions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decisions.is_empty()
+    }
+
+    pub fn push(&mut self, d: Decision) {
+        self.push_weighted(d, 1.0);
+    }
+
+    // negative weights count as zero, those decisions are never picked at random
+    pub fn push_weighted(&mut self, d: Decision, weight: f32) {
+        Rc::make_mut(&mut self.decisions).push(d);
+        Rc::make_mut(&mut self.weights).push(weight.max(0.0));
+        self.bytecode = None;
+    }
+
+    // Compiles the decisions so ants run them on the VM instead of walking the tree,
+    // changing the program afterwards throws the compiled version away
+    pub fn compile(&mut self) -> Result<(), CompileError> {
+        self.bytecode = Some(Rc::new(vm::compile(&self.decisions)?));
+        Ok(())
+    }
+
+    pub fn bytecode(&self) -> Option<&Bytecode> {
+        self.bytecode.as_deref()
+    }
+
+    // for changing decisions in place, which also throws away the compiled version
+    pub(crate) fn decisions_mut(&mut self) -> &mut Vec<Decision> {
+        self.bytecode = None;
+        Rc::make_mut(&mut self.decisions)
+    }
+}
+
+impl From<Vec<Decision>> for Program {
+    fn from(decisions: Vec<Decision>) -> Self {
+        Program::new(Policy::All, decisions)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Memory {
     Number(f32),
     Position(Vec2),
+    Bool(bool),
+    Tick(u64), // a moment, as how many steps the world had taken
+    Entity(EntityId), // something in the world, which might not be there any more
+}
+
+// What sources and conditions do with values once they have them.
+// Nonsense like the length of a number is NaN, which fails every comparison.
+impl Memory {
+    // ticks do arithmetic as plain numbers, so the time between two is a number
+    fn number(self) -> Option<f32> {
+        match self {
+            Memory::Number(x) => Some(x),
+            Memory::Tick(t) => Some(t as f32),
+            _ => None,
+        }
+    }
+
+    // numbers with numbers, positions with positions component by component,
+    // and a number with a position as if it were a position with the number twice
+    pub fn arith<F: Fn(f32, f32) -> f32>(self, other: Memory, op: F) -> Memory {
+        match (self, other) {
+            (Memory::Position(p), Memory::Position(q)) => Memory::Position(vec2(op(p.x, q.x), op(p.y, q.y))),
+            (Memory::Position(p), y) => match y.number() {
+                Some(y) => Memory::Position(vec2(op(p.x, y), op(p.y, y))),
+                None => Memory::Number(f32::NAN),
+            },
+            (x, Memory::Position(q)) => match x.number() {
+                Some(x) => Memory::Position(vec2(op(x, q.x), op(x, q.y))),
+                None => Memory::Number(f32::NAN),
+            },
+            (x, y) => Memory::Number(match (x.number(), y.number()) {
+                (Some(x), Some(y)) => op(x, y),
+                _ => f32::NAN,
+            }),
+        }
+    }
+
+    pub fn abs(self) -> Memory {
+        match self {
+            Memory::Position(p) => Memory::Position(vec2(p.x.abs(), p.y.abs())),
+            m => Memory::Number(m.number().map_or(f32::NAN, f32::abs)),
+        }
+    }
+
+    pub fn dot(self, other: Memory) -> Memory {
+        match (self, other) {
+            (Memory::Position(p), Memory::Position(q)) => Memory::Number(dot(p, q)),
+            _ => Memory::Number(f32::NAN),
+        }
+    }
+
+    pub fn length(self) -> Memory {
+        match self {
+            Memory::Position(p) => Memory::Number(length(p)),
+            _ => Memory::Number(f32::NAN),
+        }
+    }
+
+    // the zero vector stays put instead of turning into NaN
+    pub fn normalize(self) -> Memory {
+        match self {
+            Memory::Position(p) if p == vec2(0.0, 0.0) => Memory::Position(p),
+            Memory::Position(p) => Memory::Position(normalize(p)),
+            _ => Memory::Number(f32::NAN),
+        }
+    }
+
+    // positions have to be greater in both components
+    pub fn greater_than(self, other: Memory) -> bool {
+        match (self, other) {
+            (Memory::Number(a), Memory::Number(b)) => a > b,
+            (Memory::Tick(a), Memory::Tick(b)) => a > b,
+            (Memory::Position(a), Memory::Position(b)) => a.x > b.x && a.y > b.y,
+            _ => false,
+        }
+    }
+
+    pub fn less_than(self, other: Memory) -> bool {
+        other.greater_than(self)
+    }
+
+    pub fn approx_eq(self, other: Memory, eps: f32) -> bool {
+        match (self, other) {
+            (Memory::Number(a), Memory::Number(b)) => (a - b).abs() <= eps,
+            (Memory::Tick(a), Memory::Tick(b)) => a.abs_diff(b) as f32 <= eps,
+            (Memory::Position(a), Memory::Position(b)) => distance(a, b) <= eps,
+            _ => false,
+        }
+    }
+
+    pub fn between(self, lo: Memory, hi: Memory) -> bool {
+        match (self, lo, hi) {
+            (Memory::Number(x), Memory::Number(lo), Memory::Number(hi)) => lo <= x && x <= hi,
+            (Memory::Tick(x), Memory::Tick(lo), Memory::Tick(hi)) => lo <= x && x <= hi,
+            (Memory::Position(x), Memory::Position(lo), Memory::Position(hi)) => {
+                lo.x <= x.x && x.x <= hi.x && lo.y <= x.y && x.y <= hi.y
+            },
+            _ => false,
+        }
+    }
+}
+
+// What gets dropped when an ant remembers something with no room left
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Eviction {
+    #[default]
+    Oldest,
+    Newest, // the latest memory gets replaced
+    Refuse, // the new memory doesn't get remembered at all
+}
+
+// Everything an ant remembers: a list read from the newest back with Source::Memory, which holds
+// at most capacity memories, and registers that each hold one memory under a name
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Memories {
+    pub(crate) list: VecDeque<Memory>, // oldest first
+    pub(crate) registers: BTreeMap<String, Memory>,
+    pub(crate) capacity: Option<usize>, // None for no limit
+    pub(crate) eviction: Eviction,
+}
+
+impl Memories {
+    // With no limit on how many there can be
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bounded(capacity: usize, eviction: Eviction) -> Self {
+        Memories { capacity: Some(capacity), eviction, ..Default::default() }
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    // shrinking it drops memories the way the eviction policy would, refused ones as if they were newest
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+        while self.list.len() > capacity.unwrap_or(usize::MAX) {
+            let _ = match self.eviction {
+                Eviction::Oldest => self.list.pop_front(),
+                Eviction::Newest | Eviction::Refuse => self.list.pop_back(),
+            };
+        }
+    }
+
+    pub fn eviction(&self) -> Eviction {
+        self.eviction
+    }
+
+    pub fn set_eviction(&mut self, eviction: Eviction) {
+        self.eviction = eviction;
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|c| self.list.len() >= c)
+    }
+
+    // oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Memory> {
+        self.list.iter()
+    }
+
+    // counting back from the newest, like Source::Memory
+    pub fn get(&self, i: usize) -> Option<Memory> {
+        self.list.len().checked_sub(i + 1).map(|i| self.list[i])
+    }
+
+    pub fn remember(&mut self, m: Memory) {
+        if self.capacity == Some(0) {
+            return;
+        }
+        if self.is_full() {
+            let _ = match self.eviction {
+                Eviction::Oldest => self.list.pop_front(),
+                Eviction::Newest => self.list.pop_back(),
+                Eviction::Refuse => return,
+            };
+        }
+        self.list.push_back(m);
+    }
+
+    // drops the oldest memory
+    pub fn forget(&mut self) -> Option<Memory> {
+        self.list.pop_front()
+    }
+
+    pub fn register(&self, name: &str) -> Option<Memory> {
+        self.registers.get(name).copied()
+    }
+
+    pub fn registers(&self) -> &BTreeMap<String, Memory> {
+        &self.registers
+    }
+
+    pub fn store(&mut self, name: &str, m: Memory) {
+        self.registers.insert(name.to_string(), m);
+    }
+
+    pub fn clear(&mut self, name: &str) {
+        self.registers.remove(name);
+    }
+}
+
+// the oldest first, with no limit
+impl FromIterator<Memory> for Memories {
+    fn from_iter<I: IntoIterator<Item = Memory>>(iter: I) -> Self {
+        Memories { list: iter.into_iter().collect(), ..Default::default() }
+    }
 }
 
 // An ant.
 #[derive(Clone)]
 pub struct Ant {
     pub(crate) pos: Vec2, // aka center of a circle with r=2 (in a 250x250 grid)
-    pub(crate) decisions: [Decision; 4],
-    pub(crate) memory: VecDeque<Memory>,
-    pub(crate) has_food: bool,
-    pub(crate) path: Vec<Vec2>
+    pub(crate) brain: Box<dyn Brain>,
+    pub(crate) memory: Memories,
+    pub(crate) home: Vec2,
+    pub(crate) food: f32,
+    pub(crate) dest: Option<Vec2>,
+    pub(crate) path: Vec<Vec2>,
+    pub(crate) heading: f32, // degrees clockwise from +y, like everything else
+    pub(crate) max_speed: f32, // units per unit of time
+    pub(crate) turn_rate: f32, // degrees per unit of time
+    pub(crate) steering: Option<(f32, f32)>, // turn and speed asked for by the brain, see Action::Steer
+}
+
+// an ant crawls about this fast by default, and can turn around in two units of time
+const DEFAULT_SPEED: f32 = 1.0;
+const DEFAULT_TURN_RATE: f32 = 90.0;
+
+impl Ant {
+    // the ant's home is wherever it starts out
+    pub fn new<B: Brain>(pos: Vec2, brain: B) -> Self {
+        Ant {
+            pos,
+            brain: Box::new(brain),
+            memory: Memories::new(),
+            home: pos,
+            food: 0.0,
+            dest: None,
+            path: Vec::new(),
+            heading: 0.0,
+            max_speed: DEFAULT_SPEED,
+            turn_rate: DEFAULT_TURN_RATE,
+            steering: None,
+        }
+    }
+
+    pub fn brain(&self) -> &dyn Brain {
+        self.brain.as_ref()
+    }
+
+    pub fn brain_mut(&mut self) -> &mut dyn Brain {
+        self.brain.as_mut()
+    }
+
+    pub fn set_brain<B: Brain>(&mut self, brain: B) {
+        self.brain = Box::new(brain);
+    }
+
+    // the ant's program, if its brain is one
+    pub fn program(&self) -> Option<&Program> {
+        self.brain.downcast_ref::<Program>()
+    }
+
+    pub fn pos(&self) -> Vec2 {
+        self.pos
+    }
+
+    pub fn heading(&self) -> f32 {
+        self.heading
+    }
+
+    pub fn set_heading(&mut self, heading: f32) {
+        self.heading = heading.rem_euclid(360.0);
+    }
+
+    pub fn max_speed(&self) -> f32 {
+        self.max_speed
+    }
+
+    pub fn set_max_speed(&mut self, max_speed: f32) {
+        self.max_speed = max_speed.max(0.0);
+    }
+
+    pub fn turn_rate(&self) -> f32 {
+        self.turn_rate
+    }
+
+    pub fn set_turn_rate(&mut self, turn_rate: f32) {
+        self.turn_rate = turn_rate.max(0.0);
+    }
+
+    // replaces whatever path the ant was following
+    pub fn set_path(&mut self, path: Vec<Vec2>) {
+        self.path = path;
+    }
+
+    // how the brain last asked to be steered, if it's steering instead of following a path
+    pub fn steering(&self) -> Option<(f32, f32)> {
+        self.steering
+    }
+
+    // unit vector the ant is facing
+    pub fn facing(&self) -> Vec2 {
+        let (sin, cos) = self.heading.to_radians().sin_cos();
+        vec2(sin, cos)
+    }
+
+    pub fn dest(&self) -> Option<Vec2> {
+        self.dest
+    }
+
+    pub fn memory(&self) -> &Memories {
+        &self.memory
+    }
+
+    // for setting its capacity, or giving it something to remember from the start
+    pub fn memory_mut(&mut self) -> &mut Memories {
+        &mut self.memory
+    }
+
+    pub fn home(&self) -> Vec2 {
+        self.home
+    }
+
+    pub fn set_home(&mut self, home: Vec2) {
+        self.home = home;
+    }
+
+    pub fn food(&self) -> f32 {
+        self.food
+    }
+
+    pub fn set_food(&mut self, food: f32) {
+        self.food = food.max(0.0);
+    }
+
+    pub fn has_food(&self) -> bool {
+        self.food > 0.0
+    }
+
+    pub fn path(&self) -> &[Vec2] {
+        &self.path
+    }
 }
 
 impl Shape for Ant {
     fn collides(&self, shape: &dyn Shape) -> bool {
-        match shape.into_basic_shape() {
-            BasicShape::Circle(c, r) => {
-                let dist =   ((self.pos.x - c.x) * (self.pos.x - c.x)) 
-                                + ((self.pos.y - c.y) * (self.pos.y - c.y)) 
-                                - (r*r);
-                let ulp = (dist.to_bits() as i32 - 4.0f32.to_bits() as i32).abs();
-                if ulp > 16 && dist > 0.001 {
-                    false
-                } else {
-                    true
-                }
-            }
-            BasicShape::Rect(cl, wh) => {
-                let points = vec![
-                    cl, vec2(cl.x, cl.y - (wh.y/2.0)), vec2(cl.x, cl.y - wh.y), 
-                    vec2(cl.x + (wh.x/2.0), cl.y - wh.y), vec2(cl.x + wh.x, cl.y - wh.y),
-                    vec2(cl.x + wh.x, cl.y - (wh.y/2.0)), vec2(cl.x + wh.x, cl.y),
-                ];
-
-                for p in points {
-                    let dist = ((self.pos.x - p.x)*(self.pos.x - p.x)) + ((self.pos.y - p.y)*(self.pos.y - p.y));
-                    let ulp = (dist.to_bits() as i32 - 4.0f32.to_bits() as i32).abs();
-
-                    if ulp > 16 {
-                        if dist < 0.001 {
-                            return true;
-                        }
-                    }
-                }
-
-                false
-            },
-            BasicShape::Other => {
-                for p in shape.into_points() {
-                    let dist = ((self.pos.x - p.x)*(self.pos.x - p.x)) + ((self.pos.y - p.y)*(self.pos.y - p.y));
-                    let ulp = (dist.to_bits() as i32 - 4.0f32.to_bits() as i32).abs();
-
-                    if ulp > 16 {
-                        if dist < 0.001 {
-                            return true;
-                        }
-                    }
-                }
-
-                shape.collides(self)
-            }
-        }
+        geometry::collides(self, shape)
     }
 
-    fn contains_point(&self, p: Vec2) -> bool {
-        let dist = ((self.pos.x - p.x) * (self.pos.x - p.x)) + ((self.pos.y - p.y) * (self.pos.y - p.y));
-        let ulp = (dist.to_bits() as i32 - 4.0f32.to_bits() as i32).abs();
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
 
-        if ulp > 16 && dist > 0.001 {
-            false
-        } else {
-            true
-        }
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        square_dist(self.pos, p) <= 4.0
     }
 
     fn into_points(&self) -> Vec<Vec2> {
-        (0..128).map(|i| vec2(2.0 * (2.8125 * (i as f32)).to_radians().sin(), 2.0 * (2.8125 * (i as f32)).to_radians().cos())).collect()
+        (0..128).map(|i| vec2(
+            self.pos.x + 2.0 * (2.8125 * (i as f32)).to_radians().sin(), 
+            self.pos.y + 2.0 * (2.8125 * (i as f32)).to_radians().cos()
+        )).collect()
     }
 
     fn get_center(&self) -> Vec2 {
@@ -144,6 +562,10 @@ impl Shape for Ant {
     fn into_basic_shape(&self) -> crate::shape::BasicShape {
         BasicShape::Circle(self.pos, 2.0f32)
     }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::around(self.pos, 2.0)
+    }
 }
 
 // A source of pheromones
@@ -153,68 +575,42 @@ pub struct Pheromones {
     pub(crate) strength: f32, // apparent strength is calculated as (strength)/dist(p,a)
 }
 
+impl Pheromones {
+    pub fn new(pos: Vec2, strength: f32) -> Self {
+        Pheromones { pos, strength }
+    }
+
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+}
+
 impl Shape for Pheromones {
     fn collides(&self, shape: &dyn Shape) -> bool {
-        match shape.into_basic_shape() {
-            BasicShape::Circle(c, r) => {
-                let dist = square_dist(self.pos, c) - r.powi(2);
-                let ulp = (dist.to_bits() as i32 - (self.strength.powi(2)).to_bits() as i32).abs();
-                if ulp > 16 && dist > 0.001 {
-                    false
-                } else {
-                    true
-                }
-            }
-            BasicShape::Rect(cl, wh) => {
-                let points = vec![
-                    cl, vec2(cl.x, cl.y - (wh.y/2.0)), vec2(cl.x, cl.y - wh.y), 
-                    vec2(cl.x + (wh.x/2.0), cl.y - wh.y), vec2(cl.x + wh.x, cl.y - wh.y),
-                    vec2(cl.x + wh.x, cl.y - (wh.y/2.0)), vec2(cl.x + wh.x, cl.y),
-                ];
-
-                for p in points {
-                    let dist = square_dist(self.pos, p);
-                    let ulp = (dist.to_bits() as i32 - (self.strength.powi(2)).to_bits() as i32).abs();
-
-                    if ulp > 16 {
-                        if dist < 0.001 {
-                            return true;
-                        }
-                    }
-                }
-
-                false
-            },
-            BasicShape::Other => {
-                for p in shape.into_points() {
-                    let dist = square_dist(self.pos, p);
-                    let ulp = (dist.to_bits() as i32 - (self.strength.powi(2)).to_bits() as i32).abs();
-
-                    if ulp > 16 {
-                        if dist < 0.001 {
-                            return true;
-                        }
-                    }
-                }
-
-                shape.collides(self)
-            }
-        }
+        geometry::collides(self, shape)
     }
 
-    fn contains_point(&self, p: Vec2) -> bool {
-        let dist = square_dist(self.pos, p);
-        let ulp = (dist.to_bits() as i32 - (self.strength.powi(2)).to_bits() as i32).abs();
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
 
-        if ulp > 16 && dist > 0.001 {
-            false
-        } else {
-            true
-        }
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        square_dist(self.pos, p) <= self.strength.powi(2)
     }
 
     fn into_points(&self) -> Vec<Vec2> {
-        (0..128).map(|i| vec2(self.strength * (2.8125 * (i as f32)).sin(), self.strength * (2.8125 * (i as f32)).cos())).collect()
+        (0..128).map(|i| vec2(
+            self.pos.x + self.strength * (2.8125 * (i as f32)).to_radians().sin(), 
+            self.pos.y + self.strength * (2.8125 * (i as f32)).to_radians().cos()
+        )).collect()
     }
 
     fn get_center(&self) -> Vec2 {
@@ -228,4 +624,8 @@ impl Shape for Pheromones {
     fn into_basic_shape(&self) -> crate::shape::BasicShape {
         BasicShape::Circle(self.pos, self.strength)
     }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::around(self.pos, self.strength)
+    }
 }
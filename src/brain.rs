@@ -0,0 +1,566 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// Whatever drives an ant. A brain gets told what the ant senses, and says what it wants done,
+// the world does it afterwards. Programs of decisions are one kind, anything else that can
+// turn senses into actions can be another, and they can all share a world.
+
+use std::{borrow::Cow, cell::OnceCell, fmt, vec::Vec};
+
+use downcast_rs::{impl_downcast, Downcast};
+use glm::{distance, vec2, Vec2};
+
+use crate::{
+    ant::{Ant, Condition, Decision, Location, Memories, Memory, Policy, Program, Source, Then},
+    typecheck::{self, Diagnostic},
+    world::{EntityId, Environment},
+};
+
+// the antennae reach this far from the middle of the ant, one straight ahead and one to each side
+pub const ANTENNA_LENGTH: f32 = 3.0;
+pub const ANTENNA_ANGLE: f32 = 45.0; // degrees off from straight ahead
+
+// What a brain can ask for
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    SetDest(Vec2), // also plans a path there
+    EmitPheromone(f32), // of the given strength, anything that isn't positive does nothing
+    Remember(Memory),
+    Forget, // drops the oldest memory
+    Store(String, Memory), // remembers under a name
+    Clear(String),
+    // steers by hand instead of following a path, until the next SetDest. The turn is a fraction
+    // of the ant's turn rate, -1 (anticlockwise) to 1 (clockwise), and the speed a fraction of its top speed.
+    Steer(f32, f32),
+}
+
+pub trait Brain: BrainClone + Downcast + fmt::Debug {
+    // Decides what to do this step, actions happen in order once it's done
+    fn think(&mut self, senses: &Senses) -> Vec<Action>;
+
+    // Everything wrong with this brain for an ant remembering the given things
+    fn check(&self, _memory: &Memories) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+}
+impl_downcast!(Brain);
+
+// ants get copied around, and their brains have to come with them
+pub trait BrainClone {
+    fn clone_brain(&self) -> Box<dyn Brain>;
+}
+
+impl<T: Brain + Clone> BrainClone for T {
+    fn clone_brain(&self) -> Box<dyn Brain> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Brain> {
+    fn clone(&self) -> Self {
+        self.clone_brain()
+    }
+}
+
+// What an ant can tell about itself and the world, which holds still while it thinks.
+// The expensive things are only looked up if something asks for them.
+pub struct Senses<'a> {
+    env: &'a Environment,
+    ant: &'a Ant,
+    nearest_wall: OnceCell<Option<Vec2>>,
+    pheromone_src: OnceCell<Option<Vec2>>,
+    pheromone: OnceCell<f32>,
+    antennae: OnceCell<[f32; 3]>,
+}
+
+impl<'a> Senses<'a> {
+    pub fn new(env: &'a Environment, ant: &'a Ant) -> Self {
+        Senses {
+            env,
+            ant,
+            nearest_wall: OnceCell::new(),
+            pheromone_src: OnceCell::new(),
+            pheromone: OnceCell::new(),
+            antennae: OnceCell::new(),
+        }
+    }
+
+    pub fn world(&self) -> &Environment {
+        self.env
+    }
+
+    pub fn pos(&self) -> Vec2 {
+        self.ant.pos
+    }
+
+    pub fn heading(&self) -> f32 {
+        self.ant.heading
+    }
+
+    pub fn home(&self) -> Vec2 {
+        self.ant.home
+    }
+
+    pub fn dest(&self) -> Option<Vec2> {
+        self.ant.dest
+    }
+
+    pub fn food(&self) -> f32 {
+        self.ant.food
+    }
+
+    pub fn has_food(&self) -> bool {
+        self.ant.has_food()
+    }
+
+    pub fn memory(&self) -> &Memories {
+        &self.ant.memory
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.env.tick()
+    }
+
+    // how strong the pheromones are where the ant is
+    pub fn pheromone(&self) -> f32 {
+        *self.pheromone.get_or_init(|| self.env.pheromone_strength_at_pos(self.ant.pos))
+    }
+
+    // how strong the pheromones are at the tips of the left, middle and right antennae
+    pub fn antennae(&self) -> [f32; 3] {
+        *self.antennae.get_or_init(|| [-ANTENNA_ANGLE, 0.0, ANTENNA_ANGLE].map(|off| {
+            let (sin, cos) = (self.ant.heading + off).to_radians().sin_cos();
+            self.env.pheromone_strength_at_pos(self.ant.pos + vec2(sin, cos) * ANTENNA_LENGTH)
+        }))
+    }
+
+    // Where a location is, None for anywhere that doesn't exist (no walls, no destination)
+    pub fn location(&self, loc: Location) -> Option<Vec2> {
+        match loc {
+            Location::Here => Some(self.ant.pos),
+            Location::Home => Some(self.ant.home),
+            Location::Dest => self.ant.dest,
+            Location::Pos(p) => Some(p),
+            Location::NearestWall => *self.nearest_wall.get_or_init(|| {
+                self.env.nearest_wall(self.ant.pos).map(|(w, _)| w.borrow().closest_point(self.ant.pos))
+            }),
+            Location::PheromoneSrc => *self.pheromone_src.get_or_init(|| {
+                self.env.nearest_pheromone(self.ant.pos).map(|p| p.borrow().get_center())
+            }),
+        }
+    }
+
+    // The thing at a location, only the nearest wall and pheromone are things
+    pub fn thing(&self, loc: Location) -> Option<EntityId> {
+        let thing = match loc {
+            Location::NearestWall => self.env.nearest_wall(self.ant.pos).map(|(w, _)| w),
+            Location::PheromoneSrc => self.env.nearest_pheromone(self.ant.pos),
+            _ => None,
+        };
+        thing.and_then(|t| self.env.id(&t))
+    }
+
+    // Where a thing is, the middle of it for walls
+    pub fn where_is(&self, id: EntityId) -> Option<Vec2> {
+        self.env.entity(id).map(|t| t.borrow().get_center())
+    }
+
+    // a number in [0, 1), the same ones every run of a world with the same seed
+    pub fn random(&self) -> f32 {
+        self.env.random()
+    }
+
+    // What a source comes to, for brains that want to use them without being a program
+    pub fn evaluate(&self, s: &Source) -> Memory {
+        Scratch::new(self).source(s)
+    }
+
+    pub fn holds(&self, c: &Condition) -> bool {
+        Scratch::new(self).condition(c)
+    }
+}
+
+// What a program knows part way through thinking: the senses, plus whatever the decisions
+// before have changed, since each one sees what the ones before it did
+pub(crate) struct Scratch<'s, 'a> {
+    senses: &'s Senses<'a>,
+    memory: Cow<'s, Memories>, // only copied once something changes it
+    dest: Option<Vec2>,
+    actions: Vec<Action>,
+    state: f32, // what Source::State gives
+}
+
+impl<'s, 'a> Scratch<'s, 'a> {
+    pub(crate) fn new(senses: &'s Senses<'a>) -> Self {
+        Scratch {
+            senses,
+            memory: Cow::Borrowed(senses.memory()),
+            dest: senses.dest(),
+            actions: Vec::new(),
+            state: f32::NAN,
+        }
+    }
+
+    // thinking as part of a state machine that's in the given state
+    pub(crate) fn in_state(mut self, state: usize) -> Self {
+        self.state = state as f32;
+        self
+    }
+
+    pub(crate) fn state(&self) -> f32 {
+        self.state
+    }
+
+    pub(crate) fn senses(&self) -> &Senses<'a> {
+        self.senses
+    }
+
+    pub(crate) fn location(&self, loc: Location) -> Option<Vec2> {
+        match loc {
+            Location::Dest => self.dest,
+            l => self.senses.location(l),
+        }
+    }
+
+    // somewhere that doesn't exist is NaN, like anything else that makes no sense
+    pub(crate) fn position(&self, loc: Location) -> Memory {
+        self.location(loc).map_or(Memory::Number(f32::NAN), Memory::Position)
+    }
+
+    pub(crate) fn dist(&self, loc: Location) -> f32 {
+        match loc {
+            Location::Here => 0.0,
+            l => self.location(l).map_or(f32::NAN, |p| distance(p, self.senses.pos())),
+        }
+    }
+
+    // the type checker rules out reading memories that aren't there, but just in case
+    pub(crate) fn recall(&self, i: i32) -> Memory {
+        usize::try_from(i).ok()
+            .and_then(|i| self.memory.get(i))
+            .unwrap_or(Memory::Number(f32::NAN))
+    }
+
+    pub(crate) fn remember(&mut self, m: Memory) {
+        self.memory.to_mut().remember(m);
+        self.actions.push(Action::Remember(m));
+    }
+
+    pub(crate) fn forget(&mut self) {
+        let _ = self.memory.to_mut().forget();
+        self.actions.push(Action::Forget);
+    }
+
+    pub(crate) fn register(&self, name: &str) -> Memory {
+        self.memory.register(name).unwrap_or(Memory::Number(f32::NAN))
+    }
+
+    pub(crate) fn store(&mut self, name: &str, m: Memory) {
+        self.memory.to_mut().store(name, m);
+        self.actions.push(Action::Store(name.to_string(), m));
+    }
+
+    pub(crate) fn clear(&mut self, name: &str) {
+        self.memory.to_mut().clear(name);
+        self.actions.push(Action::Clear(name.to_string()));
+    }
+
+    // a thing as a memory, and where a remembered thing is
+    pub(crate) fn thing(&self, loc: Location) -> Memory {
+        self.senses.thing(loc).map_or(Memory::Number(f32::NAN), Memory::Entity)
+    }
+
+    pub(crate) fn where_is(&self, m: Memory) -> Memory {
+        match m {
+            Memory::Entity(id) => self.senses.where_is(id).map_or(Memory::Number(f32::NAN), Memory::Position),
+            _ => Memory::Number(f32::NAN),
+        }
+    }
+
+    // there's no planning a way to somewhere that doesn't exist, so that does nothing
+    pub(crate) fn set_dest(&mut self, loc: Location) {
+        if let Some(dest) = self.location(loc) {
+            self.dest = Some(dest);
+            self.actions.push(Action::SetDest(dest));
+        }
+    }
+
+    // pheromones as strong as whatever was evaluated, if that makes sense as a strength
+    pub(crate) fn emit(&mut self, m: Memory) {
+        if let Memory::Number(n) = m {
+            if n > 0.0 {
+                self.actions.push(Action::EmitPheromone(n));
+            }
+        }
+    }
+
+    pub(crate) fn source(&self, s: &Source) -> Memory {
+        match s {
+            Source::Number(n) => Memory::Number(*n),
+            Source::Dist(l) => Memory::Number(self.dist(*l)),
+            Source::Memory(i) => self.recall(*i),
+            Source::PheromoneStrength => Memory::Number(self.senses.pheromone()),
+            Source::State => Memory::Number(self.state),
+            Source::Bool(b) => Memory::Bool(*b),
+            Source::Tick => Memory::Tick(self.senses.tick()),
+            Source::Thing(l) => self.thing(*l),
+            Source::Where(a) => self.where_is(self.source(a)),
+            Source::Register(name) => self.register(name),
+            Source::Food => Memory::Number(self.senses.food()),
+            Source::Loc(l) => self.position(*l),
+            Source::Add(a, b) => self.source(a).arith(self.source(b), |x, y| x + y),
+            Source::Sub(a, b) => self.source(a).arith(self.source(b), |x, y| x - y),
+            Source::Mul(a, b) => self.source(a).arith(self.source(b), |x, y| x * y),
+            Source::Div(a, b) => self.source(a).arith(self.source(b), |x, y| x / y),
+            Source::Min(a, b) => self.source(a).arith(self.source(b), f32::min),
+            Source::Max(a, b) => self.source(a).arith(self.source(b), f32::max),
+            Source::Abs(a) => self.source(a).abs(),
+            Source::Dot(a, b) => self.source(a).dot(self.source(b)),
+            Source::Length(a) => self.source(a).length(),
+            Source::Normalize(a) => self.source(a).normalize(),
+        }
+    }
+
+    pub(crate) fn condition(&self, c: &Condition) -> bool {
+        match c {
+            Condition::Equal(a, b) => self.source(a) == self.source(b),
+            Condition::Not(c) => !self.condition(c),
+            Condition::And(a, b) => self.condition(a) && self.condition(b),
+            Condition::Or(a, b) => self.condition(a) || self.condition(b),
+            Condition::Xor(a, b) => self.condition(a) != self.condition(b),
+            Condition::ApproxEqual(a, b, eps) => self.source(a).approx_eq(self.source(b), *eps),
+            Condition::Between(x, lo, hi) => self.source(x).between(self.source(lo), self.source(hi)),
+            Condition::LessThan(a, b) => self.source(a).less_than(self.source(b)),
+            Condition::GreaterThan(a, b) => self.source(a).greater_than(self.source(b)),
+        }
+    }
+
+    // does what a decision said to, following Remember and Forget on to whatever they're attached to
+    pub(crate) fn act(&mut self, mut t: &Then) {
+        loop {
+            match t {
+                Then::Nothing => return,
+                Then::SetDest(loc) => return self.set_dest(*loc),
+                Then::EmitPheromone(s) => {
+                    let m = self.source(s);
+                    return self.emit(m);
+                },
+                Then::Remember(s, next) => {
+                    let m = self.source(s);
+                    self.remember(m);
+                    t = next;
+                },
+                Then::Forget(next) => {
+                    self.forget();
+                    t = next;
+                },
+                Then::Store(name, s, next) => {
+                    let m = self.source(s);
+                    self.store(name, m);
+                    t = next;
+                },
+                Then::Clear(name, next) => {
+                    self.clear(name);
+                    t = next;
+                },
+            }
+        }
+    }
+
+    // whether a decision's condition holds, run on the bytecode if the program was compiled
+    fn fires(&mut self, program: &Program, i: usize) -> bool {
+        if let Some(code) = &program.bytecode {
+            return self.run(code, code.condition(i));
+        }
+
+        match &program.decisions[i] {
+            Decision::Always(_) => true,
+            Decision::IfHaveFood(_) => self.senses.has_food(),
+            Decision::If(c, _) => self.condition(c),
+        }
+    }
+
+    // does what a decision says to, again on the bytecode if there is some
+    fn follow(&mut self, program: &Program, i: usize) {
+        if let Some(code) = &program.bytecode {
+            self.run(code, code.action(i));
+            return;
+        }
+
+        match &program.decisions[i] {
+            Decision::Always(t) | Decision::IfHaveFood(t) | Decision::If(_, t) => self.act(t),
+        }
+    }
+
+    // Runs a program, acting on the decisions its policy picks
+    pub(crate) fn think(mut self, program: &Program) -> Vec<Action> {
+        match program.policy {
+            Policy::All => {
+                for i in 0..program.len() {
+                    if self.fires(program, i) {
+                        self.follow(program, i);
+                    }
+                }
+            },
+            Policy::First => {
+                if let Some(i) = (0..program.len()).find(|&i| self.fires(program, i)) {
+                    self.follow(program, i);
+                }
+            },
+            Policy::WeightedRandom => {
+                let fired: Vec<usize> = (0..program.len()).filter(|&i| self.fires(program, i)).collect();
+                let total: f32 = fired.iter().map(|&i| program.weights[i]).sum();
+                if total <= 0.0 {
+                    return self.actions;
+                }
+
+                let mut pick = self.senses.random() * total;
+                for i in fired {
+                    let w = program.weights[i];
+                    if pick < w {
+                        self.follow(program, i);
+                        break;
+                    }
+                    pick -= w;
+                }
+            },
+        }
+        self.actions
+    }
+}
+
+impl Brain for Program {
+    fn think(&mut self, senses: &Senses) -> Vec<Action> {
+        Scratch::new(senses).think(self)
+    }
+
+    fn check(&self, memory: &Memories) -> Vec<Diagnostic> {
+        typecheck::check(self, memory)
+    }
+}
+
+// A brain that's just a function, or a closure holding whatever state it likes
+#[derive(Clone)]
+pub struct FnBrain<F>(F);
+
+impl<F> FnBrain<F>
+where
+    F: FnMut(&Senses) -> Vec<Action> + Clone + 'static,
+{
+    pub fn new(f: F) -> Self {
+        FnBrain(f)
+    }
+}
+
+impl<F> fmt::Debug for FnBrain<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FnBrain")
+    }
+}
+
+impl<F> Brain for FnBrain<F>
+where
+    F: FnMut(&Senses) -> Vec<Action> + Clone + 'static,
+{
+    fn think(&mut self, senses: &Senses) -> Vec<Action> {
+        (self.0)(senses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ant::Pheromones, dsl::parse_program, shape::Rect};
+
+    fn ant<B: Brain>(pos: Vec2, brain: B) -> Ant {
+        let mut a = Ant::new(pos, brain);
+        a.set_max_speed(0.0);
+        a
+    }
+
+    #[test]
+    fn senses() {
+        let mut env = Environment::new();
+        env.add(Rect::new(vec2(10.0, 5.0), vec2(2.0, 10.0)), 1);
+        env.add(Pheromones::new(vec2(-3.0, 0.0), 1.0), 2);
+        env.add(Pheromones::new(vec2(-20.0, 0.0), 5.0), 2);
+        env.add(Pheromones::new(vec2(0.0, 1.0), 4.0), 2);
+        let mut a = ant(vec2(0.0, 0.0), Program::default());
+        a.set_food(1.5);
+
+        let s = env.senses(&a);
+        assert_eq!((s.pos(), s.food(), s.has_food(), s.dest()), (vec2(0.0, 0.0), 1.5, true, None));
+        assert_eq!(s.location(Location::PheromoneSrc), Some(vec2(0.0, 1.0)));
+        assert_eq!(s.location(Location::NearestWall), Some(vec2(10.0, 0.0)));
+        assert_eq!(s.location(Location::Dest), None);
+        assert_eq!(s.pheromone(), 4.0);
+        let [left, ahead, right] = s.antennae();
+        assert_eq!(ahead, 2.0);
+        assert!((left - right).abs() < 1e-5 && left < ahead);
+        assert_eq!(s.evaluate(&Source::Dist(Location::NearestWall)), Memory::Number(10.0));
+        assert!(s.holds(&Condition::GreaterThan(Source::Food, Source::Number(1.0))));
+        assert!((0.0..1.0).contains(&s.random()));
+    }
+
+    #[test]
+    fn programs_see_their_own_changes() {
+        let env = Environment::new();
+        let mut program = parse_program("
+            always remember(5)
+            if memory(0) == 5 then set_dest((3, 4))
+            if dist(dest) == 5 then forget, emit_pheromone(dist(dest))
+            if memory(0) == 5 then emit_pheromone(-1)
+        ").unwrap();
+        let a = ant(vec2(0.0, 0.0), program.clone());
+
+        let expected = [
+            Action::Remember(Memory::Number(5.0)),
+            Action::SetDest(vec2(3.0, 4.0)),
+            Action::Forget,
+            Action::EmitPheromone(5.0),
+        ];
+        assert_eq!(program.think(&env.senses(&a)), expected);
+        // but the ant itself doesn't change until the world does what they say
+        assert!(a.memory.is_empty() && a.dest.is_none());
+
+        program.compile().unwrap();
+        assert_eq!(program.think(&env.senses(&a)), expected);
+    }
+
+    #[test]
+    fn side_by_side() {
+        let mut env = Environment::new();
+        let program = parse_program("always remember(food)").unwrap();
+        let mut steps = 0;
+        let counter = FnBrain::new(move |s: &Senses| {
+            steps += 1;
+            match steps {
+                2 => vec![Action::SetDest(s.home() + vec2(5.0, 0.0)), Action::EmitPheromone(2.0)],
+                _ => vec![Action::Remember(Memory::Number(steps as f32))],
+            }
+        });
+
+        let p = env.add_ant(ant(vec2(0.0, 0.0), program), 4).unwrap();
+        let c = env.add_ant(ant(vec2(20.0, 0.0), counter), 4).unwrap();
+        for _ in 0..3 {
+            env.step();
+        }
+
+        let p = p.borrow().downcast_ref::<Ant>().unwrap().clone();
+        let c = c.borrow().downcast_ref::<Ant>().unwrap().clone();
+        assert!(p.program().is_some() && c.program().is_none());
+        assert_eq!(p.memory.len(), 3);
+        assert_eq!(c.memory.iter().copied().collect::<Vec<_>>(), [Memory::Number(1.0), Memory::Number(3.0)]);
+        assert_eq!(c.dest(), Some(vec2(25.0, 0.0)));
+        assert_eq!(env.senses(&c).location(Location::PheromoneSrc), Some(vec2(20.0, 0.0)));
+
+        // copies of an ant get their own copy of the brain and whatever it keeps
+        let (mut c, mut copy, body) = (c.clone(), c.clone(), c);
+        copy.brain_mut().think(&env.senses(&body));
+        assert_eq!(c.brain_mut().think(&env.senses(&body)), [Action::Remember(Memory::Number(4.0))]);
+        assert_eq!(copy.brain_mut().think(&env.senses(&body)), [Action::Remember(Memory::Number(5.0))]);
+    }
+}
@@ -0,0 +1,1014 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// A little language for writing ant brains, one decision per line (or separated by ;), e.g.
+//
+//     # head home with food, otherwise follow the smell
+//     if food > 0 then set_dest(home)
+//     if pheromone > 2 then remember(loc(here)), set_dest(pheromone_src)
+//     always emit_pheromone(1.5)
+//
+// Sources are dist(loc), loc(loc), food, memory(n), recall(name), pheromone, tick, true, false
+// and numbers, combined with + - * /, min(a, b), max(a, b), abs(a), dot(a, b), length(a) and
+// normalize(a). thing(nearest_wall) and thing(pheromone_src) are the things themselves rather
+// than where they are, so they can be remembered and found again later with where(src).
+// Conditions compare sources with > < ==, or use approx(a, b, epsilon) and between(x, lo, hi),
+// and can be combined with not, and, xor and or, binding in that order.
+// Locations are home, dest, here, pheromone_src, nearest_wall and (x, y).
+// Actions are set_dest(loc), emit_pheromone(src), remember(src), forget and nothing,
+// remember and forget can be followed by another action after a comma. With a name they work
+// on a register instead of the memory list, like remember(loc(here) as food_spot) and forget(food_spot).
+//
+// A whole program can also start with `policy all`, `policy first` or `policy random`,
+// and decisions can be given a weight for the random policy with `weight 2.5 if ...`.
+//
+// State machines are a list of states, each a program of its own plus when to leave it:
+//
+//     state searching
+//         always set_dest(pheromone_src)
+//         when food > 0 go returning
+//     state returning
+//         always set_dest(home), emit_pheromone(2)
+//         when food == 0 go searching
+//
+// Inside a state machine, `state` is the number of the state it's in, counting from 0.
+
+use std::{fmt, vec::Vec};
+use glm::vec2;
+
+use crate::{ant::{Condition, Decision, Location, Policy, Program, Source, Then}, fsm::Fsm};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Word(String),
+    Number(f32),
+    Punct(&'static str),
+    End, // end of a decision, a newline or ;
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    tok: Tok,
+    line: usize,
+    col: usize,
+}
+
+const PUNCT: [&str; 11] = ["==", ">", "<", "=", "(", ")", ",", "+", "-", "*", "/"];
+
+fn lex(src: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+
+    for (l, text) in src.lines().enumerate() {
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let (line, col) = (l + 1, i + 1);
+
+            if c == '#' {
+                break;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == ';' {
+                tokens.push(Token { tok: Tok::End, line, col });
+                i += 1;
+            } else if c.is_ascii_digit() || c == '.' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let s: String = chars[start..i].iter().collect();
+                let n = s.parse().map_err(|_| ParseError { line, col, message: format!("bad number `{}`", s) })?;
+                tokens.push(Token { tok: Tok::Number(n), line, col });
+            } else if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token { tok: Tok::Word(chars[start..i].iter().collect()), line, col });
+            } else {
+                let rest: String = chars[i..].iter().take(2).collect();
+                let Some(p) = PUNCT.iter().find(|p| rest.starts_with(**p)) else {
+                    return Err(ParseError { line, col, message: format!("unexpected `{}`", c) });
+                };
+                tokens.push(Token { tok: Tok::Punct(p), line, col });
+                i += p.len();
+            }
+        }
+
+        tokens.push(Token { tok: Tok::End, line: l + 1, col: chars.len() + 1 });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        self.tokens.get(self.at).map_or(&Tok::End, |t| &t.tok)
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        let (line, col) = match self.tokens.get(self.at).or(self.tokens.last()) {
+            Some(t) => (t.line, t.col),
+            None => (1, 1),
+        };
+        ParseError { line, col, message }
+    }
+
+    fn describe(tok: &Tok) -> String {
+        match tok {
+            Tok::Word(w) => format!("`{}`", w),
+            Tok::Number(n) => format!("`{}`", n),
+            Tok::Punct(p) => format!("`{}`", p),
+            Tok::End => "the end of the line".to_string(),
+        }
+    }
+
+    fn next(&mut self) -> Tok {
+        let t = self.peek().clone();
+        self.at += 1;
+        t
+    }
+
+    fn eat(&mut self, p: &str) -> bool {
+        if *self.peek() == Tok::Punct(PUNCT.into_iter().find(|q| *q == p).unwrap()) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), ParseError> {
+        if self.eat(p) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found {}", p, Self::describe(self.peek()))))
+        }
+    }
+
+    fn eat_word(&mut self, w: &str) -> bool {
+        if matches!(self.peek(), Tok::Word(x) if x == w) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_word(&mut self, w: &str) -> Result<(), ParseError> {
+        if self.eat_word(w) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found {}", w, Self::describe(self.peek()))))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        match self.next() {
+            Tok::Word(w) => Ok(w),
+            t => {
+                self.at -= 1;
+                Err(self.error(format!("expected a name, found {}", Self::describe(&t))))
+            },
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), ParseError> {
+        if *self.peek() != Tok::End {
+            return Err(self.error(format!("expected the end of the line, found {}", Self::describe(self.peek()))));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<f32, ParseError> {
+        let neg = self.eat("-");
+        match self.next() {
+            Tok::Number(n) => Ok(if neg { -n } else { n }),
+            t => {
+                self.at -= 1;
+                Err(self.error(format!("expected a number, found {}", Self::describe(&t))))
+            },
+        }
+    }
+
+    fn decision(&mut self) -> Result<Decision, ParseError> {
+        let d = if self.eat_word("always") {
+            Decision::Always(self.then()?)
+        } else if self.eat_word("if") {
+            if self.eat_word("have_food") {
+                self.expect_word("then")?;
+                Decision::IfHaveFood(self.then()?)
+            } else {
+                let c = self.condition()?;
+                self.expect_word("then")?;
+                Decision::If(c, self.then()?)
+            }
+        } else {
+            return Err(self.error(format!("expected `if` or `always`, found {}", Self::describe(self.peek()))));
+        };
+
+        if *self.peek() != Tok::End {
+            return Err(self.error(format!("expected the end of the decision, found {}", Self::describe(self.peek()))));
+        }
+        Ok(d)
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let mut c = self.xor_condition()?;
+        while self.eat_word("or") {
+            c = Condition::Or(Box::new(c), Box::new(self.xor_condition()?));
+        }
+        Ok(c)
+    }
+
+    fn xor_condition(&mut self) -> Result<Condition, ParseError> {
+        let mut c = self.and_condition()?;
+        while self.eat_word("xor") {
+            c = Condition::Xor(Box::new(c), Box::new(self.and_condition()?));
+        }
+        Ok(c)
+    }
+
+    fn and_condition(&mut self) -> Result<Condition, ParseError> {
+        let mut c = self.not_condition()?;
+        while self.eat_word("and") {
+            c = Condition::And(Box::new(c), Box::new(self.not_condition()?));
+        }
+        Ok(c)
+    }
+
+    fn not_condition(&mut self) -> Result<Condition, ParseError> {
+        if self.eat_word("not") {
+            return Ok(Condition::Not(Box::new(self.not_condition()?)));
+        }
+
+        if *self.peek() != Tok::Punct("(") {
+            return self.comparison();
+        }
+
+        let start = self.at;
+        self.at += 1;
+        let first = match self.condition() {
+            Ok(c) => match self.expect(")") {
+                Ok(()) => return Ok(c),
+                Err(e) => e,
+            },
+            Err(e) => e,
+        };
+
+        // not a condition in parentheses, so it's a comparison that starts with a source in them,
+        // whichever attempt got further has the more useful error
+        self.at = start;
+        self.comparison().map_err(|e| if (e.line, e.col) >= (first.line, first.col) { e } else { first })
+    }
+
+    fn comparison(&mut self) -> Result<Condition, ParseError> {
+        let called = |p: &Parser, name: &str| {
+            matches!(p.peek(), Tok::Word(w) if w == name) && matches!(p.tokens.get(p.at + 1), Some(t) if t.tok == Tok::Punct("("))
+        };
+
+        if called(self, "between") {
+            self.at += 2;
+            let x = self.source()?;
+            self.expect(",")?;
+            let lo = self.source()?;
+            self.expect(",")?;
+            let hi = self.source()?;
+            self.expect(")")?;
+            return Ok(Condition::Between(x, lo, hi));
+        }
+
+        if called(self, "approx") {
+            self.at += 2;
+            let a = self.source()?;
+            self.expect(",")?;
+            let b = self.source()?;
+            self.expect(",")?;
+            let eps = self.number()?;
+            self.expect(")")?;
+            return Ok(Condition::ApproxEqual(a, b, eps));
+        }
+
+        let a = self.source()?;
+        let op: fn(Source, Source) -> Condition = match self.peek() {
+            Tok::Punct(">") => Condition::GreaterThan,
+            Tok::Punct("<") => Condition::LessThan,
+            Tok::Punct("==" | "=") => Condition::Equal,
+            t => return Err(self.error(format!("expected a comparison, found {}", Self::describe(t)))),
+        };
+        self.at += 1;
+        Ok(op(a, self.source()?))
+    }
+
+    fn source(&mut self) -> Result<Source, ParseError> {
+        let mut s = self.term()?;
+        loop {
+            let op: fn(Box<Source>, Box<Source>) -> Source = match self.peek() {
+                Tok::Punct("+") => Source::Add,
+                Tok::Punct("-") => Source::Sub,
+                _ => return Ok(s),
+            };
+            self.at += 1;
+            s = op(Box::new(s), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Source, ParseError> {
+        let mut s = self.factor()?;
+        loop {
+            let op: fn(Box<Source>, Box<Source>) -> Source = match self.peek() {
+                Tok::Punct("*") => Source::Mul,
+                Tok::Punct("/") => Source::Div,
+                _ => return Ok(s),
+            };
+            self.at += 1;
+            s = op(Box::new(s), Box::new(self.factor()?));
+        }
+    }
+
+    fn args(&mut self, n: usize) -> Result<Vec<Source>, ParseError> {
+        self.expect("(")?;
+        let mut args = Vec::with_capacity(n);
+        for i in 0..n {
+            if i > 0 {
+                self.expect(",")?;
+            }
+            args.push(self.source()?);
+        }
+        self.expect(")")?;
+        Ok(args)
+    }
+
+    fn factor(&mut self) -> Result<Source, ParseError> {
+        match self.peek().clone() {
+            Tok::Number(_) => Ok(Source::Number(self.number()?)),
+            Tok::Punct("-") => match self.tokens.get(self.at + 1).map(|t| &t.tok) {
+                Some(Tok::Number(_)) => Ok(Source::Number(self.number()?)),
+                // there's no negation, so -x is 0 - x
+                _ => {
+                    self.at += 1;
+                    Ok(Source::Sub(Box::new(Source::Number(0.0)), Box::new(self.factor()?)))
+                },
+            },
+            Tok::Punct("(") => {
+                self.at += 1;
+                let s = self.source()?;
+                self.expect(")")?;
+                Ok(s)
+            },
+            Tok::Word(w) => {
+                self.at += 1;
+                match w.as_str() {
+                    "food" => Ok(Source::Food),
+                    "pheromone" => Ok(Source::PheromoneStrength),
+                    "state" => Ok(Source::State),
+                    "tick" => Ok(Source::Tick),
+                    "true" => Ok(Source::Bool(true)),
+                    "false" => Ok(Source::Bool(false)),
+                    "thing" => {
+                        self.expect("(")?;
+                        let l = self.location()?;
+                        self.expect(")")?;
+                        Ok(Source::Thing(l))
+                    },
+                    "where" => Ok(Source::Where(Box::new(self.args(1)?.pop().unwrap()))),
+                    "recall" => {
+                        self.expect("(")?;
+                        let name = self.name()?;
+                        self.expect(")")?;
+                        Ok(Source::Register(name))
+                    },
+                    "dist" | "loc" => {
+                        self.expect("(")?;
+                        let l = self.location()?;
+                        self.expect(")")?;
+                        Ok(if w == "dist" { Source::Dist(l) } else { Source::Loc(l) })
+                    },
+                    "memory" => {
+                        self.expect("(")?;
+                        let n = self.number()?;
+                        if n.fract() != 0.0 {
+                            self.at -= 1;
+                            return Err(self.error(format!("memory index `{}` isn't a whole number", n)));
+                        }
+                        self.expect(")")?;
+                        Ok(Source::Memory(n as i32))
+                    },
+                    "min" | "max" | "dot" => {
+                        let mut a = self.args(2)?;
+                        let (y, x) = (Box::new(a.pop().unwrap()), Box::new(a.pop().unwrap()));
+                        Ok(match w.as_str() {
+                            "min" => Source::Min(x, y),
+                            "max" => Source::Max(x, y),
+                            _ => Source::Dot(x, y),
+                        })
+                    },
+                    "abs" | "length" | "normalize" => {
+                        let x = Box::new(self.args(1)?.pop().unwrap());
+                        Ok(match w.as_str() {
+                            "abs" => Source::Abs(x),
+                            "length" => Source::Length(x),
+                            _ => Source::Normalize(x),
+                        })
+                    },
+                    _ => {
+                        self.at -= 1;
+                        Err(self.error(format!("unknown source `{}`", w)))
+                    },
+                }
+            },
+            t => Err(self.error(format!("expected a source, found {}", Self::describe(&t)))),
+        }
+    }
+
+    fn location(&mut self) -> Result<Location, ParseError> {
+        if self.eat("(") {
+            let x = self.number()?;
+            self.expect(",")?;
+            let y = self.number()?;
+            self.expect(")")?;
+            return Ok(Location::Pos(vec2(x, y)));
+        }
+
+        let l = match self.peek() {
+            Tok::Word(w) => match w.as_str() {
+                "home" => Location::Home,
+                "dest" => Location::Dest,
+                "here" => Location::Here,
+                "pheromone_src" => Location::PheromoneSrc,
+                "nearest_wall" => Location::NearestWall,
+                _ => return Err(self.error(format!("unknown location `{}`", w))),
+            },
+            t => return Err(self.error(format!("expected a location, found {}", Self::describe(t)))),
+        };
+        self.at += 1;
+        Ok(l)
+    }
+
+    fn then(&mut self) -> Result<Then, ParseError> {
+        let w = match self.peek() {
+            Tok::Word(w) => w.clone(),
+            t => return Err(self.error(format!("expected an action, found {}", Self::describe(t)))),
+        };
+        self.at += 1;
+
+        // actions that something else can follow
+        let chained = |p: &mut Parser| -> Result<Box<Then>, ParseError> {
+            Ok(Box::new(if p.eat(",") { p.then()? } else { Then::Nothing }))
+        };
+
+        match w.as_str() {
+            "nothing" => Ok(Then::Nothing),
+            "set_dest" => {
+                // plain set_dest plans a fresh route to wherever the ant is already going
+                if self.eat("(") {
+                    let l = self.location()?;
+                    self.expect(")")?;
+                    Ok(Then::SetDest(l))
+                } else {
+                    Ok(Then::SetDest(Location::Dest))
+                }
+            },
+            "emit_pheromone" => {
+                self.expect("(")?;
+                let s = self.source()?;
+                self.expect(")")?;
+                Ok(Then::EmitPheromone(s))
+            },
+            "remember" => {
+                self.expect("(")?;
+                let s = self.source()?;
+                if self.eat_word("as") {
+                    let name = self.name()?;
+                    self.expect(")")?;
+                    return Ok(Then::Store(name, s, chained(self)?));
+                }
+                self.expect(")")?;
+                Ok(Then::Remember(s, chained(self)?))
+            },
+            "forget" => {
+                if self.eat("(") {
+                    let name = self.name()?;
+                    self.expect(")")?;
+                    return Ok(Then::Clear(name, chained(self)?));
+                }
+                Ok(Then::Forget(chained(self)?))
+            },
+            _ => {
+                self.at -= 1;
+                Err(self.error(format!("unknown action `{}`", w)))
+            },
+        }
+    }
+}
+
+impl Parser {
+    // one line of a program, either its policy or a decision
+    fn program_line(&mut self, program: &mut Program) -> Result<(), ParseError> {
+        if self.eat_word("policy") {
+            if !program.is_empty() {
+                self.at -= 1;
+                return Err(self.error("the policy has to come before any decisions".to_string()));
+            }
+
+            program.set_policy(match self.peek() {
+                Tok::Word(w) if w == "all" => Policy::All,
+                Tok::Word(w) if w == "first" => Policy::First,
+                Tok::Word(w) if w == "random" => Policy::WeightedRandom,
+                t => return Err(self.error(format!("expected `all`, `first` or `random`, found {}", Self::describe(t)))),
+            });
+            self.at += 1;
+            return self.expect_end();
+        }
+
+        let weight = if self.eat_word("weight") { self.number()? } else { 1.0 };
+        if weight < 0.0 {
+            self.at -= 1;
+            return Err(self.error("weights can't be negative".to_string()));
+        }
+        program.push_weighted(self.decision()?, weight);
+        Ok(())
+    }
+}
+
+// Parses a whole program, blank lines and # comments are skipped
+pub fn parse_program(src: &str) -> Result<Program, ParseError> {
+    let mut p = Parser { tokens: lex(src)?, at: 0 };
+    let mut program = Program::default();
+
+    while p.at < p.tokens.len() {
+        if *p.peek() == Tok::End {
+            p.at += 1;
+            continue;
+        }
+        p.program_line(&mut program)?;
+    }
+
+    Ok(program)
+}
+
+// Parses a state machine, which starts in the first state
+pub fn parse_fsm(src: &str) -> Result<Fsm, ParseError> {
+    let mut p = Parser { tokens: lex(src)?, at: 0 };
+    let mut fsm = Fsm::new();
+    // transitions can go to states further down, so they're only added at the end
+    let mut transitions = Vec::new();
+
+    while p.at < p.tokens.len() {
+        if *p.peek() == Tok::End {
+            p.at += 1;
+            continue;
+        }
+
+        if p.eat_word("state") {
+            let at = p.at;
+            let name = p.name()?;
+            if fsm.find(&name).is_some() {
+                p.at = at;
+                return Err(p.error(format!("there's already a state called `{}`", name)));
+            }
+            fsm.add_state(&name, Program::default());
+            p.expect_end()?;
+            continue;
+        }
+
+        let Some(from) = fsm.states().len().checked_sub(1) else {
+            return Err(p.error(format!("expected `state`, found {}", Parser::describe(p.peek()))));
+        };
+
+        if p.eat_word("when") {
+            let when = p.condition()?;
+            p.expect_word("go")?;
+            let at = p.at;
+            transitions.push((from, when, p.name()?, at));
+            p.expect_end()?;
+        } else {
+            p.program_line(&mut fsm.states[from].program)?;
+        }
+    }
+
+    for (from, when, name, at) in transitions {
+        let Some(to) = fsm.find(&name) else {
+            p.at = at;
+            return Err(p.error(format!("there's no state called `{}`", name)));
+        };
+        fsm.add_transition(from, when, to);
+    }
+    Ok(fsm)
+}
+
+// Parses a list of decisions, ignoring any policy and weights
+pub fn parse(src: &str) -> Result<Vec<Decision>, ParseError> {
+    Ok(parse_program(src)?.decisions().to_vec())
+}
+
+// Parses exactly one decision
+pub fn parse_decision(src: &str) -> Result<Decision, ParseError> {
+    let mut d = parse(src)?;
+    match d.len() {
+        1 => Ok(d.remove(0)),
+        0 => Err(ParseError { line: 1, col: 1, message: "expected a decision".to_string() }),
+        _ => Err(ParseError { line: 1, col: 1, message: "expected only one decision".to_string() }),
+    }
+}
+
+// The text version of a brain, which parses back into the same thing
+pub fn print(decisions: &[Decision]) -> String {
+    decisions.iter().map(|d| format!("{}\n", d)).collect()
+}
+
+pub fn print_program(program: &Program) -> String {
+    program.to_string()
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Home => write!(f, "home"),
+            Location::Dest => write!(f, "dest"),
+            Location::Here => write!(f, "here"),
+            Location::PheromoneSrc => write!(f, "pheromone_src"),
+            Location::NearestWall => write!(f, "nearest_wall"),
+            Location::Pos(p) => write!(f, "({}, {})", p.x, p.y),
+        }
+    }
+}
+
+// how tightly things bind when printed, anything looser than its spot needs parentheses
+fn source_prec(s: &Source) -> u8 {
+    match s {
+        Source::Add(..) | Source::Sub(..) => 1,
+        Source::Mul(..) | Source::Div(..) => 2,
+        _ => 3,
+    }
+}
+
+fn condition_prec(c: &Condition) -> u8 {
+    match c {
+        Condition::Or(..) => 1,
+        Condition::Xor(..) => 2,
+        Condition::And(..) => 3,
+        Condition::Not(..) => 4,
+        _ => 5,
+    }
+}
+
+struct Operand<'a, T>(&'a T, u8);
+
+impl fmt::Display for Operand<'_, Source> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if source_prec(self.0) < self.1 {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl fmt::Display for Operand<'_, Condition> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if condition_prec(self.0) < self.1 {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // operators are left associative, so the right side needs parentheses even at the same level
+        let binary = |f: &mut fmt::Formatter<'_>, a: &Source, op: &str, b: &Source, p: u8| {
+            write!(f, "{} {} {}", Operand(a, p), op, Operand(b, p + 1))
+        };
+
+        match self {
+            Source::Dist(l) => write!(f, "dist({})", l),
+            Source::Loc(l) => write!(f, "loc({})", l),
+            Source::Food => write!(f, "food"),
+            Source::Number(n) => write!(f, "{}", n),
+            Source::Memory(i) => write!(f, "memory({})", i),
+            Source::PheromoneStrength => write!(f, "pheromone"),
+            Source::State => write!(f, "state"),
+            Source::Bool(b) => write!(f, "{}", b),
+            Source::Tick => write!(f, "tick"),
+            Source::Thing(l) => write!(f, "thing({})", l),
+            Source::Where(a) => write!(f, "where({})", a),
+            Source::Register(name) => write!(f, "recall({})", name),
+            Source::Add(a, b) => binary(f, a, "+", b, 1),
+            Source::Sub(a, b) => binary(f, a, "-", b, 1),
+            Source::Mul(a, b) => binary(f, a, "*", b, 2),
+            Source::Div(a, b) => binary(f, a, "/", b, 2),
+            Source::Min(a, b) => write!(f, "min({}, {})", a, b),
+            Source::Max(a, b) => write!(f, "max({}, {})", a, b),
+            Source::Abs(a) => write!(f, "abs({})", a),
+            Source::Dot(a, b) => write!(f, "dot({}, {})", a, b),
+            Source::Length(a) => write!(f, "length({})", a),
+            Source::Normalize(a) => write!(f, "normalize({})", a),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let binary = |f: &mut fmt::Formatter<'_>, a: &Condition, op: &str, b: &Condition, p: u8| {
+            write!(f, "{} {} {}", Operand(a, p), op, Operand(b, p + 1))
+        };
+
+        match self {
+            Condition::GreaterThan(a, b) => write!(f, "{} > {}", a, b),
+            Condition::LessThan(a, b) => write!(f, "{} < {}", a, b),
+            Condition::Equal(a, b) => write!(f, "{} == {}", a, b),
+            Condition::ApproxEqual(a, b, eps) => write!(f, "approx({}, {}, {})", a, b, eps),
+            Condition::Between(x, lo, hi) => write!(f, "between({}, {}, {})", x, lo, hi),
+            Condition::Not(c) => write!(f, "not {}", Operand(&**c, 4)),
+            Condition::And(a, b) => binary(f, a, "and", b, 3),
+            Condition::Xor(a, b) => binary(f, a, "xor", b, 2),
+            Condition::Or(a, b) => binary(f, a, "or", b, 1),
+        }
+    }
+}
+
+impl fmt::Display for Then {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Then::SetDest(Location::Dest) => write!(f, "set_dest"),
+            Then::SetDest(l) => write!(f, "set_dest({})", l),
+            Then::EmitPheromone(s) => write!(f, "emit_pheromone({})", s),
+            Then::Remember(s, t) => match **t {
+                Then::Nothing => write!(f, "remember({})", s),
+                _ => write!(f, "remember({}), {}", s, t),
+            },
+            Then::Forget(t) => match **t {
+                Then::Nothing => write!(f, "forget"),
+                _ => write!(f, "forget, {}", t),
+            },
+            Then::Store(name, s, t) => match **t {
+                Then::Nothing => write!(f, "remember({} as {})", s, name),
+                _ => write!(f, "remember({} as {}), {}", s, name, t),
+            },
+            Then::Clear(name, t) => match **t {
+                Then::Nothing => write!(f, "forget({})", name),
+                _ => write!(f, "forget({}), {}", name, t),
+            },
+            Then::Nothing => write!(f, "nothing"),
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::If(c, t) => write!(f, "if {} then {}", c, t),
+            Decision::IfHaveFood(t) => write!(f, "if have_food then {}", t),
+            Decision::Always(t) => write!(f, "always {}", t),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.policy {
+            Policy::All => (),
+            Policy::First => writeln!(f, "policy first")?,
+            Policy::WeightedRandom => writeln!(f, "policy random")?,
+        }
+
+        for (d, w) in self.decisions.iter().zip(self.weights.iter()) {
+            if *w != 1.0 {
+                write!(f, "weight {} ", w)?;
+            }
+            writeln!(f, "{}", d)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Fsm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in self.states() {
+            writeln!(f, "state {}", s.name())?;
+            for line in s.program().to_string().lines() {
+                writeln!(f, "    {}", line)?;
+            }
+            for (when, to) in s.transitions() {
+                writeln!(f, "    when {} go {}", when, self.states()[*to].name())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BRAIN: &str = "
+        # a forager
+        if have_food then set_dest(home)
+        if dist(home) > 5 then set_dest
+        if not pheromone < 2.5 then remember(loc(here)), forget, set_dest(pheromone_src)
+        if memory(0) == loc((3, -4.25)) then emit_pheromone(food)
+        always remember(dist(nearest_wall)); if (-1 = food) then nothing
+    ";
+
+    #[test]
+    fn example() {
+        let d = parse_decision("if dist(home) > 5 then set_dest").unwrap();
+        assert!(matches!(d, Decision::If(
+            Condition::GreaterThan(Source::Dist(Location::Home), Source::Number(n)),
+            Then::SetDest(Location::Dest),
+        ) if n == 5.0));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let parsed = parse(BRAIN).unwrap();
+        assert_eq!(parsed.len(), 6);
+        assert!(matches!(&parsed[2], Decision::If(Condition::Not(_), Then::Remember(_, t)) if matches!(**t, Then::Forget(_))));
+
+        let printed = print(&parsed);
+        let again = parse(&printed).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", again));
+        assert_eq!(printed, print(&again));
+        assert!(printed.contains("if memory(0) == loc((3, -4.25)) then emit_pheromone(food)\n"));
+    }
+
+    #[test]
+    fn expressions() {
+        let d = parse_decision("if food + 1 * 2 > 3 and not dist(home) < 2 or pheromone == 0 then nothing").unwrap();
+        let Decision::If(Condition::Or(a, _), _) = &d else { panic!("{:?}", d) };
+        let Condition::And(a, b) = &**a else { panic!("{:?}", a) };
+        assert!(matches!(&**a, Condition::GreaterThan(Source::Add(_, m), _) if matches!(**m, Source::Mul(..))));
+        assert!(matches!(&**b, Condition::Not(_)));
+
+        let d = parse_decision("if (food - 1) * -2 > -food then nothing").unwrap();
+        let Decision::If(Condition::GreaterThan(a, b), _) = &d else { panic!("{:?}", d) };
+        assert!(matches!(a, Source::Mul(x, y) if matches!(**x, Source::Sub(..)) && matches!(**y, Source::Number(n) if n == -2.0)));
+        assert!(matches!(b, Source::Sub(x, _) if matches!(**x, Source::Number(n) if n == 0.0)));
+
+        let src = "
+            if (food > 1 or food < 0) and not (pheromone > 2 xor pheromone < 1) then forget
+            if a_or_b > 0 then forget
+            if approx(length(normalize(loc(here) - loc(home))), 1, 0.001) then nothing
+            if between(dot(loc(here), loc((1, 0))), min(food, 2), max(abs(food - 3), 1 / (2 / 3))) then nothing
+            if (1 - (2 - 3)) - 4 > 8 / (4 / 2) then nothing
+            if not not (food == 1 or food == 2) or food == 3 then nothing
+        ";
+        let parsed = parse(&src.replace("a_or_b", "(food - 1)"));
+        let parsed = parsed.unwrap();
+        let printed = print(&parsed);
+        assert_eq!(printed, "\
+if (food > 1 or food < 0) and not (pheromone > 2 xor pheromone < 1) then forget
+if food - 1 > 0 then forget
+if approx(length(normalize(loc(here) - loc(home))), 1, 0.001) then nothing
+if between(dot(loc(here), loc((1, 0))), min(food, 2), max(abs(food - 3), 1 / (2 / 3))) then nothing
+if 1 - (2 - 3) - 4 > 8 / (4 / 2) then nothing
+if not not (food == 1 or food == 2) or food == 3 then nothing
+");
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", parse(&printed).unwrap()));
+
+        // the error from whichever reading of the parentheses got further
+        let e = parse("if (food > fly) then nothing").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 12));
+        let e = parse("if min(food) > 1 then nothing").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 12));
+    }
+
+    #[test]
+    fn programs() {
+        let p = parse_program("policy random\nweight 3 if food > 0 then set_dest(home)\nalways forget\nweight 0.5 always nothing").unwrap();
+        assert_eq!(p.policy(), Policy::WeightedRandom);
+        assert_eq!(p.weights(), &[3.0, 1.0, 0.5]);
+
+        let printed = print_program(&p);
+        assert_eq!(printed, "policy random\nweight 3 if food > 0 then set_dest(home)\nalways forget\nweight 0.5 always nothing\n");
+        let again = parse_program(&printed).unwrap();
+        assert_eq!(format!("{:?}", p), format!("{:?}", again));
+
+        assert_eq!(parse_program(BRAIN).unwrap().policy(), Policy::All);
+        assert_eq!(parse_program("policy first").unwrap().policy(), Policy::First);
+
+        let e = parse_program("always nothing\npolicy first").unwrap_err();
+        assert_eq!((e.line, e.col), (2, 1));
+        let e = parse_program("policy sometimes").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 8));
+        let e = parse_program("weight -1 always nothing").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 9));
+    }
+
+    #[test]
+    fn state_machines() {
+        let src = "
+            state searching
+                always set_dest(pheromone_src)
+                when food > 0 go returning
+            state returning
+                policy first
+                if state == 1 then set_dest(home)
+                weight 2 always emit_pheromone(2)
+                when food == 0 go searching
+                when dist(home) < 1 go searching
+        ";
+        let fsm = parse_fsm(src).unwrap();
+        assert_eq!(fsm.states().len(), 2);
+        assert_eq!(fsm.current_state().unwrap().name(), "searching");
+        let returning = &fsm.states()[1];
+        assert_eq!((returning.program().policy(), returning.program().weights()), (Policy::First, &[1.0, 2.0][..]));
+        assert_eq!(returning.transitions().iter().map(|(_, to)| *to).collect::<Vec<_>>(), [0, 0]);
+
+        let printed = fsm.to_string();
+        assert!(printed.starts_with("state searching\n    always set_dest(pheromone_src)\n    when food > 0 go returning\n"));
+        assert_eq!(parse_fsm(&printed).unwrap().to_string(), printed);
+
+        let e = parse_fsm("always nothing").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 1));
+        let e = parse_fsm("state a\nstate a").unwrap_err();
+        assert_eq!((e.line, e.col), (2, 7));
+        let e = parse_fsm("state a\nwhen food > 0 go b").unwrap_err();
+        assert_eq!((e.line, e.col), (2, 18));
+        assert_eq!(e.message, "there's no state called `b`");
+        let e = parse_fsm("state a\nwhen food > 0 go a now").unwrap_err();
+        assert_eq!((e.line, e.col), (2, 20));
+    }
+
+    #[test]
+    fn registers() {
+        let src = "
+            if recall(spot) == true then remember(loc(here) as food_spot), forget, remember(tick as at)
+            if dist(here) < 1 then forget(food_spot), forget(at)
+            always remember(where(thing(pheromone_src)) - loc(here) as smell)
+        ";
+        let parsed = parse(src).unwrap();
+        let Decision::If(Condition::Equal(a, b), Then::Store(name, s, t)) = &parsed[0] else { panic!("{:?}", parsed[0]) };
+        assert!(matches!(a, Source::Register(n) if n == "spot") && matches!(b, Source::Bool(true)));
+        assert!(name == "food_spot" && matches!(s, Source::Loc(Location::Here)));
+        assert!(matches!(&**t, Then::Forget(t) if matches!(&**t, Then::Store(n, Source::Tick, _) if n == "at")));
+        assert!(matches!(&parsed[1], Decision::If(_, Then::Clear(n, t)) if n == "food_spot" && matches!(**t, Then::Clear(..))));
+
+        let printed = print(&parsed);
+        assert_eq!(print(&parse(&printed).unwrap()), printed);
+        assert!(printed.contains("then forget(food_spot), forget(at)\n"));
+        assert!(printed.contains("always remember(where(thing(pheromone_src)) - loc(here) as smell)\n"));
+
+        let e = parse("always remember(food as 3)").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 25));
+        let e = parse("if recall() > 1 then nothing").unwrap_err();
+        assert_eq!(e.message, "expected a name, found `)`");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |src: &str| parse(src).unwrap_err();
+
+        let e = err("always set_dest(home)\nif food >> 2 then nothing");
+        assert_eq!((e.line, e.col), (2, 10));
+
+        let e = err("if food > 2 then fly");
+        assert_eq!((e.line, e.col), (1, 18));
+        assert!(e.message.contains("fly"));
+
+        let e = err("\n\n   if dist(attic) < 1 then forget");
+        assert_eq!((e.line, e.col), (3, 12));
+        assert_eq!(e.to_string(), "3:12: unknown location `attic`");
+
+        let e = err("always set_dest(home), forget");
+        assert_eq!((e.line, e.col), (1, 22));
+
+        let e = err("if food dist(home) then nothing");
+        assert_eq!((e.line, e.col), (1, 9));
+
+        let e = err("if food > 1");
+        assert_eq!((e.line, e.col), (1, 12));
+
+        let e = err("always remember(memory(1.5))");
+        assert_eq!((e.line, e.col), (1, 24));
+
+        let e = err("always nothing $");
+        assert_eq!((e.line, e.col), (1, 16));
+    }
+}
@@ -0,0 +1,597 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// Evolving brains: every program in a population gets an episode in the same scenario, a
+// fitness function scores how its ants did, and tournaments pick who breeds the next generation.
+
+use std::{cell::RefCell, fmt, fs, io, path::{Path, PathBuf}, rc::Rc};
+
+use glm::{distance, vec2, Vec2};
+use rand::Rng;
+
+use crate::{
+    ant::{Ant, Program},
+    brain::Brain,
+    dsl::{parse_program, ParseError},
+    gp::{Gp, Limits},
+    loader::Obstacle,
+    shape::Shape,
+    world::Environment,
+};
+
+// ants pick up and drop off food this close to it, which is about their size
+const FOOD_REACH: f32 = 2.0;
+// ants start out spread around the nest this far from it
+const SPAWN_RADIUS: f32 = 1.0;
+const DEFAULT_ENERGY: f32 = 100.0;
+const DEFAULT_UPKEEP: f32 = 0.1;
+
+// The world every brain gets tried in, the same every time
+#[derive(Clone)]
+pub struct Scenario {
+    pub(crate) nest: Vec2,
+    pub(crate) food: Vec<Vec2>, // sources that never run out
+    pub(crate) obstacles: Vec<Obstacle>,
+    pub(crate) ants: usize,
+    pub(crate) ticks: usize,
+    pub(crate) energy: f32, // what an ant starts with, and gets back by bringing food home
+    pub(crate) upkeep: f32, // energy per unit of time just for being alive, moving costs one per unit
+    pub(crate) seed: u64,
+}
+
+// an ant in an episode, with what it has left and where it was last step
+struct Living {
+    ant: Rc<RefCell<dyn Shape>>,
+    energy: f32,
+    last: Vec2,
+}
+
+// How the ants running one brain did in a scenario
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Episode {
+    pub delivered: f32, // food brought home
+    pub energy_spent: f32,
+    pub survivors: usize, // ants that didn't run out of energy
+    pub ants: usize,
+    pub ticks: usize,
+}
+
+impl Scenario {
+    pub fn new(nest: Vec2, ants: usize, ticks: usize) -> Self {
+        Scenario {
+            nest,
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            ants,
+            ticks,
+            energy: DEFAULT_ENERGY,
+            upkeep: DEFAULT_UPKEEP,
+            seed: 0,
+        }
+    }
+
+    pub fn nest(&self) -> Vec2 {
+        self.nest
+    }
+
+    pub fn food(&self) -> &[Vec2] {
+        &self.food
+    }
+
+    pub fn add_food(&mut self, pos: Vec2) {
+        self.food.push(pos);
+    }
+
+    pub fn add_obstacles(&mut self, obstacles: Vec<Obstacle>) {
+        self.obstacles.extend(obstacles);
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn set_ticks(&mut self, ticks: usize) {
+        self.ticks = ticks;
+    }
+
+    pub fn set_energy(&mut self, energy: f32, upkeep: f32) {
+        self.energy = energy;
+        self.upkeep = upkeep.max(0.0);
+    }
+
+    // the seed for the environment, so wandering goes the same way every episode
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    // Runs the ants with this program for the scenario's ticks, compiled if it can be
+    pub fn run(&self, program: &Program) -> Episode {
+        // too deep to compile is still fine to walk
+        let mut program = program.clone();
+        let _ = program.compile();
+        self.run_brain(&program)
+    }
+
+    // Runs the ants with any brain, each ant gets its own copy. A brain that doesn't
+    // pass the type checker never gets any ants and scores an empty episode.
+    pub fn run_brain<B: Brain + Clone>(&self, brain: &B) -> Episode {
+        let mut episode = Episode { ants: self.ants, ..Default::default() };
+
+        let mut env = Environment::new();
+        env.set_seed(self.seed);
+        env.add_obstacles(self.obstacles.clone(), 3);
+
+        // ants don't bump into each other here, so they can all start at the nest
+        let mut ants: Vec<Living> = Vec::new();
+        for i in 0..self.ants {
+            let angle = (360.0 * i as f32 / self.ants as f32).to_radians();
+            let pos = self.nest + vec2(angle.sin(), angle.cos()) * SPAWN_RADIUS;
+            let mut ant = Ant::new(pos, brain.clone());
+            ant.set_home(self.nest);
+            match env.add_ant(ant, 4) {
+                Ok(ant) => ants.push(Living { ant, energy: self.energy, last: pos }),
+                Err(_) => return episode,
+            }
+        }
+
+        for _ in 0..self.ticks {
+            env.step();
+            let upkeep = self.upkeep * env.time_step();
+            ants.retain_mut(|Living { ant: a, energy, last }| {
+                let mut b = a.borrow_mut();
+                let ant = b.downcast_mut::<Ant>().unwrap();
+
+                let spent = distance(ant.pos, *last) + upkeep;
+                *energy -= spent;
+                *last = ant.pos;
+                episode.energy_spent += spent;
+
+                if !ant.has_food() && self.food.iter().any(|f| distance(*f, ant.pos) <= FOOD_REACH) {
+                    ant.set_food(1.0);
+                } else if ant.has_food() && distance(ant.home, ant.pos) <= FOOD_REACH {
+                    episode.delivered += ant.food;
+                    ant.set_food(0.0);
+                    *energy = self.energy;
+                }
+
+                if *energy > 0.0 {
+                    return true;
+                }
+                drop(b);
+                env.rm(Rc::clone(a));
+                false
+            });
+            episode.ticks += 1;
+        }
+
+        episode.survivors = ants.len();
+        episode
+    }
+}
+
+// Scores an episode, higher is fitter. Any Fn(&Episode) -> f32 works, for mixing these up.
+pub trait Fitness {
+    fn score(&self, episode: &Episode) -> f32;
+}
+
+impl<F: Fn(&Episode) -> f32> Fitness for F {
+    fn score(&self, episode: &Episode) -> f32 {
+        self(episode)
+    }
+}
+
+// a score that isn't a number counts as the worst there is
+pub(crate) fn score(fitness: &dyn Fitness, episode: &Episode) -> f32 {
+    let score = fitness.score(episode);
+    if score.is_nan() { f32::NEG_INFINITY } else { score }
+}
+
+// indices from the fittest down
+pub(crate) fn ranked(scores: &[f32]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    order
+}
+
+// the best of a few picked at random
+pub(crate) fn tournament<R: Rng>(rng: &mut R, scores: &[f32], size: usize) -> usize {
+    (0..size.max(1))
+        .map(|_| rng.gen_range(0..scores.len()))
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+        .unwrap()
+}
+
+pub struct FoodDelivered;
+
+impl Fitness for FoodDelivered {
+    fn score(&self, episode: &Episode) -> f32 {
+        episode.delivered
+    }
+}
+
+// the less spent the better, so it's negative
+pub struct EnergySpent;
+
+impl Fitness for EnergySpent {
+    fn score(&self, episode: &Episode) -> f32 {
+        -episode.energy_spent
+    }
+}
+
+// the fraction of ants still alive at the end
+pub struct Survival;
+
+impl Fitness for Survival {
+    fn score(&self, episode: &Episode) -> f32 {
+        if episode.ants == 0 {
+            return 0.0;
+        }
+        episode.survivors as f32 / episode.ants as f32
+    }
+}
+
+// How a population breeds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub population: usize,
+    pub tournament: usize, // how many compete for each parent
+    pub elites: usize, // the best few go to the next generation as they are
+    pub crossover: f64, // chance of a child having two parents
+    pub point_mutation: f64,
+    pub subtree_mutation: f64,
+    pub limits: Limits,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            population: 50,
+            tournament: 3,
+            elites: 1,
+            crossover: 0.7,
+            point_mutation: 0.2,
+            subtree_mutation: 0.2,
+            limits: Limits::default(),
+        }
+    }
+}
+
+// A population of brains being evolved in one scenario
+pub struct Evolution {
+    scenario: Scenario,
+    fitness: Box<dyn Fitness>,
+    settings: Settings,
+    gp: Gp,
+    generation: usize,
+    population: Vec<Program>,
+    scores: Option<Vec<f32>>, // for this generation, once it's been run
+    checkpoints: Option<PathBuf>,
+}
+
+impl Evolution {
+    // starts from random programs, the seed decides them and everything else random about breeding
+    pub fn new<F: Fitness + 'static>(scenario: Scenario, fitness: F, settings: Settings, seed: u64) -> Self {
+        assert!(settings.population > 0, "a population needs at least one program");
+        let mut gp = Gp::new(seed, settings.limits);
+        let population = (0..settings.population).map(|_| gp.random_program()).collect();
+        Evolution {
+            scenario,
+            fitness: Box::new(fitness),
+            settings,
+            gp,
+            generation: 0,
+            population,
+            scores: None,
+            checkpoints: None,
+        }
+    }
+
+    // picks up where a checkpoint left off, its generation gets run again before breeding.
+    // Checkpoints don't keep the breeding rng, so it starts over from seed: resuming with the same
+    // seed always goes the same way, but not the way the run that wrote the checkpoint went on.
+    pub fn resume<F: Fitness + 'static>(checkpoint: Checkpoint, scenario: Scenario, fitness: F, settings: Settings, seed: u64) -> Result<Self, CheckpointError> {
+        assert!(settings.population > 0, "a population needs at least one program");
+        if checkpoint.programs.is_empty() {
+            return Err(CheckpointError::Format("no programs to resume from".to_string()));
+        }
+
+        Ok(Evolution {
+            scenario,
+            fitness: Box::new(fitness),
+            settings,
+            gp: Gp::new(seed, settings.limits),
+            generation: checkpoint.generation,
+            population: checkpoint.programs,
+            scores: None,
+            checkpoints: None,
+        })
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn population(&self) -> &[Program] {
+        &self.population
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    // every generation gets written to dir once it's scored, see checkpoint
+    pub fn set_checkpoints<P: Into<PathBuf>>(&mut self, dir: Option<P>) {
+        self.checkpoints = dir.map(Into::into);
+    }
+
+    // Runs everyone in this generation that hasn't been yet and returns their scores,
+    // a score that isn't a number counts as the worst there is
+    pub fn evaluate(&mut self) -> &[f32] {
+        let scores = self.scores.get_or_insert_with(|| {
+            self.population.iter().map(|p| score(self.fitness.as_ref(), &self.scenario.run(p))).collect()
+        });
+        scores
+    }
+
+    // The fittest program of this generation and its score
+    pub fn best(&mut self) -> (&Program, f32) {
+        let scores = self.evaluate();
+        let (i, score) = scores.iter().copied().enumerate().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        (&self.population[i], score)
+    }
+
+    // Scores this generation, checkpoints it if that's on, and breeds the next one
+    pub fn step(&mut self) -> io::Result<()> {
+        if let Some(dir) = self.checkpoints.clone() {
+            self.checkpoint(dir)?;
+        }
+        let scores = self.evaluate().to_vec();
+
+        let mut next: Vec<Program> = ranked(&scores).iter().take(self.settings.elites).map(|&i| self.population[i].clone()).collect();
+
+        while next.len() < self.settings.population {
+            let a = tournament(self.gp.rng(), &scores, self.settings.tournament);
+            let mut child = if self.gp.rng().gen_bool(self.settings.crossover) {
+                let b = tournament(self.gp.rng(), &scores, self.settings.tournament);
+                self.gp.crossover(&self.population[a], &self.population[b])
+            } else {
+                self.population[a].clone()
+            };
+            if self.gp.rng().gen_bool(self.settings.point_mutation) {
+                child = self.gp.point_mutation(&child);
+            }
+            if self.gp.rng().gen_bool(self.settings.subtree_mutation) {
+                child = self.gp.subtree_mutation(&child);
+            }
+            next.push(child);
+        }
+
+        self.population = next;
+        self.scores = None;
+        self.generation += 1;
+        Ok(())
+    }
+
+    // Breeds this many generations, the last one is scored and checkpointed too
+    pub fn run(&mut self, generations: usize) -> io::Result<()> {
+        for _ in 0..generations {
+            self.step()?;
+        }
+        if let Some(dir) = self.checkpoints.clone() {
+            self.checkpoint(dir)?;
+        }
+        Ok(())
+    }
+
+    // Writes this generation and its scores to dir, in a file named after the generation
+    pub fn checkpoint<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<PathBuf> {
+        self.evaluate();
+        let scores = self.scores.as_deref().unwrap_or_default();
+
+        let mut text = format!("# generation {}\n", self.generation);
+        for (i, (p, score)) in self.population.iter().zip(scores).enumerate() {
+            text += &format!("# program {} fitness {}\n{}", i, score, p);
+        }
+
+        fs::create_dir_all(&dir)?;
+        let path = dir.as_ref().join(format!("generation-{:04}.txt", self.generation));
+        fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+// A generation as it was written to disk
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub generation: usize,
+    pub programs: Vec<Program>,
+    pub scores: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(String),
+    Parse(usize, ParseError), // which program couldn't be parsed
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "io error: {}", e),
+            CheckpointError::Format(e) => write!(f, "bad checkpoint: {}", e),
+            CheckpointError::Parse(i, e) => write!(f, "program {}: {}", i, e),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+// Reads back a file written by Evolution::checkpoint
+pub fn load_checkpoint<P: AsRef<Path>>(path: P) -> Result<Checkpoint, CheckpointError> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    let generation = lines.next()
+        .and_then(|l| l.strip_prefix("# generation "))
+        .and_then(|g| g.trim().parse().ok())
+        .ok_or_else(|| CheckpointError::Format("expected `# generation` first".to_string()))?;
+
+    let mut checkpoint = Checkpoint { generation, programs: Vec::new(), scores: Vec::new() };
+    let mut sources: Vec<String> = Vec::new();
+    for line in lines {
+        if let Some(header) = line.strip_prefix("# program ") {
+            let score = header.split_once(" fitness ")
+                .and_then(|(_, s)| s.trim().parse().ok())
+                .ok_or_else(|| CheckpointError::Format(format!("bad program header `{}`", line)))?;
+            checkpoint.scores.push(score);
+            sources.push(String::new());
+        } else if let Some(src) = sources.last_mut() {
+            src.push_str(line);
+            src.push('\n');
+        } else if !line.trim().is_empty() {
+            return Err(CheckpointError::Format("decisions before any `# program`".to_string()));
+        }
+    }
+
+    if sources.is_empty() {
+        return Err(CheckpointError::Format("no `# program` in it".to_string()));
+    }
+    for (i, src) in sources.iter().enumerate() {
+        checkpoint.programs.push(parse_program(src).map_err(|e| CheckpointError::Parse(i, e))?);
+    }
+    Ok(checkpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        let mut s = Scenario::new(vec2(0.0, 0.0), 3, 40);
+        s.add_food(vec2(8.0, 0.0));
+        s
+    }
+
+    const FORAGER: &str = "
+        if food > 0 then set_dest(home)
+        if not food > 0 then set_dest((8, 0))
+    ";
+
+    #[test]
+    fn episodes() {
+        let forager = parse_program(FORAGER).unwrap();
+        let e = scenario().run(&forager);
+        assert!(e.delivered >= 3.0, "{:?}", e);
+        assert_eq!((e.survivors, e.ants, e.ticks), (3, 3, 40));
+        assert!(e.energy_spent > 40.0 * 3.0 * DEFAULT_UPKEEP);
+        assert_eq!(e, scenario().run(&forager));
+
+        // wandering about only finds food by luck
+        let lazy = scenario().run(&parse_program("always nothing").unwrap());
+        assert!(lazy.delivered < e.delivered);
+
+        // ants that can't get back with food in time starve, ones that do get their energy back
+        let mut hungry = scenario();
+        hungry.set_energy(25.0, 0.25);
+        let e = hungry.run(&parse_program("always set_dest((30, 0))").unwrap());
+        assert_eq!(e.survivors, 0);
+        assert!(e.ticks == 40 && e.energy_spent < 3.0 * 26.25);
+        assert_eq!(hungry.run(&forager).survivors, 3);
+
+        // badly typed brains never get to run
+        let bad = scenario().run(&parse_program("if memory(0) > 1 then nothing").unwrap());
+        assert_eq!((bad.ticks, bad.survivors, bad.energy_spent), (0, 0, 0.0));
+    }
+
+    #[test]
+    fn fitness() {
+        let e = Episode { delivered: 4.0, energy_spent: 30.0, survivors: 1, ants: 4, ticks: 10 };
+        assert_eq!(FoodDelivered.score(&e), 4.0);
+        assert_eq!(EnergySpent.score(&e), -30.0);
+        assert_eq!(Survival.score(&e), 0.25);
+        let mixed = |e: &Episode| FoodDelivered.score(e) + EnergySpent.score(e) / 10.0;
+        assert_eq!(mixed.score(&e), 1.0);
+    }
+
+    // shorter episodes, these run a lot of them
+    fn quick() -> Scenario {
+        let mut s = scenario();
+        s.set_ticks(20);
+        s
+    }
+
+    fn settings() -> Settings {
+        Settings { population: 6, tournament: 2, ..Default::default() }
+    }
+
+    #[test]
+    fn evolution() {
+        let run = |seed| {
+            let mut e = Evolution::new(quick(), FoodDelivered, settings(), seed);
+            let mut best = Vec::new();
+            for _ in 0..3 {
+                best.push(e.best().1);
+                e.step().unwrap();
+            }
+            assert_eq!(e.generation(), 3);
+            assert_eq!(e.population().len(), 6);
+            (best, e.population().iter().map(|p| p.to_string()).collect::<Vec<_>>())
+        };
+
+        let (best, population) = run(5);
+        assert_eq!((best.clone(), population), run(5));
+        // the best always makes it to the next generation, and does just as well there
+        assert!(best.windows(2).all(|w| w[1] >= w[0]), "{:?}", best);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one program")]
+    fn empty_populations() {
+        Evolution::new(quick(), FoodDelivered, Settings { population: 0, elites: 0, ..settings() }, 1);
+    }
+
+    #[test]
+    fn checkpoints() {
+        let dir = std::env::temp_dir().join(format!("ants-checkpoints-{}", std::process::id()));
+        let mut e = Evolution::new(quick(), FoodDelivered, settings(), 9);
+        e.set_checkpoints(Some(&dir));
+        e.run(2).unwrap();
+
+        let written: Vec<_> = (0..=2).map(|g| dir.join(format!("generation-{:04}.txt", g))).collect();
+        assert!(written.iter().all(|p| p.exists()));
+
+        let c = load_checkpoint(&written[2]).unwrap();
+        assert_eq!(c.generation, 2);
+        assert_eq!(c.scores, e.evaluate());
+        let printed: Vec<_> = c.programs.iter().map(|p| p.to_string()).collect();
+        assert_eq!(printed, e.population().iter().map(|p| p.to_string()).collect::<Vec<_>>());
+
+        let mut resumed = Evolution::resume(c.clone(), quick(), FoodDelivered, settings(), 9).unwrap();
+        assert_eq!(resumed.generation(), 2);
+        assert_eq!(resumed.evaluate(), e.evaluate());
+
+        fs::write(&written[0], "# generation 0\n# program 0 fitness 1\nif food >\n").unwrap();
+        assert!(matches!(load_checkpoint(&written[0]), Err(CheckpointError::Parse(0, _))));
+        assert!(matches!(load_checkpoint(dir.join("nope.txt")), Err(CheckpointError::Io(_))));
+
+        // there's nothing to breed from an empty population
+        fs::write(&written[0], "# generation 0\n").unwrap();
+        assert!(matches!(load_checkpoint(&written[0]), Err(CheckpointError::Format(_))));
+        let empty = Checkpoint { programs: Vec::new(), scores: Vec::new(), ..c };
+        assert!(matches!(Evolution::resume(empty, quick(), FoodDelivered, settings(), 9), Err(CheckpointError::Format(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// State machine brains, for ants that go through modes like searching, returning and idling.
+// Each state has its own decisions and its own conditions for moving to another state.
+// Every step the current state's transitions are tried in order and the first one that holds
+// is taken, then whichever state the ant ends up in decides what to do. The state it's in
+// can be read with Source::State, which numbers the states in the order they were added.
+
+use std::{fmt, vec::Vec};
+
+use crate::{
+    ant::{Condition, Memories, Program},
+    brain::{Action, Brain, Scratch, Senses},
+    typecheck::{self, Diagnostic},
+    vm::CompileError,
+};
+
+#[derive(Clone, Debug)]
+pub struct State {
+    pub(crate) name: String,
+    pub(crate) program: Program,
+    pub(crate) transitions: Vec<(Condition, usize)>, // when to go to which state
+}
+
+impl State {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn transitions(&self) -> &[(Condition, usize)] {
+        &self.transitions
+    }
+}
+
+// The first state added is the one it starts in
+#[derive(Clone, Default)]
+pub struct Fsm {
+    pub(crate) states: Vec<State>,
+    pub(crate) current: usize,
+}
+
+impl Fsm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a state and returns its number, a state with the same name just gets the new decisions
+    pub fn add_state(&mut self, name: &str, program: Program) -> usize {
+        if let Some(i) = self.find(name) {
+            self.states[i].program = program;
+            return i;
+        }
+        self.states.push(State { name: name.to_string(), program, transitions: Vec::new() });
+        self.states.len() - 1
+    }
+
+    // Goes from one state to another whenever the condition holds, after any transitions added before
+    pub fn add_transition(&mut self, from: usize, when: Condition, to: usize) {
+        assert!(from < self.states.len(), "there's no state {}", from);
+        assert!(to < self.states.len(), "there's no state {}", to);
+        self.states[from].transitions.push((when, to));
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.name == name)
+    }
+
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn set_current(&mut self, i: usize) {
+        assert!(i < self.states.len(), "there's no state {}", i);
+        self.current = i;
+    }
+
+    pub fn current_state(&self) -> Option<&State> {
+        self.states.get(self.current)
+    }
+
+    // Compiles every state's decisions, the transitions are always walked
+    pub fn compile(&mut self) -> Result<(), CompileError> {
+        self.states.iter_mut().try_for_each(|s| s.program.compile())
+    }
+}
+
+impl fmt::Debug for Fsm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fsm")
+            .field("state", &self.current_state().map(State::name))
+            .field("states", &self.states.iter().map(State::name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Brain for Fsm {
+    fn think(&mut self, senses: &Senses) -> Vec<Action> {
+        let Some(state) = self.states.get(self.current) else {
+            return Vec::new();
+        };
+
+        let scratch = Scratch::new(senses).in_state(self.current);
+        if let Some((_, to)) = state.transitions.iter().find(|(c, _)| scratch.condition(c)) {
+            self.current = *to;
+        }
+
+        Scratch::new(senses).in_state(self.current).think(&self.states[self.current].program)
+    }
+
+    fn check(&self, memory: &Memories) -> Vec<Diagnostic> {
+        typecheck::check_fsm(self, memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec2;
+    use crate::{ant::{Ant, Location, Memory, Source}, dsl::parse_program, world::Environment};
+
+    fn forager() -> Fsm {
+        let mut fsm = Fsm::new();
+        let searching = fsm.add_state("searching", parse_program("always set_dest((10, 0))").unwrap());
+        let returning = fsm.add_state("returning", parse_program("
+            always set_dest(home)
+            if state == 1 then emit_pheromone(2)
+        ").unwrap());
+        let has_food = Condition::GreaterThan(Source::Food, Source::Number(0.0));
+        fsm.add_transition(searching, has_food.clone(), returning);
+        fsm.add_transition(returning, Condition::Not(Box::new(has_food)), searching);
+        fsm
+    }
+
+    #[test]
+    fn transitions() {
+        let env = Environment::new();
+        let mut a = Ant::new(vec2(5.0, 0.0), forager());
+        a.set_home(vec2(0.0, 0.0));
+
+        let mut fsm = forager();
+        assert_eq!(fsm.think(&env.senses(&a)), [Action::SetDest(vec2(10.0, 0.0))]);
+        assert_eq!(fsm.current(), 0);
+
+        // picking up food switches state straight away, and the new state does the thinking
+        a.set_food(1.0);
+        assert_eq!(fsm.think(&env.senses(&a)), [Action::SetDest(vec2(0.0, 0.0)), Action::EmitPheromone(2.0)]);
+        assert_eq!(fsm.current_state().unwrap().name(), "returning");
+        assert_eq!(fsm.think(&env.senses(&a)).len(), 2);
+
+        a.set_food(0.0);
+        fsm.think(&env.senses(&a));
+        assert_eq!(fsm.current(), 0);
+
+        // the state is only a thing inside a state machine
+        assert!(matches!(env.senses(&a).evaluate(&Source::State), Memory::Number(n) if n.is_nan()));
+        assert!(Fsm::new().think(&env.senses(&a)).is_empty());
+    }
+
+    #[test]
+    fn in_the_world() {
+        let mut env = Environment::new();
+        let mut a = Ant::new(vec2(5.0, 0.0), forager());
+        a.set_home(vec2(0.0, 0.0));
+        a.set_food(1.0);
+        a.set_max_speed(0.0);
+
+        let mut compiled = forager();
+        compiled.compile().unwrap();
+        let mut b = Ant::new(vec2(5.0, 0.0), compiled);
+        b.set_home(vec2(0.0, 0.0));
+        b.set_food(1.0);
+        b.set_max_speed(0.0);
+
+        env.think(&mut a);
+        env.think(&mut b);
+        for ant in [&a, &b] {
+            let fsm = ant.brain().downcast_ref::<Fsm>().unwrap();
+            assert_eq!(fsm.current(), 1);
+            assert_eq!(ant.dest, Some(vec2(0.0, 0.0)));
+        }
+        assert_eq!(format!("{:?}", a.brain()), r#"Fsm { state: Some("returning"), states: ["searching", "returning"] }"#);
+    }
+
+    #[test]
+    fn checking() {
+        assert!(forager().check(&Memories::new()).is_empty());
+
+        // what one state remembers can be read in another, as long as no state forgets it
+        let mut fsm = Fsm::new();
+        let a = fsm.add_state("a", parse_program("always remember(5)").unwrap());
+        let b = fsm.add_state("b", parse_program("if memory(0) > 2 then forget, remember(1)").unwrap());
+        fsm.add_transition(a, Condition::GreaterThan(Source::Memory(0), Source::Number(1.0)), b);
+        fsm.add_transition(b, Condition::Equal(Source::Loc(Location::Here), Source::Number(1.0)), a);
+        let memory: Memories = [Memory::Number(1.0)].into_iter().collect();
+
+        let d = fsm.check(&memory);
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].to_string(), "state b, transition 0: equal compares different types, loc(here) is a position and 1 is a number");
+
+        fsm.add_state("b", parse_program("if memory(0) > 2 then forget").unwrap());
+        let d: Vec<String> = fsm.check(&memory).iter().map(|d| d.to_string()).collect();
+        assert_eq!(d.len(), 3, "{:?}", d);
+        assert!(d[0].starts_with("state a, transition 0: memory(0)"));
+        assert!(d[1].starts_with("state b, transition 0: equal"));
+        assert!(d[2].starts_with("state b, decision 0: memory(0)"));
+
+        // inside a state machine there is a state to read
+        let mut fsm = Fsm::new();
+        fsm.add_state("a", parse_program("if state == 0 then emit_pheromone(state + 1)").unwrap());
+        assert!(fsm.check(&Memories::new()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "there's no state 1")]
+    fn transitions_from_nowhere() {
+        let mut fsm = Fsm::new();
+        let a = fsm.add_state("a", Program::default());
+        fsm.add_transition(1, Condition::Equal(Source::Bool(true), Source::Bool(true)), a);
+    }
+}
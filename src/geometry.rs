@@ -0,0 +1,917 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+use std::vec::Vec;
+use glm::{dot, vec2, Vec2};
+
+use crate::{shape::{BasicShape, Shape}, world::square_dist};
+
+// 2D cross product, aka the z component of the 3D one
+pub fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// The corners of a BasicShape::Rect, in order
+pub fn rect_points(cl: Vec2, wh: Vec2) -> Vec<Vec2> {
+    vec![cl, vec2(cl.x, cl.y - wh.y), vec2(cl.x + wh.x, cl.y - wh.y), vec2(cl.x + wh.x, cl.y)]
+}
+
+// Every edge of a closed outline, the last point connects back to the first
+pub fn edges(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    (0..points.len()).map(move |i| (points[i], points[(i + 1) % points.len()]))
+}
+
+pub fn closest_point_on_segment(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let len = dot(ab, ab);
+    if len == 0.0 {
+        return a;
+    }
+
+    let t = (dot(p - a, ab) / len).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+// How many times a closed outline winds around p, counter clockwise is positive
+pub fn winding_number(points: &[Vec2], p: Vec2) -> i32 {
+    let mut wn = 0;
+
+    for (a, b) in edges(points) {
+        let side = cross(b - a, p - a);
+        if a.y <= p.y {
+            if b.y > p.y && side > 0.0 {
+                wn += 1;
+            }
+        } else if b.y <= p.y && side < 0.0 {
+            wn -= 1;
+        }
+    }
+
+    wn
+}
+
+// Nonzero winding rule, so concave and self-overlapping outlines work too
+pub fn point_in_polygon(points: &[Vec2], p: Vec2) -> bool {
+    winding_number(points, p) != 0 || edges(points).any(|(a, b)| square_dist(closest_point_on_segment(p, a, b), p) == 0.0)
+}
+
+// Every segment of an open chain of points
+pub fn segments(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    points.windows(2).map(|w| (w[0], w[1]))
+}
+
+// Closest points between two segments, as (point on ab, point on cd)
+pub fn closest_points_segments(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> (Vec2, Vec2) {
+    if segments_intersect(a, b, c, d) {
+        // any point on both works, find where they cross if they aren't parallel
+        let denom = cross(b - a, d - c);
+        let p = if denom == 0.0 {
+            if square_dist(closest_point_on_segment(c, a, b), c) == 0.0 { c } else { a }
+        } else {
+            a + (b - a) * (cross(c - a, d - c) / denom)
+        };
+        return (p, p);
+    }
+
+    [
+        (closest_point_on_segment(c, a, b), c),
+        (closest_point_on_segment(d, a, b), d),
+        (a, closest_point_on_segment(a, c, d)),
+        (b, closest_point_on_segment(b, c, d)),
+    ].into_iter().fold((a, c), |acc, (p, q)| if square_dist(p, q) < square_dist(acc.0, acc.1) { (p, q) } else { acc })
+}
+
+// Closest point to p on an open chain of points
+pub fn closest_point_on_polyline(points: &[Vec2], p: Vec2) -> Vec2 {
+    if points.len() == 1 {
+        return points[0];
+    }
+
+    segments(points).map(|(a, b)| closest_point_on_segment(p, a, b)).fold(vec2(f32::MAX, f32::MAX), |acc, q| {
+        if square_dist(q, p) < square_dist(acc, p) {
+            q
+        } else {
+            acc
+        }
+    })
+}
+
+pub fn is_convex(points: &[Vec2]) -> bool {
+    let mut sign = 0.0f32;
+
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        let c = points[(i + 2) % points.len()];
+        let z = cross(b - a, c - b);
+
+        if z != 0.0 {
+            if sign != 0.0 && z.signum() != sign {
+                return false;
+            }
+            sign = z.signum();
+        }
+    }
+
+    true
+}
+
+pub fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let d1 = cross(b - a, c - a);
+    let d2 = cross(b - a, d - a);
+    let d3 = cross(d - c, a - c);
+    let d4 = cross(d - c, b - c);
+
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        return true;
+    }
+
+    // collinear or touching cases, check if the endpoint lies on the other segment
+    let on = |p: Vec2, a: Vec2, b: Vec2, d: f32| {
+        d == 0.0 && p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x) && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+    };
+
+    on(c, a, b, d1) || on(d, a, b, d2) || on(a, c, d, d3) || on(b, c, d, d4)
+}
+
+pub fn circle_circle(c1: Vec2, r1: f32, c2: Vec2, r2: f32) -> bool {
+    square_dist(c1, c2) <= (r1 + r2).powi(2)
+}
+
+pub fn circle_rect(c: Vec2, r: f32, cl: Vec2, wh: Vec2) -> bool {
+    let closest = vec2(c.x.clamp(cl.x, cl.x + wh.x), c.y.clamp(cl.y - wh.y, cl.y));
+    square_dist(closest, c) <= r.powi(2)
+}
+
+pub fn rect_rect(cl1: Vec2, wh1: Vec2, cl2: Vec2, wh2: Vec2) -> bool {
+    cl1.x <= cl2.x + wh2.x && cl2.x <= cl1.x + wh1.x &&
+    cl1.y - wh1.y <= cl2.y && cl2.y - wh2.y <= cl1.y
+}
+
+// Works for any simple polygon, convex or not
+pub fn circle_polygon(c: Vec2, r: f32, points: &[Vec2]) -> bool {
+    if point_in_polygon(points, c) {
+        return true;
+    }
+
+    edges(points).any(|(a, b)| square_dist(closest_point_on_segment(c, a, b), c) <= r.powi(2))
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+        let d = dot(*p, axis);
+        (lo.min(d), hi.max(d))
+    })
+}
+
+// Separating axis test, both polygons must be convex
+pub fn convex_convex(a: &[Vec2], b: &[Vec2]) -> bool {
+    for (p, q) in edges(a).chain(edges(b)) {
+        let axis = vec2(q.y - p.y, p.x - q.x);
+        let (amin, amax) = project(a, axis);
+        let (bmin, bmax) = project(b, axis);
+
+        if amax < bmin || bmax < amin {
+            return false;
+        }
+    }
+
+    true
+}
+
+pub fn polygon_polygon(a: &[Vec2], b: &[Vec2]) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+
+    if is_convex(a) && is_convex(b) {
+        return convex_convex(a, b);
+    }
+
+    // concave polygons overlap iff their edges cross or one is inside the other
+    edges(a).any(|(p, q)| edges(b).any(|(r, s)| segments_intersect(p, q, r, s))) ||
+    point_in_polygon(b, a[0]) || point_in_polygon(a, b[0])
+}
+
+// Closest points between an open chain and a closed outline, as (point on chain, point on outline)
+fn closest_points_polyline_outline(line: &[Vec2], outline: &[Vec2]) -> (Vec2, Vec2) {
+    let line_segs: Vec<_> = if line.len() == 1 { vec![(line[0], line[0])] } else { segments(line).collect() };
+    line_segs.into_iter()
+        .flat_map(|(a, b)| edges(outline).map(move |(c, d)| closest_points_segments(a, b, c, d)))
+        .fold((line[0], outline[0]), |acc, (p, q)| if square_dist(p, q) < square_dist(acc.0, acc.1) { (p, q) } else { acc })
+}
+
+// Closest points between two open chains
+fn closest_points_polylines(a: &[Vec2], b: &[Vec2]) -> (Vec2, Vec2) {
+    let segs = |l: &[Vec2]| -> Vec<(Vec2, Vec2)> { if l.len() == 1 { vec![(l[0], l[0])] } else { segments(l).collect() } };
+    let (sa, sb) = (segs(a), segs(b));
+    sa.iter()
+        .flat_map(|(p, q)| sb.iter().map(move |(r, s)| closest_points_segments(*p, *q, *r, *s)))
+        .fold((a[0], b[0]), |acc, (p, q)| if square_dist(p, q) < square_dist(acc.0, acc.1) { (p, q) } else { acc })
+}
+
+// A wall made of a chain of segments, r is half of its thickness
+pub fn circle_polyline(c: Vec2, r: f32, line: &[Vec2], w: f32) -> bool {
+    !line.is_empty() && square_dist(closest_point_on_polyline(line, c), c) <= (r + w).powi(2)
+}
+
+pub fn polygon_polyline(outline: &[Vec2], line: &[Vec2], w: f32) -> bool {
+    if outline.is_empty() || line.is_empty() {
+        return false;
+    }
+
+    if point_in_polygon(outline, line[0]) {
+        return true;
+    }
+
+    let (p, q) = closest_points_polyline_outline(line, outline);
+    square_dist(p, q) <= w.powi(2)
+}
+
+pub fn polyline_polyline(a: &[Vec2], wa: f32, b: &[Vec2], wb: f32) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+
+    let (p, q) = closest_points_polylines(a, b);
+    square_dist(p, q) <= (wa + wb).powi(2)
+}
+
+// Turns anything that isn't a circle into an outline
+fn outline(shape: &dyn Shape, basic: BasicShape) -> Vec<Vec2> {
+    match basic {
+        BasicShape::Rect(cl, wh) => rect_points(cl, wh),
+        BasicShape::Polygon(points) | BasicShape::Polyline(points, _) => points,
+        _ => shape.into_points(),
+    }
+}
+
+// Exact collision test between any two shapes, every Shape::collides should end up here
+pub fn collides(a: &dyn Shape, b: &dyn Shape) -> bool {
+    // cheap broad phase first
+    if !a.aabb().overlaps(&b.aabb()) {
+        return false;
+    }
+
+    match (a.into_basic_shape(), b.into_basic_shape()) {
+        (BasicShape::Polyline(l1, w1), BasicShape::Polyline(l2, w2)) => polyline_polyline(&l1, w1, &l2, w2),
+        (BasicShape::Circle(c, r), BasicShape::Polyline(l, w)) |
+        (BasicShape::Polyline(l, w), BasicShape::Circle(c, r)) => circle_polyline(c, r, &l, w),
+        (BasicShape::Polyline(l, w), other) => polygon_polyline(&outline(b, other), &l, w),
+        (other, BasicShape::Polyline(l, w)) => polygon_polyline(&outline(a, other), &l, w),
+        (BasicShape::Circle(c1, r1), BasicShape::Circle(c2, r2)) => circle_circle(c1, r1, c2, r2),
+        (BasicShape::Circle(c, r), BasicShape::Rect(cl, wh)) |
+        (BasicShape::Rect(cl, wh), BasicShape::Circle(c, r)) => circle_rect(c, r, cl, wh),
+        (BasicShape::Rect(cl1, wh1), BasicShape::Rect(cl2, wh2)) => rect_rect(cl1, wh1, cl2, wh2),
+        (BasicShape::Circle(c, r), other) => circle_polygon(c, r, &outline(b, other)),
+        (other, BasicShape::Circle(c, r)) => circle_polygon(c, r, &outline(a, other)),
+        (sa, sb) => polygon_polygon(&outline(a, sa), &outline(b, sb)),
+    }
+}
+
+// Where two shapes touch, normal points from the first shape into the second,
+// moving the first shape by -normal * depth separates them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub depth: f32,
+}
+
+pub fn centroid(points: &[Vec2]) -> Vec2 {
+    points.iter().fold(vec2(0.0, 0.0), |acc, p| acc + *p) / (points.len().max(1) as f32)
+}
+
+// Closest point to p on the outline of a polygon
+pub fn closest_point_on_outline(points: &[Vec2], p: Vec2) -> Vec2 {
+    edges(points).map(|(a, b)| closest_point_on_segment(p, a, b)).fold(vec2(f32::MAX, f32::MAX), |acc, q| {
+        if square_dist(q, p) < square_dist(acc, p) {
+            q
+        } else {
+            acc
+        }
+    })
+}
+
+fn normal_or_default(v: Vec2) -> Vec2 {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        vec2(1.0, 0.0)
+    } else {
+        v / len
+    }
+}
+
+pub fn circle_circle_contact(c1: Vec2, r1: f32, c2: Vec2, r2: f32) -> Option<Contact> {
+    let dist = square_dist(c1, c2).sqrt();
+    if dist > r1 + r2 {
+        return None;
+    }
+
+    let normal = normal_or_default(c2 - c1);
+    let depth = r1 + r2 - dist;
+    Some(Contact { point: c1 + normal * (r1 - depth / 2.0), normal, depth })
+}
+
+pub fn circle_polygon_contact(c: Vec2, r: f32, points: &[Vec2]) -> Option<Contact> {
+    let q = closest_point_on_outline(points, c);
+    let dist = square_dist(q, c).sqrt();
+
+    if point_in_polygon(points, c) {
+        // the center is inside, so the circle has to get out through the closest edge
+        Some(Contact { point: q, normal: normal_or_default(c - q), depth: r + dist })
+    } else if dist <= r {
+        Some(Contact { point: q, normal: normal_or_default(q - c), depth: r - dist })
+    } else {
+        None
+    }
+}
+
+// Separating axis test that keeps track of the axis with the least overlap
+pub fn convex_convex_contact(a: &[Vec2], b: &[Vec2]) -> Option<Contact> {
+    let mut best = (f32::MAX, vec2(1.0, 0.0));
+
+    for (p, q) in edges(a).chain(edges(b)) {
+        let axis = normal_or_default(vec2(q.y - p.y, p.x - q.x));
+        let (amin, amax) = project(a, axis);
+        let (bmin, bmax) = project(b, axis);
+
+        if amax < bmin || bmax < amin {
+            return None;
+        }
+
+        let overlap = amax.min(bmax) - amin.max(bmin);
+        if overlap < best.0 {
+            best = (overlap, axis);
+        }
+    }
+
+    let (depth, mut normal) = best;
+    if dot(centroid(b) - centroid(a), normal) < 0.0 {
+        normal = -normal;
+    }
+
+    // the deepest vertex of b along the normal is where they touch
+    let point = b.iter().fold(b[0], |acc, p| if dot(*p, normal) < dot(acc, normal) { *p } else { acc });
+    Some(Contact { point, normal, depth })
+}
+
+pub fn polygon_polygon_contact(a: &[Vec2], b: &[Vec2]) -> Option<Contact> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    if is_convex(a) && is_convex(b) {
+        return convex_convex_contact(a, b);
+    }
+
+    // find the vertex that sticks out the furthest into the other polygon
+    let mut best: Option<Contact> = None;
+    for p in b.iter().filter(|p| point_in_polygon(a, **p)) {
+        let q = closest_point_on_outline(a, *p);
+        let depth = square_dist(*p, q).sqrt();
+        if best.is_none_or(|c| depth > c.depth) {
+            best = Some(Contact { point: *p, normal: normal_or_default(q - *p), depth });
+        }
+    }
+    for p in a.iter().filter(|p| point_in_polygon(b, **p)) {
+        let q = closest_point_on_outline(b, *p);
+        let depth = square_dist(*p, q).sqrt();
+        if best.is_none_or(|c| depth > c.depth) {
+            best = Some(Contact { point: *p, normal: normal_or_default(*p - q), depth });
+        }
+    }
+
+    if best.is_some() {
+        return best;
+    }
+
+    // only edges cross, there is no good depth to report
+    edges(a).flat_map(|(p, q)| edges(b).map(move |(r, s)| (p, q, r, s)))
+        .find(|(p, q, r, s)| segments_intersect(*p, *q, *r, *s))
+        .map(|(p, q, _, _)| Contact {
+            point: (p + q) / 2.0,
+            normal: normal_or_default(centroid(b) - centroid(a)),
+            depth: 0.0,
+        })
+}
+
+pub fn circle_polyline_contact(c: Vec2, r: f32, line: &[Vec2], w: f32) -> Option<Contact> {
+    if line.is_empty() {
+        return None;
+    }
+
+    let q = closest_point_on_polyline(line, c);
+    let dist = square_dist(q, c).sqrt();
+    if dist > r + w {
+        return None;
+    }
+
+    Some(Contact { point: q, normal: normal_or_default(q - c), depth: r + w - dist })
+}
+
+pub fn polygon_polyline_contact(outline: &[Vec2], line: &[Vec2], w: f32) -> Option<Contact> {
+    if outline.is_empty() || line.is_empty() {
+        return None;
+    }
+
+    let (p, q) = closest_points_polyline_outline(line, outline);
+    let dist = square_dist(p, q).sqrt();
+    let inside = line.iter().any(|v| point_in_polygon(outline, *v));
+
+    if dist > 0.0 && !inside {
+        if dist > w {
+            return None;
+        }
+        return Some(Contact { point: q, normal: normal_or_default(p - q), depth: w - dist });
+    }
+
+    // the wall goes through the polygon, push out along whichever side is shorter
+    let deepest = line.iter().filter(|v| point_in_polygon(outline, **v))
+        .map(|v| (*v, square_dist(closest_point_on_outline(outline, *v), *v).sqrt()))
+        .fold((p, 0.0f32), |acc, v| if v.1 > acc.1 { v } else { acc });
+    Some(Contact {
+        point: deepest.0,
+        normal: normal_or_default(deepest.0 - centroid(outline)),
+        depth: deepest.1 + w,
+    })
+}
+
+pub fn polyline_polyline_contact(a: &[Vec2], wa: f32, b: &[Vec2], wb: f32) -> Option<Contact> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    let (p, q) = closest_points_polylines(a, b);
+    let dist = square_dist(p, q).sqrt();
+    if dist > wa + wb {
+        return None;
+    }
+
+    let normal = if dist == 0.0 { normal_or_default(centroid(b) - centroid(a)) } else { (q - p) / dist };
+    Some(Contact { point: p + normal * wa, normal, depth: wa + wb - dist })
+}
+
+fn flip(c: Contact) -> Contact {
+    Contact { normal: -c.normal, ..c }
+}
+
+// Like collides(), but also reports how the shapes overlap
+pub fn contact(a: &dyn Shape, b: &dyn Shape) -> Option<Contact> {
+    match (a.into_basic_shape(), b.into_basic_shape()) {
+        (BasicShape::Polyline(l1, w1), BasicShape::Polyline(l2, w2)) => polyline_polyline_contact(&l1, w1, &l2, w2),
+        (BasicShape::Circle(c, r), BasicShape::Polyline(l, w)) => circle_polyline_contact(c, r, &l, w),
+        (BasicShape::Polyline(l, w), BasicShape::Circle(c, r)) => circle_polyline_contact(c, r, &l, w).map(flip),
+        (other, BasicShape::Polyline(l, w)) => polygon_polyline_contact(&outline(a, other), &l, w),
+        (BasicShape::Polyline(l, w), other) => polygon_polyline_contact(&outline(b, other), &l, w).map(flip),
+        (BasicShape::Circle(c1, r1), BasicShape::Circle(c2, r2)) => circle_circle_contact(c1, r1, c2, r2),
+        (BasicShape::Circle(c, r), other) => circle_polygon_contact(c, r, &outline(b, other)),
+        (other, BasicShape::Circle(c, r)) => circle_polygon_contact(c, r, &outline(a, other)).map(flip),
+        (sa, sb) => polygon_polygon_contact(&outline(a, sa), &outline(b, sb)),
+    }
+}
+
+// Distance along a ray to a circle, dir has to be normalized
+pub fn ray_circle(origin: Vec2, dir: Vec2, c: Vec2, r: f32) -> Option<f32> {
+    let oc = origin - c;
+    let b = dot(oc, dir);
+    let disc = b * b - (dot(oc, oc) - r * r);
+    if disc < 0.0 {
+        return None;
+    }
+
+    let t = -b - disc.sqrt();
+    if t >= 0.0 {
+        Some(t)
+    } else if -b + disc.sqrt() >= 0.0 {
+        // started inside the circle
+        Some(0.0)
+    } else {
+        None
+    }
+}
+
+pub fn ray_segment(origin: Vec2, dir: Vec2, a: Vec2, b: Vec2) -> Option<f32> {
+    let ab = b - a;
+    let denom = cross(dir, ab);
+    if denom == 0.0 {
+        return None;
+    }
+
+    let t = cross(a - origin, ab) / denom;
+    let u = cross(a - origin, dir) / denom;
+    if t >= 0.0 && (0.0..=1.0).contains(&u) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+pub fn ray_polygon(origin: Vec2, dir: Vec2, points: &[Vec2]) -> Option<f32> {
+    if point_in_polygon(points, origin) {
+        return Some(0.0);
+    }
+
+    edges(points).filter_map(|(a, b)| ray_segment(origin, dir, a, b)).reduce(f32::min)
+}
+
+// A segment with rounded ends of radius r
+pub fn ray_capsule(origin: Vec2, dir: Vec2, a: Vec2, b: Vec2, r: f32) -> Option<f32> {
+    if square_dist(closest_point_on_segment(origin, a, b), origin) <= r * r {
+        return Some(0.0);
+    }
+
+    let side = normal_or_default(vec2(a.y - b.y, b.x - a.x)) * r;
+    [
+        ray_circle(origin, dir, a, r),
+        ray_circle(origin, dir, b, r),
+        ray_segment(origin, dir, a + side, b + side),
+        ray_segment(origin, dir, a - side, b - side),
+    ].into_iter().flatten().reduce(f32::min)
+}
+
+pub fn ray_polyline(origin: Vec2, dir: Vec2, line: &[Vec2], w: f32) -> Option<f32> {
+    if line.len() == 1 {
+        return ray_circle(origin, dir, line[0], w);
+    }
+
+    segments(line).filter_map(|(a, b)| ray_capsule(origin, dir, a, b, w)).reduce(f32::min)
+}
+
+// Distance along a ray to the first point of a shape, dir has to be normalized
+pub fn raycast(origin: Vec2, dir: Vec2, shape: &dyn Shape) -> Option<f32> {
+    match shape.into_basic_shape() {
+        BasicShape::Circle(c, r) => ray_circle(origin, dir, c, r),
+        BasicShape::Polyline(l, w) => ray_polyline(origin, dir, &l, w),
+        other => ray_polygon(origin, dir, &outline(shape, other)),
+    }
+}
+
+// Closest point on the boundary of a shape
+pub fn closest_point(shape: &dyn Shape, p: Vec2) -> Vec2 {
+    match shape.into_basic_shape() {
+        BasicShape::Circle(c, r) => c + normal_or_default(p - c) * r,
+        BasicShape::Polyline(l, w) => {
+            if l.is_empty() {
+                return p;
+            }
+            let q = closest_point_on_polyline(&l, p);
+            q + normal_or_default(p - q) * w
+        },
+        other => {
+            let points = outline(shape, other);
+            if points.is_empty() {
+                p
+            } else {
+                closest_point_on_outline(&points, p)
+            }
+        },
+    }
+}
+
+// Distance from p to the boundary of a shape, negative when p is inside
+pub fn signed_distance(shape: &dyn Shape, p: Vec2) -> f32 {
+    match shape.into_basic_shape() {
+        BasicShape::Circle(c, r) => square_dist(p, c).sqrt() - r,
+        BasicShape::Polyline(l, w) => {
+            if l.is_empty() {
+                return f32::INFINITY;
+            }
+            square_dist(closest_point_on_polyline(&l, p), p).sqrt() - w
+        },
+        other => {
+            let points = outline(shape, other);
+            if points.is_empty() {
+                return f32::INFINITY;
+            }
+
+            let dist = square_dist(closest_point_on_outline(&points, p), p).sqrt();
+            if point_in_polygon(&points, p) {
+                -dist
+            } else {
+                dist
+            }
+        },
+    }
+}
+
+// how far into a shape a move has to go before it counts, so sliding along a wall doesn't catch on it
+const SWEEP_SLOP: f32 = 1e-4;
+
+// Time of impact for a circle of radius r moving from c to c + delta, as a fraction of delta.
+// This is a ray cast against the shape grown by r, so thin walls can't be skipped over.
+// A circle already touching the shape only hits it by moving further in, backing off or
+// sliding along is free.
+pub fn sweep_circle(c: Vec2, r: f32, delta: Vec2, shape: &dyn Shape) -> Option<f32> {
+    let len = dot(delta, delta).sqrt();
+    if len == 0.0 {
+        return None;
+    }
+
+    if let Some(start) = circle_contact(c, r, shape) {
+        let inward = dot(delta / len, start.normal) > SWEEP_SLOP;
+        let deeper = circle_contact(c + delta, r, shape).is_some_and(|end| end.depth > start.depth + SWEEP_SLOP);
+        return (inward || deeper).then_some(0.0);
+    }
+
+    let dir = delta / len;
+    let dist = match shape.into_basic_shape() {
+        BasicShape::Circle(c2, r2) => ray_circle(c, dir, c2, r + r2),
+        BasicShape::Polyline(l, w) => ray_polyline(c, dir, &l, r + w),
+        other => edges(&outline(shape, other)).filter_map(|(a, b)| ray_capsule(c, dir, a, b, r)).reduce(f32::min),
+    };
+
+    dist.map(|d| d / len).filter(|t| *t <= 1.0)
+}
+
+// normal points from the circle into the shape
+fn circle_contact(c: Vec2, r: f32, shape: &dyn Shape) -> Option<Contact> {
+    match shape.into_basic_shape() {
+        BasicShape::Circle(c2, r2) => circle_circle_contact(c, r, c2, r2),
+        BasicShape::Polyline(l, w) => circle_polyline_contact(c, r, &l, w),
+        other => circle_polygon_contact(c, r, &outline(shape, other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{Aabb, Circle, Polygon, Polyline, Rect, RegularPolygon, Star};
+
+    fn square(x: f32, y: f32, s: f32) -> Vec<Vec2> {
+        vec![vec2(x, y), vec2(x + s, y), vec2(x + s, y + s), vec2(x, y + s)]
+    }
+
+    #[test]
+    fn circles() {
+        assert!(circle_circle(vec2(0.0, 0.0), 2.0, vec2(3.0, 0.0), 1.5));
+        assert!(circle_circle(vec2(0.0, 0.0), 2.0, vec2(4.0, 0.0), 2.0));
+        assert!(!circle_circle(vec2(0.0, 0.0), 2.0, vec2(4.1, 0.0), 2.0));
+        assert!(circle_circle(vec2(0.0, 0.0), 10.0, vec2(1.0, 1.0), 0.5));
+    }
+
+    #[test]
+    fn circle_vs_rect() {
+        let (cl, wh) = (vec2(0.0, 10.0), vec2(10.0, 10.0));
+        assert!(circle_rect(vec2(5.0, 5.0), 1.0, cl, wh));
+        assert!(circle_rect(vec2(-1.0, 5.0), 1.5, cl, wh));
+        assert!(!circle_rect(vec2(-2.0, 5.0), 1.5, cl, wh));
+        // near a corner, inside the bounding box of the circle but not the circle itself
+        assert!(!circle_rect(vec2(-1.5, 11.5), 2.0, cl, wh));
+        assert!(circle_rect(vec2(-1.0, 11.0), 2.0, cl, wh));
+    }
+
+    #[test]
+    fn circle_vs_polygon() {
+        let tri = vec![vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(0.0, 10.0)];
+        assert!(circle_polygon(vec2(2.0, 2.0), 0.5, &tri));
+        assert!(circle_polygon(vec2(6.0, 6.0), 1.5, &tri));
+        assert!(!circle_polygon(vec2(6.0, 6.0), 1.0, &tri));
+        assert!(!circle_polygon(vec2(-3.0, -3.0), 2.0, &tri));
+
+        // the notch of a U shape
+        let u = vec![
+            vec2(0.0, 0.0), vec2(9.0, 0.0), vec2(9.0, 9.0), vec2(6.0, 9.0),
+            vec2(6.0, 3.0), vec2(3.0, 3.0), vec2(3.0, 9.0), vec2(0.0, 9.0),
+        ];
+        assert!(!circle_polygon(vec2(4.5, 6.0), 1.0, &u));
+        assert!(circle_polygon(vec2(4.5, 6.0), 1.6, &u));
+    }
+
+    #[test]
+    fn convex_polygons() {
+        assert!(polygon_polygon(&square(0.0, 0.0, 2.0), &square(1.0, 1.0, 2.0)));
+        assert!(polygon_polygon(&square(0.0, 0.0, 2.0), &square(2.0, 0.0, 2.0)));
+        assert!(!polygon_polygon(&square(0.0, 0.0, 2.0), &square(2.5, 0.0, 2.0)));
+        assert!(polygon_polygon(&square(0.0, 0.0, 10.0), &square(4.0, 4.0, 1.0)));
+
+        // diamond next to a square, the bounding boxes overlap but the shapes don't
+        let diamond = vec![vec2(3.0, 0.0), vec2(4.5, 1.5), vec2(3.0, 3.0), vec2(1.5, 1.5)];
+        assert!(!polygon_polygon(&square(-0.5, 2.5, 1.9), &diamond));
+        assert!(polygon_polygon(&square(2.0, 1.0, 1.0), &diamond));
+    }
+
+    #[test]
+    fn concave_polygons() {
+        // a plus sign, built from two bars that each only cross the other without containing a vertex
+        let bar = vec![vec2(-5.0, -1.0), vec2(5.0, -1.0), vec2(5.0, 1.0), vec2(-5.0, 1.0)];
+        let col = vec![vec2(-1.0, -5.0), vec2(1.0, -5.0), vec2(1.0, 5.0), vec2(-1.0, 5.0)];
+        assert!(polygon_polygon(&bar, &col));
+
+        let l = vec![
+            vec2(0.0, 0.0), vec2(6.0, 0.0), vec2(6.0, 2.0),
+            vec2(2.0, 2.0), vec2(2.0, 6.0), vec2(0.0, 6.0),
+        ];
+        assert!(!is_convex(&l));
+        assert!(!polygon_polygon(&l, &square(3.0, 3.0, 2.0)));
+        assert!(polygon_polygon(&l, &square(1.5, 1.5, 2.0)));
+    }
+
+    #[test]
+    fn segments() {
+        assert!(segments_intersect(vec2(0.0, 0.0), vec2(2.0, 2.0), vec2(0.0, 2.0), vec2(2.0, 0.0)));
+        assert!(!segments_intersect(vec2(0.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 2.0), vec2(0.9, 1.1)));
+        assert!(segments_intersect(vec2(0.0, 0.0), vec2(2.0, 0.0), vec2(1.0, 0.0), vec2(3.0, 0.0)));
+        assert!(!segments_intersect(vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(2.0, 0.0), vec2(3.0, 0.0)));
+    }
+
+    #[test]
+    fn dispatch() {
+        let circle = Circle::new(vec2(0.0, 0.0), 3.0);
+        let rect = Rect::new(vec2(2.0, 1.0), vec2(4.0, 2.0));
+        let far = Rect::new(vec2(20.0, 1.0), vec2(4.0, 2.0));
+        let hex = RegularPolygon::new(vec2(5.0, 0.0), 2.5, 6, 0.0);
+        let star = Star::new(vec2(-6.0, 0.0), 4.0, 1.0, 5, 0.0);
+
+        assert!(collides(&circle, &rect));
+        assert!(collides(&rect, &circle));
+        assert!(!collides(&circle, &far));
+        assert!(collides(&rect, &hex));
+        assert!(!collides(&far, &hex));
+        assert!(collides(&circle, &star));
+        assert!(!collides(&star, &hex));
+        assert!(circle.collides(&rect) && rect.collides(&circle));
+        assert!(star.collides(&circle) && !hex.collides(&far));
+
+        let maze = Polyline::new(vec![vec2(-10.0, 4.0), vec2(10.0, 4.0), vec2(10.0, -10.0)], 0.5);
+        let cup = Polygon::new(vec![
+            vec2(3.0, 8.0), vec2(9.0, 8.0), vec2(9.0, 5.0), vec2(8.0, 5.0),
+            vec2(8.0, 7.0), vec2(4.0, 7.0), vec2(4.0, 5.0), vec2(3.0, 5.0),
+        ]);
+        assert!(!collides(&maze, &cup));
+        assert!(collides(&maze, &Circle::new(vec2(0.0, 2.0), 2.0)) && !collides(&maze, &circle));
+        assert!(!collides(&hex, &maze) && collides(&RegularPolygon::new(vec2(9.0, 0.0), 1.0, 6, 0.0), &maze));
+        assert!(!cup.contains_point(vec2(6.0, 6.0)));
+        assert!(cup.collides(&Circle::new(vec2(6.0, 6.0), 1.1)));
+    }
+
+    #[test]
+    fn contacts() {
+        let c = circle_circle_contact(vec2(0.0, 0.0), 2.0, vec2(3.0, 0.0), 2.0).unwrap();
+        assert_eq!(c.normal, vec2(1.0, 0.0));
+        assert!((c.depth - 1.0).abs() < 1e-5);
+        assert!((c.point.x - 1.5).abs() < 1e-5);
+        assert!(circle_circle_contact(vec2(0.0, 0.0), 1.0, vec2(3.0, 0.0), 1.0).is_none());
+
+        // circle poking into the left side of a box
+        let b = square(0.0, 0.0, 10.0);
+        let c = circle_polygon_contact(vec2(-1.0, 5.0), 2.0, &b).unwrap();
+        assert_eq!(c.normal, vec2(1.0, 0.0));
+        assert!((c.depth - 1.0).abs() < 1e-5);
+
+        // center already inside, it has to be pushed out the same way
+        let c = circle_polygon_contact(vec2(0.5, 5.0), 2.0, &b).unwrap();
+        assert_eq!(c.normal, vec2(1.0, 0.0));
+        assert!((c.depth - 2.5).abs() < 1e-5);
+
+        let c = polygon_polygon_contact(&square(0.0, 0.0, 2.0), &square(1.5, 0.2, 2.0)).unwrap();
+        assert_eq!(c.normal, vec2(1.0, 0.0));
+        assert!((c.depth - 0.5).abs() < 1e-5);
+        assert!(polygon_polygon_contact(&square(0.0, 0.0, 2.0), &square(3.0, 0.0, 2.0)).is_none());
+    }
+
+    #[test]
+    fn contact_separates() {
+        let wall = Rect::new(vec2(0.0, 10.0), vec2(2.0, 10.0));
+        let mut ball = Circle::new(vec2(2.5, 4.0), 1.0);
+
+        let c = contact(&ball, &wall).unwrap();
+        assert_eq!(c.normal, vec2(-1.0, 0.0));
+        ball.pos = ball.pos - c.normal * c.depth;
+        assert!((ball.pos.x - 3.0).abs() < 1e-5);
+        assert!(contact(&ball, &wall).is_none_or(|c| c.depth < 1e-5));
+    }
+
+    #[test]
+    fn rays() {
+        let right = vec2(1.0, 0.0);
+        assert!((ray_circle(vec2(0.0, 0.0), right, vec2(5.0, 0.0), 1.0).unwrap() - 4.0).abs() < 1e-5);
+        assert!(ray_circle(vec2(0.0, 0.0), right, vec2(5.0, 2.0), 1.0).is_none());
+        assert!(ray_circle(vec2(0.0, 0.0), right, vec2(-5.0, 0.0), 1.0).is_none());
+        assert_eq!(ray_circle(vec2(5.0, 0.0), right, vec2(5.0, 0.0), 1.0), Some(0.0));
+
+        let b = square(3.0, -1.0, 2.0);
+        assert!((ray_polygon(vec2(0.0, 0.0), right, &b).unwrap() - 3.0).abs() < 1e-5);
+        assert!(ray_polygon(vec2(0.0, 0.0), vec2(0.0, 1.0), &b).is_none());
+        assert_eq!(ray_polygon(vec2(4.0, 0.0), right, &b), Some(0.0));
+
+        let wall = Rect::new(vec2(10.0, 5.0), vec2(1.0, 10.0));
+        let t = raycast(vec2(0.0, 0.0), right, &wall).unwrap();
+        assert!((t - 10.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn winding() {
+        let ccw = square(0.0, 0.0, 4.0);
+        let cw: Vec<_> = ccw.iter().rev().copied().collect();
+        assert_eq!(winding_number(&ccw, vec2(2.0, 2.0)), 1);
+        assert_eq!(winding_number(&cw, vec2(2.0, 2.0)), -1);
+        assert_eq!(winding_number(&ccw, vec2(5.0, 2.0)), 0);
+
+        // a pentagram traced as one outline, the middle is wound twice and still counts as inside
+        let star: Vec<_> = (0..5).map(|i| {
+            let a = (144.0 * i as f32).to_radians();
+            vec2(a.sin() * 5.0, a.cos() * 5.0)
+        }).collect();
+        assert_eq!(winding_number(&star, vec2(0.0, 0.0)).abs(), 2);
+        assert!(point_in_polygon(&star, vec2(0.0, 0.0)));
+        assert!(!point_in_polygon(&star, vec2(4.0, -4.0)));
+    }
+
+    #[test]
+    fn polylines() {
+        let wall = vec![vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0)];
+        assert!(circle_polyline(vec2(5.0, 1.5), 1.0, &wall, 0.5));
+        assert!(!circle_polyline(vec2(5.0, 1.6), 1.0, &wall, 0.5));
+        assert!(!circle_polyline(vec2(5.0, 5.0), 1.0, &wall, 0.5));
+        assert!(circle_polyline(vec2(11.0, 5.0), 1.0, &wall, 0.5));
+
+        assert!(polygon_polyline(&square(4.0, -1.0, 2.0), &wall, 0.1));
+        assert!(polygon_polyline(&square(4.0, 1.0, 2.0), &wall, 1.0));
+        assert!(!polygon_polyline(&square(4.0, 1.0, 2.0), &wall, 0.5));
+        assert!(!polygon_polyline(&square(2.0, 2.0, 2.0), &wall, 0.5));
+
+        let other = vec![vec2(5.0, 5.0), vec2(5.0, 2.0)];
+        assert!(!polyline_polyline(&wall, 0.5, &other, 0.5));
+        assert!(polyline_polyline(&wall, 1.0, &other, 1.0));
+
+        let c = circle_polyline_contact(vec2(5.0, 1.0), 1.0, &wall, 0.5).unwrap();
+        assert_eq!(c.normal, vec2(0.0, -1.0));
+        assert!((c.depth - 0.5).abs() < 1e-5);
+
+        assert!((ray_polyline(vec2(5.0, 5.0), vec2(0.0, -1.0), &wall, 0.5).unwrap() - 4.5).abs() < 1e-5);
+        assert!((ray_polyline(vec2(5.0, 5.0), vec2(1.0, 0.0), &wall, 0.5).unwrap() - 4.5).abs() < 1e-5);
+        assert!(ray_polyline(vec2(5.0, 5.0), vec2(-1.0, 0.0), &wall, 0.5).is_none());
+    }
+
+    #[test]
+    fn distances() {
+        let circle = Circle::new(vec2(0.0, 0.0), 2.0);
+        assert!((signed_distance(&circle, vec2(5.0, 0.0)) - 3.0).abs() < 1e-5);
+        assert!((signed_distance(&circle, vec2(0.5, 0.0)) + 1.5).abs() < 1e-5);
+        assert_eq!(closest_point(&circle, vec2(0.0, -7.0)), vec2(0.0, -2.0));
+
+        let rect = Rect::new(vec2(0.0, 10.0), vec2(10.0, 10.0));
+        assert!((signed_distance(&rect, vec2(13.0, 5.0)) - 3.0).abs() < 1e-5);
+        assert!((signed_distance(&rect, vec2(2.0, 5.0)) + 2.0).abs() < 1e-5);
+        assert_eq!(closest_point(&rect, vec2(5.0, 9.0)), vec2(5.0, 10.0));
+        assert_eq!(closest_point(&rect, vec2(-3.0, 14.0)), vec2(0.0, 10.0));
+
+        let wall = Polyline::new(vec![vec2(0.0, 0.0), vec2(10.0, 0.0)], 1.0);
+        assert!((signed_distance(&wall, vec2(5.0, 4.0)) - 3.0).abs() < 1e-5);
+        assert!((signed_distance(&wall, vec2(5.0, 0.5)) + 0.5).abs() < 1e-5);
+        assert_eq!(closest_point(&wall, vec2(12.0, 0.0)), vec2(11.0, 0.0));
+        assert!((wall.distance_to(vec2(5.0, -2.0)) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let circle = Circle::new(vec2(1.0, 1.0), 2.0);
+        assert_eq!(circle.aabb(), Aabb::new(vec2(-1.0, -1.0), vec2(3.0, 3.0)));
+
+        let rect = Rect::new(vec2(0.0, 10.0), vec2(4.0, 2.0));
+        assert_eq!(rect.aabb(), Aabb::new(vec2(0.0, 8.0), vec2(4.0, 10.0)));
+
+        let wall = Polyline::new(vec![vec2(0.0, 0.0), vec2(5.0, 3.0)], 0.5);
+        assert_eq!(wall.aabb(), Aabb::new(vec2(-0.5, -0.5), vec2(5.5, 3.5)));
+
+        let hex = RegularPolygon::new(vec2(0.0, 0.0), 1.0, 4, 0.0);
+        let b = hex.aabb();
+        assert!((b.min.x + 1.0).abs() < 1e-5 && (b.max.y - 1.0).abs() < 1e-5);
+
+        assert!(circle.aabb().overlaps(&hex.aabb()));
+        assert!(!rect.aabb().overlaps(&circle.aabb()));
+        assert!(!Aabb::from_points(&[]).overlaps(&circle.aabb()));
+    }
+
+    #[test]
+    fn swept_circles() {
+        // a wall much thinner than the ant, which a discrete check would step right over
+        let wall = Rect::new(vec2(10.0, 10.0), vec2(0.1, 20.0));
+        let delta = vec2(20.0, 0.0);
+        assert!(!collides(&Circle::new(vec2(20.0, 0.0), 2.0), &wall));
+
+        let t = sweep_circle(vec2(0.0, 0.0), 2.0, delta, &wall).unwrap();
+        assert!((t - 0.4).abs() < 1e-5);
+        assert!(sweep_circle(vec2(0.0, 0.0), 2.0, vec2(5.0, 0.0), &wall).is_none());
+        assert!(sweep_circle(vec2(0.0, 15.0), 2.0, delta, &wall).is_none());
+        assert_eq!(sweep_circle(vec2(9.0, 0.0), 2.0, delta, &wall), Some(0.0));
+
+        // touching the wall, it can back off or slide along but not go further in
+        let touching = vec2(8.0, 0.0);
+        assert_eq!(sweep_circle(touching, 2.0, vec2(1.0, 0.0), &wall), Some(0.0));
+        assert!(sweep_circle(touching, 2.0, vec2(-5.0, 0.0), &wall).is_none());
+        assert!(sweep_circle(touching, 2.0, vec2(0.0, 3.0), &wall).is_none());
+        assert!(sweep_circle(touching, 2.0, vec2(0.0, 0.0), &wall).is_none());
+
+        let post = Circle::new(vec2(10.0, 0.0), 1.0);
+        assert!((sweep_circle(vec2(0.0, 0.0), 2.0, delta, &post).unwrap() - 0.35).abs() < 1e-5);
+        assert!(sweep_circle(vec2(10.0, 3.0), 2.0, vec2(0.0, 6.0), &post).is_none());
+
+        let line = Polyline::new(vec![vec2(10.0, -10.0), vec2(10.0, 10.0)], 0.0);
+        assert!((sweep_circle(vec2(0.0, 0.0), 2.0, delta, &line).unwrap() - 0.4).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,610 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// Genetic programming over ant brains: random programs, mutation and crossover.
+// Everything it hands back is within the limits and passes the type checker, anything that
+// wouldn't is thrown away and tried again, falling back to the parent if that keeps failing.
+
+use std::mem;
+
+use glm::vec2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{ant::{Condition, Decision, Location, Memories, Policy, Program, Source, Then}, typecheck::{self, Type}};
+
+// how many times to try making something valid before giving up
+const TRIES: usize = 32;
+// generated memory reads only go this far back
+const MEMORY_READS: i32 = 3;
+// chance of a source or condition having more beneath it, when there's room
+const BRANCH_CHANCE: f64 = 0.4;
+
+type Binary<T> = fn(Box<T>, Box<T>) -> T;
+
+const ARITH: [Binary<Source>; 6] = [
+    Source::Add, Source::Sub, Source::Mul, Source::Div, Source::Min, Source::Max,
+];
+const COMPARE: [fn(Source, Source) -> Condition; 3] = [Condition::GreaterThan, Condition::LessThan, Condition::Equal];
+const LOGIC: [Binary<Condition>; 3] = [Condition::And, Condition::Or, Condition::Xor];
+
+// How big programs are allowed to get
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Limits {
+    pub max_depth: usize, // levels below a decision, `always nothing` is 1 deep
+    pub max_size: usize, // nodes in one decision, not counting the decision itself
+    pub max_decisions: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_depth: 6, max_size: 24, max_decisions: 8 }
+    }
+}
+
+impl Limits {
+    // `always nothing` has to fit, or there'd be no program at all within them
+    fn check(&self) {
+        assert!(self.max_depth >= 1, "max_depth has to be at least 1");
+        assert!(self.max_size >= 1, "max_size has to be at least 1");
+        assert!(self.max_decisions >= 1, "max_decisions has to be at least 1");
+    }
+}
+
+// What a subtree is, only ones of the same kind can replace each other
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Kind {
+    Condition,
+    Source(Type),
+    Then,
+}
+
+enum Node<'a> {
+    Condition(&'a mut Condition),
+    Source(&'a mut Source),
+    Then(&'a mut Then),
+}
+
+enum Tree {
+    Condition(Condition),
+    Source(Source),
+    Then(Then),
+}
+
+impl Node<'_> {
+    fn cloned(&self) -> Tree {
+        match self {
+            Node::Condition(c) => Tree::Condition((*c).clone()),
+            Node::Source(s) => Tree::Source((*s).clone()),
+            Node::Then(t) => Tree::Then((*t).clone()),
+        }
+    }
+
+    fn graft(self, tree: Tree) -> bool {
+        match (self, tree) {
+            (Node::Condition(c), Tree::Condition(new)) => *c = new,
+            (Node::Source(s), Tree::Source(new)) => *s = new,
+            (Node::Then(t), Tree::Then(new)) => *t = new,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// the type of a source, assuming everything remembered is a number, which is all this remembers
+fn type_of(s: &Source) -> Type {
+    match s {
+        Source::Loc(_) | Source::Normalize(_) | Source::Where(_) => Type::Position,
+        Source::Dist(_) | Source::Food | Source::Number(_) | Source::Memory(_) | Source::Register(_) |
+        Source::PheromoneStrength | Source::State | Source::Dot(..) | Source::Length(_) => Type::Number,
+        Source::Bool(_) => Type::Bool,
+        Source::Tick => Type::Tick,
+        Source::Thing(_) => Type::Entity,
+        Source::Add(a, b) | Source::Sub(a, b) | Source::Mul(a, b) |
+        Source::Div(a, b) | Source::Min(a, b) | Source::Max(a, b) => match (type_of(a), type_of(b)) {
+            (Type::Position, _) | (_, Type::Position) => Type::Position,
+            _ => Type::Number,
+        },
+        Source::Abs(a) => match type_of(a) {
+            Type::Position => Type::Position,
+            _ => Type::Number,
+        },
+    }
+}
+
+fn operands(s: &Source) -> Vec<&Source> {
+    match s {
+        Source::Add(a, b) | Source::Sub(a, b) | Source::Mul(a, b) | Source::Div(a, b) |
+        Source::Min(a, b) | Source::Max(a, b) | Source::Dot(a, b) => vec![a, b],
+        Source::Abs(a) | Source::Length(a) | Source::Normalize(a) | Source::Where(a) => vec![a],
+        _ => Vec::new(),
+    }
+}
+
+fn operands_mut(s: &mut Source) -> Vec<&mut Source> {
+    match s {
+        Source::Add(a, b) | Source::Sub(a, b) | Source::Mul(a, b) | Source::Div(a, b) |
+        Source::Min(a, b) | Source::Max(a, b) | Source::Dot(a, b) => vec![a, b],
+        Source::Abs(a) | Source::Length(a) | Source::Normalize(a) | Source::Where(a) => vec![a],
+        _ => Vec::new(),
+    }
+}
+
+// Every node of a decision in preorder, with how far below the decision it is
+struct Survey(Vec<(Kind, usize)>);
+
+impl Survey {
+    fn of(d: &Decision) -> Vec<(Kind, usize)> {
+        let mut s = Survey(Vec::new());
+        match d {
+            Decision::If(c, t) => {
+                s.condition(c, 1);
+                s.then(t, 1);
+            },
+            Decision::IfHaveFood(t) | Decision::Always(t) => s.then(t, 1),
+        }
+        s.0
+    }
+
+    fn source(&mut self, s: &Source, level: usize) {
+        self.0.push((Kind::Source(type_of(s)), level));
+        for o in operands(s) {
+            self.source(o, level + 1);
+        }
+    }
+
+    fn condition(&mut self, c: &Condition, level: usize) {
+        self.0.push((Kind::Condition, level));
+        match c {
+            Condition::GreaterThan(a, b) | Condition::LessThan(a, b) |
+            Condition::Equal(a, b) | Condition::ApproxEqual(a, b, _) => {
+                self.source(a, level + 1);
+                self.source(b, level + 1);
+            },
+            Condition::Between(x, lo, hi) => {
+                self.source(x, level + 1);
+                self.source(lo, level + 1);
+                self.source(hi, level + 1);
+            },
+            Condition::Not(a) => self.condition(a, level + 1),
+            Condition::And(a, b) | Condition::Or(a, b) | Condition::Xor(a, b) => {
+                self.condition(a, level + 1);
+                self.condition(b, level + 1);
+            },
+        }
+    }
+
+    fn then(&mut self, t: &Then, level: usize) {
+        self.0.push((Kind::Then, level));
+        match t {
+            Then::EmitPheromone(s) => self.source(s, level + 1),
+            Then::Remember(s, next) | Then::Store(_, s, next) => {
+                self.source(s, level + 1);
+                self.then(next, level + 1);
+            },
+            Then::Forget(next) | Then::Clear(_, next) => self.then(next, level + 1),
+            Then::SetDest(_) | Then::Nothing => {},
+        }
+    }
+}
+
+// Finds the nth node of a decision, in the same order as Survey
+struct Seek(usize);
+
+impl Seek {
+    fn find(d: &mut Decision, n: usize) -> Option<Node<'_>> {
+        let mut s = Seek(n);
+        match d {
+            Decision::If(c, t) => s.condition(c).or_else(|| s.then(t)),
+            Decision::IfHaveFood(t) | Decision::Always(t) => s.then(t),
+        }
+    }
+
+    fn hit(&mut self) -> bool {
+        if self.0 == 0 {
+            return true;
+        }
+        self.0 -= 1;
+        false
+    }
+
+    fn source<'a>(&mut self, s: &'a mut Source) -> Option<Node<'a>> {
+        if self.hit() {
+            return Some(Node::Source(s));
+        }
+        operands_mut(s).into_iter().find_map(|o| self.source(o))
+    }
+
+    fn condition<'a>(&mut self, c: &'a mut Condition) -> Option<Node<'a>> {
+        if self.hit() {
+            return Some(Node::Condition(c));
+        }
+        match c {
+            Condition::GreaterThan(a, b) | Condition::LessThan(a, b) |
+            Condition::Equal(a, b) | Condition::ApproxEqual(a, b, _) => self.source(a).or_else(|| self.source(b)),
+            Condition::Between(x, lo, hi) => self.source(x).or_else(|| self.source(lo)).or_else(|| self.source(hi)),
+            Condition::Not(a) => self.condition(a),
+            Condition::And(a, b) | Condition::Or(a, b) | Condition::Xor(a, b) => {
+                self.condition(a).or_else(|| self.condition(b))
+            },
+        }
+    }
+
+    fn then<'a>(&mut self, t: &'a mut Then) -> Option<Node<'a>> {
+        if self.hit() {
+            return Some(Node::Then(t));
+        }
+        match t {
+            Then::EmitPheromone(s) => self.source(s),
+            Then::Remember(s, next) | Then::Store(_, s, next) => self.source(s).or_else(|| self.then(next)),
+            Then::Forget(next) | Then::Clear(_, next) => self.then(next),
+            Then::SetDest(_) | Then::Nothing => None,
+        }
+    }
+}
+
+// Breeds ant programs, seeded so the same seed always breeds the same ones
+pub struct Gp {
+    rng: StdRng,
+    limits: Limits,
+}
+
+impl Gp {
+    pub fn new(seed: u64, limits: Limits) -> Self {
+        limits.check();
+        Gp { rng: StdRng::seed_from_u64(seed), limits }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    // for anything else about breeding that's random, so one seed covers all of it
+    pub(crate) fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        limits.check();
+        self.limits = limits;
+    }
+
+    // Whether a program is within the limits and passes the type checker, for an ant
+    // that starts out remembering nothing
+    pub fn is_valid(&self, program: &Program) -> bool {
+        let fits = program.decisions().iter().all(|d| {
+            let nodes = Survey::of(d);
+            nodes.len() <= self.limits.max_size && nodes.iter().all(|&(_, level)| level <= self.limits.max_depth)
+        });
+
+        !program.is_empty() && program.len() <= self.limits.max_decisions && fits &&
+            typecheck::check(program, &Memories::new()).is_empty()
+    }
+
+    // A new program, running every decision that fires
+    pub fn random_program(&mut self) -> Program {
+        let mut program = Program::new(Policy::All, Vec::new());
+        for _ in 0..self.rng.gen_range(1..=self.limits.max_decisions) {
+            let d = self.valid_decision(&program);
+            program.push(d);
+        }
+        program
+    }
+
+    fn valid_decision(&mut self, program: &Program) -> Decision {
+        for _ in 0..TRIES {
+            let d = self.decision();
+            let mut p = program.clone();
+            p.push(d.clone());
+            if self.is_valid(&p) {
+                return d;
+            }
+        }
+        // can't go wrong, it doesn't touch memory and it's as small as decisions get
+        Decision::Always(Then::Nothing)
+    }
+
+    // Changes one node into another like it, keeping what's beneath it
+    pub fn point_mutation(&mut self, parent: &Program) -> Program {
+        self.retry(parent, |gp, child| {
+            let (d, n) = gp.pick_node(child)?;
+            let node = Seek::find(&mut child.decisions_mut()[d], n)?;
+            gp.relabel(node);
+            Some(())
+        })
+    }
+
+    // Replaces one subtree with a new random one of the same kind
+    pub fn subtree_mutation(&mut self, parent: &Program) -> Program {
+        self.retry(parent, |gp, child| {
+            let (d, n) = gp.pick_node(child)?;
+            let (kind, level) = Survey::of(&child.decisions()[d])[n];
+            let tree = gp.grow(kind, (gp.limits.max_depth + 1).checked_sub(level)?)?;
+            Seek::find(&mut child.decisions_mut()[d], n)?.graft(tree).then_some(())
+        })
+    }
+
+    // A copy of a with one of its subtrees swapped for one of the same kind from b
+    pub fn crossover(&mut self, a: &Program, b: &Program) -> Program {
+        let mut donors: Vec<(usize, usize, Kind)> = Vec::new();
+        for (i, d) in b.decisions().iter().enumerate() {
+            donors.extend(Survey::of(d).into_iter().enumerate().map(|(n, (kind, _))| (i, n, kind)));
+        }
+
+        self.retry(a, |gp, child| {
+            let (d, n) = gp.pick_node(child)?;
+            let (kind, _) = Survey::of(&child.decisions()[d])[n];
+            let fits: Vec<_> = donors.iter().filter(|donor| donor.2 == kind).collect();
+            if fits.is_empty() {
+                return None;
+            }
+
+            let &(i, m, _) = fits[gp.rng.gen_range(0..fits.len())];
+            let mut donor = b.decisions()[i].clone();
+            let tree = Seek::find(&mut donor, m)?.cloned();
+            Seek::find(&mut child.decisions_mut()[d], n)?.graft(tree).then_some(())
+        })
+    }
+
+    // runs f on copies of the parent until it makes a valid program different from it
+    fn retry<F>(&mut self, parent: &Program, mut f: F) -> Program
+    where
+        F: FnMut(&mut Self, &mut Program) -> Option<()>,
+    {
+        let before = parent.to_string();
+        for _ in 0..TRIES {
+            let mut child = parent.clone();
+            if f(self, &mut child).is_some() && self.is_valid(&child) && child.to_string() != before {
+                return child;
+            }
+        }
+        parent.clone()
+    }
+
+    // a random decision and node in it
+    fn pick_node(&mut self, program: &Program) -> Option<(usize, usize)> {
+        if program.is_empty() {
+            return None;
+        }
+        let d = self.rng.gen_range(0..program.len());
+        let n = self.rng.gen_range(0..Survey::of(&program.decisions()[d]).len());
+        Some((d, n))
+    }
+
+    fn relabel(&mut self, node: Node) {
+        match node {
+            Node::Source(s) => {
+                let ty = type_of(s);
+                *s = match mem::replace(s, Source::Food) {
+                    Source::Number(x) => Source::Number(x + self.number() / 4.0),
+                    Source::Dist(_) => Source::Dist(self.location()),
+                    Source::Loc(_) => Source::Loc(self.location()),
+                    Source::Add(a, b) | Source::Sub(a, b) | Source::Mul(a, b) |
+                    Source::Div(a, b) | Source::Min(a, b) | Source::Max(a, b) => self.pick(&ARITH)(a, b),
+                    Source::Abs(a) if ty == Type::Position => Source::Normalize(a),
+                    Source::Normalize(a) => Source::Abs(a),
+                    old @ (Source::Abs(_) | Source::Dot(..) | Source::Length(_) | Source::Where(_) | Source::Register(_)) => old,
+                    _ => self.terminal(ty),
+                };
+            },
+            Node::Condition(c) => {
+                *c = match mem::replace(c, Condition::Not(Box::new(Condition::Equal(Source::Food, Source::Food)))) {
+                    Condition::GreaterThan(a, b) | Condition::LessThan(a, b) | Condition::Equal(a, b) => {
+                        self.pick(&COMPARE)(a, b)
+                    },
+                    Condition::ApproxEqual(a, b, _) => Condition::ApproxEqual(a, b, self.number().abs() / 4.0),
+                    Condition::And(a, b) | Condition::Or(a, b) | Condition::Xor(a, b) => self.pick(&LOGIC)(a, b),
+                    Condition::Not(a) => *a,
+                    old @ Condition::Between(..) => old,
+                };
+            },
+            Node::Then(t) => {
+                *t = match mem::replace(t, Then::Nothing) {
+                    Then::SetDest(_) | Then::Nothing => self.leaf_then(),
+                    Then::Remember(_, next) => Then::Forget(next),
+                    Then::Forget(next) => Then::Remember(self.terminal(Type::Number), next),
+                    // registers are left to whoever named them
+                    old @ (Then::EmitPheromone(_) | Then::Store(..) | Then::Clear(..)) => old,
+                };
+            },
+        }
+    }
+
+    // a new subtree of the given kind, at most levels deep
+    fn grow(&mut self, kind: Kind, levels: usize) -> Option<Tree> {
+        match kind {
+            Kind::Condition if levels >= 2 => Some(Tree::Condition(self.condition(levels))),
+            Kind::Source(ty) if levels >= 1 => Some(Tree::Source(self.source(ty, levels))),
+            Kind::Then if levels >= 1 => Some(Tree::Then(self.then(levels))),
+            _ => None,
+        }
+    }
+
+    fn decision(&mut self) -> Decision {
+        let levels = self.limits.max_depth;
+        match self.rng.gen_range(0..4) {
+            0 => Decision::Always(self.then(levels)),
+            1 => Decision::IfHaveFood(self.then(levels)),
+            _ if levels >= 2 => Decision::If(self.condition(levels), self.then(levels)),
+            _ => Decision::Always(self.then(levels)),
+        }
+    }
+
+    // levels has to be at least 2, a condition always has something beneath it
+    fn condition(&mut self, levels: usize) -> Condition {
+        if levels > 2 && self.rng.gen_bool(BRANCH_CHANCE) {
+            return match self.rng.gen_range(0..4) {
+                0 => Condition::Not(Box::new(self.condition(levels - 1))),
+                _ => {
+                    let (a, b) = (self.condition(levels - 1), self.condition(levels - 1));
+                    self.pick(&LOGIC)(Box::new(a), Box::new(b))
+                },
+            };
+        }
+
+        let ty = if self.rng.gen_bool(0.75) { Type::Number } else { Type::Position };
+        let (a, b) = (self.source(ty, levels - 1), self.source(ty, levels - 1));
+        match self.rng.gen_range(0..5) {
+            0 => Condition::ApproxEqual(a, b, self.number().abs() / 4.0),
+            1 => Condition::Between(a, b, self.source(ty, levels - 1)),
+            _ => self.pick(&COMPARE)(a, b),
+        }
+    }
+
+    fn source(&mut self, ty: Type, levels: usize) -> Source {
+        if levels <= 1 || !self.rng.gen_bool(BRANCH_CHANCE) {
+            return self.terminal(ty);
+        }
+
+        let below = levels - 1;
+        let sub = |gp: &mut Self, ty| Box::new(gp.source(ty, below));
+        match (ty, self.rng.gen_range(0..6)) {
+            (Type::Number, 0) => Source::Abs(sub(self, Type::Number)),
+            (Type::Number, 1) => Source::Dot(sub(self, Type::Position), sub(self, Type::Position)),
+            (Type::Number, 2) => Source::Length(sub(self, Type::Position)),
+            (Type::Number, _) => {
+                let (a, b) = (sub(self, Type::Number), sub(self, Type::Number));
+                self.pick(&ARITH)(a, b)
+            },
+            (Type::Position, 0) => Source::Abs(sub(self, Type::Position)),
+            (Type::Position, 1) => Source::Normalize(sub(self, Type::Position)),
+            (Type::Position, n) => {
+                // a number with a position still makes a position
+                let (a, b) = match n {
+                    2 => (sub(self, Type::Number), sub(self, Type::Position)),
+                    3 => (sub(self, Type::Position), sub(self, Type::Number)),
+                    _ => (sub(self, Type::Position), sub(self, Type::Position)),
+                };
+                self.pick(&ARITH)(a, b)
+            },
+            _ => self.terminal(ty),
+        }
+    }
+
+    fn terminal(&mut self, ty: Type) -> Source {
+        match (ty, self.rng.gen_range(0..5)) {
+            (Type::Position, _) => Source::Loc(self.location()),
+            (Type::Number, 0) => Source::Dist(self.location()),
+            (Type::Number, 1) => Source::Food,
+            (Type::Number, 2) => Source::PheromoneStrength,
+            (Type::Number, 3) => Source::Memory(self.rng.gen_range(0..MEMORY_READS)),
+            (Type::Number, _) => Source::Number(self.number()),
+            // these only come up when replacing something a program was given to start with
+            (Type::Bool, _) => Source::Bool(self.rng.gen()),
+            (Type::Tick, _) => Source::Tick,
+            (Type::Entity, n) => Source::Thing(if n % 2 == 0 { Location::NearestWall } else { Location::PheromoneSrc }),
+        }
+    }
+
+    fn then(&mut self, levels: usize) -> Then {
+        if levels < 2 {
+            return self.leaf_then();
+        }
+        match self.rng.gen_range(0..10) {
+            0..=2 => Then::Remember(self.source(Type::Number, levels - 1), Box::new(self.then(levels - 1))),
+            3 => Then::Forget(Box::new(self.then(levels - 1))),
+            4 | 5 => Then::EmitPheromone(self.source(Type::Number, levels - 1)),
+            _ => self.leaf_then(),
+        }
+    }
+
+    fn leaf_then(&mut self) -> Then {
+        if self.rng.gen_bool(0.75) { Then::SetDest(self.location()) } else { Then::Nothing }
+    }
+
+    fn location(&mut self) -> Location {
+        match self.rng.gen_range(0..6) {
+            0 => Location::Home,
+            1 => Location::Dest,
+            2 => Location::Here,
+            3 => Location::PheromoneSrc,
+            4 => Location::NearestWall,
+            _ => Location::Pos(vec2(self.rng.gen_range(-20..=20) as f32, self.rng.gen_range(-20..=20) as f32)),
+        }
+    }
+
+    // to one decimal place, so programs print nicely
+    fn number(&mut self) -> f32 {
+        (self.rng.gen_range(-100..=100) as f32) / 10.0
+    }
+
+    fn pick<T: Copy>(&mut self, from: &[T]) -> T {
+        from[self.rng.gen_range(0..from.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded() {
+        let programs = |seed| {
+            let mut gp = Gp::new(seed, Limits::default());
+            (0..5).map(|_| gp.random_program().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(programs(3), programs(3));
+        assert_ne!(programs(3), programs(4));
+    }
+
+    #[test]
+    fn random_programs_are_valid() {
+        let mut gp = Gp::new(1, Limits::default());
+        for _ in 0..200 {
+            let mut p = gp.random_program();
+            assert!(gp.is_valid(&p), "{}", p);
+            assert!(p.compile().is_ok());
+        }
+    }
+
+    #[test]
+    fn breeding_stays_valid() {
+        for limits in [Limits::default(), Limits { max_depth: 3, max_size: 5, max_decisions: 2 }] {
+            let mut gp = Gp::new(2, limits);
+            let mut population: Vec<Program> = (0..10).map(|_| gp.random_program()).collect();
+            let mut changed = 0;
+            for i in 0..300 {
+                let parent = &population[i % 10];
+                let child = match i % 3 {
+                    0 => gp.point_mutation(parent),
+                    1 => gp.subtree_mutation(parent),
+                    _ => gp.crossover(parent, &population[(i + 3) % 10]),
+                };
+                assert!(gp.is_valid(&child), "{}", child);
+                if child.to_string() != parent.to_string() {
+                    changed += 1;
+                }
+                population[i % 10] = child;
+            }
+            assert!(changed > 200, "only {} changed", changed);
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let gp = Gp::new(0, Limits { max_depth: 2, max_size: 4, max_decisions: 1 });
+        let parse = |s| crate::dsl::parse_program(s).unwrap();
+        assert!(gp.is_valid(&parse("if food > 1 then nothing")));
+        // too big, too deep, too many and badly typed
+        assert!(!gp.is_valid(&parse("if food > 1 then remember(food), nothing")));
+        assert!(!gp.is_valid(&parse("if not food > 1 then nothing")));
+        assert!(!gp.is_valid(&parse("always nothing\nalways nothing")));
+        assert!(!gp.is_valid(&parse("if food > loc(home) then nothing")));
+        assert!(!gp.is_valid(&Program::default()));
+
+        // the smallest limits there can be still leave room for something
+        let mut gp = Gp::new(0, Limits { max_depth: 1, max_size: 1, max_decisions: 1 });
+        assert!((0..20).all(|_| {
+            let p = gp.random_program();
+            gp.is_valid(&p)
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "max_decisions has to be at least 1")]
+    fn no_room() {
+        Gp::new(0, Limits { max_decisions: 0, ..Limits::default() });
+    }
+}
@@ -0,0 +1,21 @@
+/*  
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+pub mod ant;
+pub mod brain;
+pub mod dsl;
+pub mod evolve;
+pub mod fsm;
+pub mod geometry;
+pub mod gp;
+pub mod loader;
+pub mod neural;
+pub mod shape;
+pub mod spatial;
+pub mod transform;
+pub mod typecheck;
+pub mod vm;
+pub mod world;
@@ -0,0 +1,640 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+use std::{cell::RefCell, fmt, fs::File, io, path::Path, rc::Rc, vec::Vec};
+use glm::{vec2, Vec2};
+
+use crate::{shape::{Circle, Polygon, Polyline, Rect, Shape}, world::Environment};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Svg(String),
+    Png(String),
+    Mask(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "io error: {}", e),
+            LoadError::Svg(e) => write!(f, "bad svg: {}", e),
+            LoadError::Png(e) => write!(f, "bad png: {}", e),
+            LoadError::Mask(e) => write!(f, "bad mask: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+// Anything a loader can produce, since Environment::add needs a concrete type
+#[derive(Clone)]
+pub enum Obstacle {
+    Circle(Circle),
+    Rect(Rect),
+    Polygon(Polygon),
+    Polyline(Polyline),
+}
+
+impl Obstacle {
+    pub fn add_to(self, env: &mut Environment, flags: i32) -> Rc<RefCell<dyn Shape>> {
+        match self {
+            Obstacle::Circle(s) => env.add(s, flags),
+            Obstacle::Rect(s) => env.add(s, flags),
+            Obstacle::Polygon(s) => env.add(s, flags),
+            Obstacle::Polyline(s) => env.add(s, flags),
+        }
+    }
+}
+
+// 2x3 affine matrix, as in svg's matrix(a b c d e f)
+#[derive(Clone, Copy, PartialEq)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn mul(&self, o: &Matrix) -> Matrix {
+        let (a, b) = (self.0, o.0);
+        Matrix([
+            a[0] * b[0] + a[2] * b[1], a[1] * b[0] + a[3] * b[1],
+            a[0] * b[2] + a[2] * b[3], a[1] * b[2] + a[3] * b[3],
+            a[0] * b[4] + a[2] * b[5] + a[4], a[1] * b[4] + a[3] * b[5] + a[5],
+        ])
+    }
+
+    fn apply(&self, p: Vec2) -> Vec2 {
+        let m = self.0;
+        vec2(m[0] * p.x + m[2] * p.y + m[4], m[1] * p.x + m[3] * p.y + m[5])
+    }
+
+    // only scales and moves things, so rects and circles stay rects and circles
+    fn is_axis_aligned(&self) -> bool {
+        self.0[1] == 0.0 && self.0[2] == 0.0
+    }
+
+    fn scale(&self) -> f32 {
+        ((self.0[0] * self.0[3] - self.0[1] * self.0[2]).abs()).sqrt()
+    }
+}
+
+fn numbers(s: &str) -> Vec<f32> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+
+    for c in s.chars() {
+        let starts_new = (c == '-' || c == '+') && !cur.is_empty() && !cur.ends_with(['e', 'E']);
+        let second_dot = c == '.' && cur.contains('.') && !cur.contains(['e', 'E']);
+        if c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E' {
+            if starts_new || second_dot {
+                out.extend(cur.parse::<f32>().ok());
+                cur.clear();
+            }
+            cur.push(c);
+        } else if !cur.is_empty() {
+            out.extend(cur.parse::<f32>().ok());
+            cur.clear();
+        }
+    }
+    out.extend(cur.parse::<f32>().ok());
+
+    out
+}
+
+fn parse_transform(s: &str) -> Result<Matrix, LoadError> {
+    let mut m = Matrix::IDENTITY;
+
+    for part in s.split(')').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, args) = part.split_once('(').ok_or_else(|| LoadError::Svg(format!("bad transform {}", s)))?;
+        let n = numbers(args);
+        let arg = |i: usize, default: f32| n.get(i).copied().unwrap_or(default);
+        let t = match name.trim().trim_start_matches(',').trim() {
+            "matrix" if n.len() == 6 => Matrix([n[0], n[1], n[2], n[3], n[4], n[5]]),
+            "translate" => Matrix([1.0, 0.0, 0.0, 1.0, arg(0, 0.0), arg(1, 0.0)]),
+            "scale" => Matrix([arg(0, 1.0), 0.0, 0.0, arg(1, arg(0, 1.0)), 0.0, 0.0]),
+            "rotate" => {
+                let (sin, cos) = arg(0, 0.0).to_radians().sin_cos();
+                let (cx, cy) = (arg(1, 0.0), arg(2, 0.0));
+                Matrix([1.0, 0.0, 0.0, 1.0, cx, cy])
+                    .mul(&Matrix([cos, sin, -sin, cos, 0.0, 0.0]))
+                    .mul(&Matrix([1.0, 0.0, 0.0, 1.0, -cx, -cy]))
+            },
+            other => return Err(LoadError::Svg(format!("unsupported transform {}", other))),
+        };
+        m = m.mul(&t);
+    }
+
+    Ok(m)
+}
+
+// Cubic and quadratic curves get cut into this many straight pieces, and arcs into this many per quarter turn
+const CURVE_STEPS: usize = 8;
+
+// Points along an elliptical arc from p1 to p2, not including p1, going from the endpoint
+// form svg uses to the center form (see the svg spec's implementation notes, section F.6.5)
+fn arc_points(p1: Vec2, radii: Vec2, rotation: f32, large: bool, sweep: bool, p2: Vec2) -> Vec<Vec2> {
+    let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+    if p1 == p2 {
+        return Vec::new();
+    }
+    if rx == 0.0 || ry == 0.0 {
+        return vec![p2];
+    }
+
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let h = (p1 - p2) / 2.0;
+    let (x1, y1) = (cos * h.x + sin * h.y, -sin * h.x + cos * h.y);
+
+    // radii too small to reach get scaled up until they just do
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let coef = (num / den).max(0.0).sqrt() * if large == sweep { -1.0 } else { 1.0 };
+    let (cx1, cy1) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+    let mid = (p1 + p2) / 2.0;
+    let c = vec2(cos * cx1 - sin * cy1 + mid.x, sin * cx1 + cos * cy1 + mid.y);
+
+    let angle = |u: Vec2, v: Vec2| (u.x * v.y - u.y * v.x).atan2(u.x * v.x + u.y * v.y);
+    let start = vec2((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let theta = angle(vec2(1.0, 0.0), start);
+    let mut delta = angle(start, vec2((-x1 - cx1) / rx, (-y1 - cy1) / ry));
+    if !sweep && delta > 0.0 {
+        delta -= std::f32::consts::TAU;
+    } else if sweep && delta < 0.0 {
+        delta += std::f32::consts::TAU;
+    }
+
+    let steps = ((CURVE_STEPS as f32 * delta.abs() / std::f32::consts::FRAC_PI_2).ceil() as usize).max(1);
+    let mut points: Vec<Vec2> = (1..steps).map(|s| {
+        let (s, k) = (theta + delta * (s as f32 / steps as f32)).sin_cos();
+        c + vec2(rx * cos * k - ry * sin * s, rx * sin * k + ry * cos * s)
+    }).collect();
+    points.push(p2);
+    points
+}
+
+// Splits path data into subpaths, each one a list of points and whether it was closed
+fn parse_path(d: &str) -> Result<Vec<(Vec<Vec2>, bool)>, LoadError> {
+    let mut paths = Vec::new();
+    let mut cur: Vec<Vec2> = Vec::new();
+    let mut pos = vec2(0.0, 0.0);
+    let mut start = pos;
+    let mut last_ctrl: Option<Vec2> = None;
+
+    // split into (command, numbers) pairs
+    let mut cmds: Vec<(char, Vec<f32>)> = Vec::new();
+    let mut buf = String::new();
+    let mut cmd = None;
+    for c in d.chars() {
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            if let Some(k) = cmd {
+                cmds.push((k, numbers(&buf)));
+            }
+            cmd = Some(c);
+            buf.clear();
+        } else {
+            buf.push(c);
+        }
+    }
+    if let Some(k) = cmd {
+        cmds.push((k, numbers(&buf)));
+    }
+
+    let bad = |k: char| LoadError::Svg(format!("bad arguments to path command {}", k));
+    for (k, n) in cmds {
+        let rel = k.is_ascii_lowercase();
+        let off = |p: Vec2, pos: Vec2| if rel { p + pos } else { p };
+        let arity = match k.to_ascii_uppercase() {
+            'M' | 'L' | 'T' => 2,
+            'H' | 'V' => 1,
+            'C' => 6,
+            'S' | 'Q' => 4,
+            'A' => 7,
+            'Z' => 0,
+            _ => return Err(LoadError::Svg(format!("unknown path command {}", k))),
+        };
+
+        if arity == 0 {
+            if cur.len() > 1 {
+                paths.push((std::mem::take(&mut cur), true));
+            }
+            cur.clear();
+            pos = start;
+            last_ctrl = None;
+            continue;
+        }
+        if n.is_empty() || n.len() % arity != 0 {
+            return Err(bad(k));
+        }
+
+        for (i, a) in n.chunks(arity).enumerate() {
+            match k.to_ascii_uppercase() {
+                'M' if i == 0 => {
+                    if cur.len() > 1 {
+                        paths.push((std::mem::take(&mut cur), false));
+                    }
+                    cur.clear();
+                    pos = off(vec2(a[0], a[1]), pos);
+                    start = pos;
+                    cur.push(pos);
+                    last_ctrl = None;
+                    continue;
+                },
+                // extra pairs after a moveto are linetos
+                'M' | 'L' => pos = off(vec2(a[0], a[1]), pos),
+                'H' => pos = vec2(if rel { pos.x + a[0] } else { a[0] }, pos.y),
+                'V' => pos = vec2(pos.x, if rel { pos.y + a[0] } else { a[0] }),
+                'C' | 'S' => {
+                    let (c1, c2, end) = if arity == 6 {
+                        (off(vec2(a[0], a[1]), pos), off(vec2(a[2], a[3]), pos), off(vec2(a[4], a[5]), pos))
+                    } else {
+                        let c1 = last_ctrl.map_or(pos, |c| pos * 2.0 - c);
+                        (c1, off(vec2(a[0], a[1]), pos), off(vec2(a[2], a[3]), pos))
+                    };
+                    for s in 1..=CURVE_STEPS {
+                        let t = s as f32 / CURVE_STEPS as f32;
+                        let u = 1.0 - t;
+                        cur.push(pos * (u * u * u) + c1 * (3.0 * u * u * t) + c2 * (3.0 * u * t * t) + end * (t * t * t));
+                    }
+                    pos = end;
+                    last_ctrl = Some(c2);
+                    continue;
+                },
+                'Q' | 'T' => {
+                    let (c, end) = if arity == 4 {
+                        (off(vec2(a[0], a[1]), pos), off(vec2(a[2], a[3]), pos))
+                    } else {
+                        (last_ctrl.map_or(pos, |c| pos * 2.0 - c), off(vec2(a[0], a[1]), pos))
+                    };
+                    for s in 1..=CURVE_STEPS {
+                        let t = s as f32 / CURVE_STEPS as f32;
+                        let u = 1.0 - t;
+                        cur.push(pos * (u * u) + c * (2.0 * u * t) + end * (t * t));
+                    }
+                    pos = end;
+                    last_ctrl = Some(c);
+                    continue;
+                },
+                'A' => {
+                    let end = off(vec2(a[5], a[6]), pos);
+                    if cur.is_empty() {
+                        cur.push(start);
+                    }
+                    cur.extend(arc_points(pos, vec2(a[0], a[1]), a[2], a[3] != 0.0, a[4] != 0.0, end));
+                    pos = end;
+                    last_ctrl = None;
+                    continue;
+                },
+                _ => return Err(bad(k)),
+            }
+
+            if cur.is_empty() {
+                cur.push(start);
+            }
+            cur.push(pos);
+            last_ctrl = None;
+        }
+    }
+
+    if cur.len() > 1 {
+        paths.push((cur, false));
+    }
+
+    Ok(paths)
+}
+
+fn attr(node: &roxmltree::Node, name: &str) -> f32 {
+    node.attribute(name).and_then(|v| numbers(v).first().copied()).unwrap_or(0.0)
+}
+
+fn points_attr(node: &roxmltree::Node) -> Vec<Vec2> {
+    numbers(node.attribute("points").unwrap_or("")).chunks_exact(2).map(|p| vec2(p[0], p[1])).collect()
+}
+
+// display="none", either as an attribute or in the style
+fn hidden(node: &roxmltree::Node) -> bool {
+    let styled = node.attribute("style").is_some_and(|s| {
+        s.split(';').any(|d| d.split_once(':').is_some_and(|(k, v)| k.trim() == "display" && v.trim() == "none"))
+    });
+    styled || node.attribute("display").map(str::trim) == Some("none")
+}
+
+// stroke is the stroke width the parent had, children that don't set their own use that
+fn collect(node: roxmltree::Node, parent: Matrix, stroke: f32, out: &mut Vec<Obstacle>) -> Result<(), LoadError> {
+    // definitions, clip paths, markers and the like only show up where something else uses them
+    if hidden(&node) || matches!(node.tag_name().name(), "defs" | "clipPath" | "mask" | "symbol" | "marker" | "pattern") {
+        return Ok(());
+    }
+
+    let m = match node.attribute("transform") {
+        Some(t) => parent.mul(&parse_transform(t)?),
+        None => parent,
+    };
+    let stroke = node.attribute("stroke-width").map_or(stroke, |_| attr(&node, "stroke-width"));
+    let half_stroke = stroke / 2.0 * m.scale();
+    let poly = |points: Vec<Vec2>| Obstacle::Polygon(Polygon::new(points.into_iter().map(|p| m.apply(p)).collect()));
+    let line = |points: Vec<Vec2>| Obstacle::Polyline(Polyline::new(points.into_iter().map(|p| m.apply(p)).collect(), half_stroke));
+
+    match node.tag_name().name() {
+        "rect" => {
+            let (x, y, w, h) = (attr(&node, "x"), attr(&node, "y"), attr(&node, "width"), attr(&node, "height"));
+            if m.is_axis_aligned() {
+                let (a, b) = (m.apply(vec2(x, y)), m.apply(vec2(x + w, y + h)));
+                let (lo, hi) = (vec2(a.x.min(b.x), a.y.min(b.y)), vec2(a.x.max(b.x), a.y.max(b.y)));
+                out.push(Obstacle::Rect(Rect::new(vec2(lo.x, hi.y), hi - lo)));
+            } else {
+                out.push(poly(vec![vec2(x, y), vec2(x + w, y), vec2(x + w, y + h), vec2(x, y + h)]));
+            }
+        },
+        "circle" | "ellipse" => {
+            let c = vec2(attr(&node, "cx"), attr(&node, "cy"));
+            let (rx, ry) = if node.tag_name().name() == "circle" {
+                (attr(&node, "r"), attr(&node, "r"))
+            } else {
+                (attr(&node, "rx"), attr(&node, "ry"))
+            };
+            if rx == ry && m.is_axis_aligned() && m.0[0].abs() == m.0[3].abs() {
+                out.push(Obstacle::Circle(Circle::new(m.apply(c), rx * m.scale())));
+            } else {
+                out.push(poly((0..32).map(|i| {
+                    let a = (i as f32 * 360.0 / 32.0).to_radians();
+                    c + vec2(rx * a.cos(), ry * a.sin())
+                }).collect()));
+            }
+        },
+        "line" => out.push(line(vec![
+            vec2(attr(&node, "x1"), attr(&node, "y1")),
+            vec2(attr(&node, "x2"), attr(&node, "y2"))
+        ])),
+        "polyline" => out.push(line(points_attr(&node))),
+        "polygon" => out.push(poly(points_attr(&node))),
+        "path" => {
+            let filled = node.attribute("fill") != Some("none");
+            for (points, closed) in parse_path(node.attribute("d").unwrap_or(""))? {
+                if closed || filled {
+                    out.push(poly(points));
+                } else {
+                    out.push(line(points));
+                }
+            }
+        },
+        _ => {
+            for child in node.children().filter(|c| c.is_element()) {
+                collect(child, m, stroke, out)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+// Turns the rects, circles, lines, polygons and paths of an svg into obstacles.
+// The y axis gets flipped so the bottom of the image ends up at y=0.
+pub fn svg_obstacles(src: &str) -> Result<Vec<Obstacle>, LoadError> {
+    let doc = roxmltree::Document::parse(src).map_err(|e| LoadError::Svg(e.to_string()))?;
+    let root = doc.root_element();
+
+    let height = match root.attribute("viewBox").map(numbers) {
+        Some(vb) if vb.len() == 4 => vb[1] + vb[3],
+        _ => attr(&root, "height"),
+    };
+    let flip = Matrix([1.0, 0.0, 0.0, -1.0, 0.0, height]);
+
+    let mut out = Vec::new();
+    collect(root, flip, 1.0, &mut out)?;
+    Ok(out)
+}
+
+pub fn load_svg<P: AsRef<Path>>(path: P) -> Result<Vec<Obstacle>, LoadError> {
+    svg_obstacles(&std::fs::read_to_string(path)?)
+}
+
+// Merges the set pixels of a mask into as few rects as it easily can, each pixel becoming a scale sized square.
+// Row 0 is the top of the image, so like svg_obstacles the bottom ends up at y=0.
+pub fn mask_obstacles(width: usize, height: usize, mask: &[bool], scale: f32) -> Result<Vec<Obstacle>, LoadError> {
+    if width.checked_mul(height).is_none_or(|n| mask.len() < n) {
+        return Err(LoadError::Mask(format!("{} pixels is too few for {}x{}", mask.len(), width, height)));
+    }
+
+    let mut out = Vec::new();
+    // runs from the previous row that might still grow downwards, as (start, end, first row)
+    let mut open: Vec<(usize, usize, usize)> = Vec::new();
+
+    let finish = |(x0, x1, y0): (usize, usize, usize), y1: usize, out: &mut Vec<Obstacle>| {
+        out.push(Obstacle::Rect(Rect::new(
+            vec2(x0 as f32 * scale, (height - y0) as f32 * scale),
+            vec2((x1 - x0) as f32 * scale, (y1 - y0) as f32 * scale),
+        )));
+    };
+
+    for y in 0..=height {
+        let mut runs = Vec::new();
+        if y < height {
+            let row = &mask[y * width..(y + 1) * width];
+            let mut x = 0;
+            while x < width {
+                if row[x] {
+                    let start = x;
+                    while x < width && row[x] {
+                        x += 1;
+                    }
+                    runs.push((start, x));
+                } else {
+                    x += 1;
+                }
+            }
+        }
+
+        let mut next = Vec::new();
+        for run in open.drain(..) {
+            if let Some(i) = runs.iter().position(|r| r.0 == run.0 && r.1 == run.1) {
+                runs.remove(i);
+                next.push(run);
+            } else {
+                finish(run, y, &mut out);
+            }
+        }
+        next.extend(runs.into_iter().map(|(a, b)| (a, b, y)));
+        open = next;
+    }
+
+    Ok(out)
+}
+
+// Dark, opaque pixels of a png become walls
+pub fn load_png_mask<P: AsRef<Path>>(path: P, scale: f32) -> Result<Vec<Obstacle>, LoadError> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| LoadError::Png(e.to_string()))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| LoadError::Png(e.to_string()))?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let mask: Vec<bool> = buf[..info.buffer_size()].chunks(channels).map(|px| {
+        let (lum, alpha) = match channels {
+            1 => (px[0] as f32, 255.0),
+            2 => (px[0] as f32, px[1] as f32),
+            3 => (0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32, 255.0),
+            _ => (0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32, px[3] as f32),
+        };
+        lum < 128.0 && alpha >= 128.0
+    }).collect();
+
+    mask_obstacles(width, height, &mask, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Shape;
+
+    fn shape(o: &Obstacle) -> &dyn Shape {
+        match o {
+            Obstacle::Circle(s) => s,
+            Obstacle::Rect(s) => s,
+            Obstacle::Polygon(s) => s,
+            Obstacle::Polyline(s) => s,
+        }
+    }
+
+    #[test]
+    fn numbers_in_svg_style() {
+        assert_eq!(numbers("10,20 -3.5-4"), vec![10.0, 20.0, -3.5, -4.0]);
+        assert_eq!(numbers("1.5.5 1e2"), vec![1.5, 0.5, 100.0]);
+    }
+
+    #[test]
+    fn svg_shapes() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect x="10" y="10" width="20" height="5"/>
+            <circle cx="50" cy="50" r="4"/>
+            <g transform="translate(5, 0)">
+                <polyline points="0,90 40,90" stroke-width="2"/>
+            </g>
+            <path d="M 60 60 l 10 0 l 0 10 z"/>
+            <path d="M 0 0 C 10 0 10 10 20 10" fill="none"/>
+        </svg>"#;
+        let obstacles = svg_obstacles(svg).unwrap();
+        assert_eq!(obstacles.len(), 5);
+
+        // y is flipped, so the rect spanning y 10..15 in the svg spans 85..90
+        assert!(shape(&obstacles[0]).contains_point(vec2(20.0, 87.0)));
+        assert!(!shape(&obstacles[0]).contains_point(vec2(20.0, 12.0)));
+        assert!(shape(&obstacles[1]).contains_point(vec2(50.0, 47.0)));
+        assert!(shape(&obstacles[2]).contains_point(vec2(44.0, 10.5)));
+        assert!(!shape(&obstacles[2]).contains_point(vec2(2.0, 10.0)));
+        assert!(matches!(obstacles[3], Obstacle::Polygon(_)));
+        assert!(shape(&obstacles[3]).contains_point(vec2(68.0, 38.0)));
+        match &obstacles[4] {
+            Obstacle::Polyline(l) => assert_eq!(l.points().len(), CURVE_STEPS + 1),
+            _ => panic!("open unfilled path should be a wall"),
+        }
+    }
+
+    #[test]
+    fn svg_hidden_and_inherited() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <defs><rect x="0" y="0" width="10" height="10"/></defs>
+            <clipPath id="clip"><circle cx="5" cy="5" r="5"/></clipPath>
+            <marker id="arrow"><path d="M 0 0 L 4 2 L 0 4 z"/></marker>
+            <rect x="20" y="20" width="10" height="10" display="none"/>
+            <g style="fill: red; display: none"><rect x="40" y="40" width="10" height="10"/></g>
+            <g stroke-width="4">
+                <polyline points="0,90 40,90"/>
+                <g transform="scale(2)"><line x1="0" y1="10" x2="10" y2="10"/></g>
+                <line x1="0" y1="50" x2="10" y2="50" stroke-width="1"/>
+            </g>
+            <line x1="0" y1="70" x2="10" y2="70"/>
+        </svg>"#;
+        let obstacles = svg_obstacles(svg).unwrap();
+        let widths: Vec<f32> = obstacles.iter().map(|o| match o {
+            Obstacle::Polyline(l) => l.r,
+            _ => panic!("only the lines should be left"),
+        }).collect();
+        // the scaled one inherits the width and then gets scaled with everything else
+        assert_eq!(widths, [2.0, 4.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn svg_arcs() {
+        // half a circle over the top (in svg, where y goes down), then a quarter of an ellipse
+        let paths = parse_path("M 0 0 A 10 10 0 0 1 20 0 a 5 10 0 0 0 5 10").unwrap();
+        let points = &paths[0].0;
+        assert_eq!(points.len(), 1 + 2 * CURVE_STEPS + CURVE_STEPS);
+        assert_eq!(points[2 * CURVE_STEPS], vec2(20.0, 0.0));
+        assert_eq!(*points.last().unwrap(), vec2(25.0, 10.0));
+
+        let half = &points[..=2 * CURVE_STEPS];
+        assert!(half.iter().all(|p| (glm::distance(*p, vec2(10.0, 0.0)) - 10.0).abs() < 1e-4 && p.y <= 1e-4));
+        assert!(half.iter().any(|p| glm::distance(*p, vec2(10.0, -10.0)) < 1e-4));
+        // the quarter bulges out to the right, around a center at (20, 10)
+        assert!(points[2 * CURVE_STEPS + 1..].iter().all(|p| p.x >= 20.0 && p.y >= 0.0));
+
+        // too small to reach gets scaled up, and no radius at all is a straight line
+        let small = parse_path("M 0 0 A 1 1 0 0 0 4 0").unwrap();
+        assert!(small[0].0.iter().all(|p| (glm::distance(*p, vec2(2.0, 0.0)) - 2.0).abs() < 1e-4));
+        assert_eq!(parse_path("M 0 0 A 0 5 0 0 0 4 0").unwrap()[0].0, [vec2(0.0, 0.0), vec2(4.0, 0.0)]);
+    }
+
+    #[test]
+    fn svg_errors() {
+        assert!(svg_obstacles("<svg").is_err());
+        assert!(svg_obstacles(r#"<svg><path d="M 0 0 L 1"/></svg>"#).is_err());
+        assert!(svg_obstacles(r#"<svg><rect transform="skewX(3)"/></svg>"#).is_err());
+    }
+
+    #[test]
+    fn mask_merging() {
+        let m = |s: &str| s.chars().filter(|c| !c.is_whitespace()).map(|c| c == '#').collect::<Vec<_>>();
+        // a 4x3 mask, a block on the left and a single pixel on the right
+        let mask = m("##..
+                      ##.#
+                      ....");
+        let obstacles = mask_obstacles(4, 3, &mask, 2.0).unwrap();
+        assert_eq!(obstacles.len(), 2);
+
+        let block = shape(obstacles.iter().find(|o| matches!(o, Obstacle::Rect(r) if r.wh.y == 4.0)).unwrap()).aabb();
+        assert_eq!(block.min, vec2(0.0, 2.0));
+        assert_eq!(block.max, vec2(4.0, 6.0));
+        assert!(obstacles.iter().any(|o| shape(o).contains_point(vec2(7.0, 3.0))));
+        assert!(!obstacles.iter().any(|o| shape(o).contains_point(vec2(5.0, 5.0))));
+
+        assert!(matches!(mask_obstacles(4, 4, &mask, 1.0), Err(LoadError::Mask(_))));
+        assert!(matches!(mask_obstacles(usize::MAX, 2, &mask, 1.0), Err(LoadError::Mask(_))));
+        assert!(mask_obstacles(0, 0, &[], 1.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn png_mask() {
+        let path = std::env::temp_dir().join(format!("ants-mask-{}.png", std::process::id()));
+        {
+            let mut enc = png::Encoder::new(File::create(&path).unwrap(), 3, 2);
+            enc.set_color(png::ColorType::Grayscale);
+            enc.set_depth(png::BitDepth::Eight);
+            enc.write_header().unwrap().write_image_data(&[0, 255, 0, 0, 255, 255]).unwrap();
+        }
+
+        let obstacles = load_png_mask(&path, 1.0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(obstacles.len(), 2);
+        assert!(obstacles.iter().any(|o| shape(o).contains_point(vec2(0.5, 0.5))));
+        assert!(obstacles.iter().any(|o| shape(o).contains_point(vec2(2.5, 1.5))));
+        assert!(!obstacles.iter().any(|o| shape(o).contains_point(vec2(2.5, 0.5))));
+    }
+}
@@ -4,10 +4,6 @@
 *   Copyright (C) 2024 Teresa Maria Rivera
 */
 
-mod ant;
-mod shape;
-mod world;
-
 fn main() {
     /* TODO: stuff */
 }
\ No newline at end of file
@@ -0,0 +1,398 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// A tiny neural network brain. What the antennae smell, how far it is home and whether the ant
+// has food go in, through fully connected tanh layers, and out come how to steer and how likely
+// it is to leave a pheromone. All the weights are one flat list, which makes them easy to write
+// down, mutate and mix, and NeuroEvolution breeds them in the same scenarios programs get.
+
+use std::{fmt, iter, str::FromStr, vec::Vec};
+
+use glm::distance;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    brain::{Action, Brain, Senses},
+    dsl::ParseError,
+    evolve::{ranked, score, tournament, Fitness, Scenario},
+};
+
+// the pheromones at the left, middle and right antennae, how far home is, and having food
+pub const INPUTS: usize = 5;
+// the turn, the speed and the chance of emitting
+pub const OUTPUTS: usize = 3;
+// how strong the pheromones it leaves are
+pub const EMIT_STRENGTH: f32 = 2.0;
+// distances get divided by this, so they're about as big as the other inputs
+const DIST_SCALE: f32 = 100.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mlp {
+    pub(crate) sizes: Vec<usize>, // neurons in each layer, from the inputs to the outputs
+    pub(crate) weights: Vec<f32>, // every neuron's bias and then its weights, layer by layer
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+impl Mlp {
+    // With these hidden layers in between and every weight zero, none of them can be empty
+    pub fn new(hidden: &[usize]) -> Self {
+        assert!(!hidden.contains(&0), "hidden layers need at least one neuron");
+        let sizes: Vec<usize> = [INPUTS].into_iter().chain(hidden.iter().copied()).chain([OUTPUTS]).collect();
+        let weights = vec![0.0; Self::count(&sizes)];
+        Mlp { sizes, weights }
+    }
+
+    // weights anywhere from -1 to 1
+    pub fn random<R: Rng>(hidden: &[usize], rng: &mut R) -> Self {
+        let mut mlp = Self::new(hidden);
+        mlp.weights.iter_mut().for_each(|w| *w = rng.gen_range(-1.0..=1.0));
+        mlp
+    }
+
+    // None unless the layers go from INPUTS to OUTPUTS and there are just enough weights for them
+    pub fn from_weights(sizes: Vec<usize>, weights: Vec<f32>) -> Option<Self> {
+        let fits = sizes.len() >= 2 && sizes[0] == INPUTS && sizes[sizes.len() - 1] == OUTPUTS &&
+            !sizes.contains(&0) && weights.len() == Self::count(&sizes);
+        fits.then_some(Mlp { sizes, weights })
+    }
+
+    // how many weights layers of these sizes need, biases included
+    fn count(sizes: &[usize]) -> usize {
+        sizes.windows(2).map(|w| (w[0] + 1) * w[1]).sum()
+    }
+
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn weights_mut(&mut self) -> &mut [f32] {
+        &mut self.weights
+    }
+
+    // What goes into the network, pheromones are squashed since they can get very strong up close
+    pub fn inputs(senses: &Senses) -> [f32; INPUTS] {
+        let [left, ahead, right] = senses.antennae().map(f32::ln_1p);
+        let home = distance(senses.home(), senses.pos()) / DIST_SCALE;
+        [left, ahead, right, home, if senses.has_food() { 1.0 } else { 0.0 }]
+    }
+
+    // The turn from -1 to 1, and the speed and chance of emitting from 0 to 1
+    pub fn forward(&self, inputs: &[f32; INPUTS]) -> [f32; OUTPUTS] {
+        let mut layer = inputs.to_vec();
+        let mut weights = &self.weights[..];
+        let hidden = self.sizes.len() - 2;
+        for (i, w) in self.sizes.windows(2).enumerate() {
+            layer = (0..w[1]).map(|_| {
+                let (neuron, rest) = weights.split_at(w[0] + 1);
+                weights = rest;
+                let sum = neuron[0] + neuron[1..].iter().zip(&layer).map(|(w, x)| w * x).sum::<f32>();
+                if i < hidden { sum.tanh() } else { sum }
+            }).collect();
+        }
+        [layer[0].tanh(), sigmoid(layer[1]), sigmoid(layer[2])]
+    }
+}
+
+impl Brain for Mlp {
+    fn think(&mut self, senses: &Senses) -> Vec<Action> {
+        let [turn, speed, emit] = self.forward(&Self::inputs(senses));
+        let mut actions = vec![Action::Steer(turn, speed)];
+        if senses.random() < emit {
+            actions.push(Action::EmitPheromone(EMIT_STRENGTH));
+        }
+        actions
+    }
+}
+
+// Written as `mlp` and the layer sizes, then a line for each neuron's bias and weights.
+// Numbers are printed exactly, so this parses back into the same network.
+impl fmt::Display for Mlp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mlp")?;
+        for n in &self.sizes {
+            write!(f, " {}", n)?;
+        }
+        writeln!(f)?;
+
+        let neurons = self.sizes.windows(2).flat_map(|w| iter::repeat_n(w[0] + 1, w[1]));
+        let mut weights = &self.weights[..];
+        for n in neurons {
+            let (neuron, rest) = weights.split_at(n);
+            weights = rest;
+            let line: Vec<String> = neuron.iter().map(|w| w.to_string()).collect();
+            writeln!(f, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Mlp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |line: usize, col: usize, message: String| ParseError { line: line + 1, col: col + 1, message };
+        // every word with where it is, blank lines and # comments skipped
+        let mut words = s.lines().enumerate().flat_map(|(l, line)| {
+            let line = line.split('#').next().unwrap_or_default();
+            line.split_whitespace().map(move |w| (l, w.as_ptr() as usize - line.as_ptr() as usize, w))
+        });
+
+        let (mut sizes, mut weights) = (Vec::new(), Vec::new());
+        match words.next() {
+            Some((l, _, "mlp")) => {
+                // the sizes are whatever follows on the same line
+                let mut words = words.by_ref().peekable();
+                while let Some((_, c, w)) = words.next_if(|(line, _, _)| *line == l) {
+                    sizes.push(w.parse().map_err(|_| error(l, c, format!("bad layer size `{}`", w)))?);
+                }
+                for (l, c, w) in words {
+                    weights.push(w.parse().map_err(|_| error(l, c, format!("bad weight `{}`", w)))?);
+                }
+            },
+            Some((l, c, w)) => return Err(error(l, c, format!("expected `mlp`, found `{}`", w))),
+            None => return Err(error(0, 0, "expected `mlp`".to_string())),
+        }
+
+        let (expected, found) = (Self::count(&sizes), weights.len());
+        Mlp::from_weights(sizes, weights).ok_or_else(|| {
+            let lines = s.lines().count().max(1);
+            error(lines - 1, 0, format!(
+                "layers have to go from {} inputs to {} outputs, with {} weights for them, found {}",
+                INPUTS, OUTPUTS, expected, found,
+            ))
+        })
+    }
+}
+
+// How a population of networks breeds
+#[derive(Clone, Debug, PartialEq)]
+pub struct NeuroSettings {
+    pub population: usize,
+    pub tournament: usize, // how many compete for each parent
+    pub elites: usize, // the best few go to the next generation as they are
+    pub crossover: f64, // chance of a child taking each weight from either of two parents
+    pub mutation: f64, // chance of each weight being nudged
+    pub mutation_size: f32, // how far a weight can be nudged either way
+    pub hidden: Vec<usize>,
+}
+
+impl Default for NeuroSettings {
+    fn default() -> Self {
+        NeuroSettings {
+            population: 50,
+            tournament: 3,
+            elites: 1,
+            crossover: 0.5,
+            mutation: 0.1,
+            mutation_size: 0.5,
+            hidden: vec![6],
+        }
+    }
+}
+
+// A population of networks being evolved in one scenario, like Evolution does for programs
+pub struct NeuroEvolution {
+    scenario: Scenario,
+    fitness: Box<dyn Fitness>,
+    settings: NeuroSettings,
+    rng: StdRng,
+    generation: usize,
+    population: Vec<Mlp>,
+    scores: Option<Vec<f32>>, // for this generation, once it's been run
+}
+
+impl NeuroEvolution {
+    // starts from random networks, the seed decides them and everything else random about breeding
+    pub fn new<F: Fitness + 'static>(scenario: Scenario, fitness: F, settings: NeuroSettings, seed: u64) -> Self {
+        assert!(settings.population > 0, "a population needs at least one network");
+        let mut rng = StdRng::seed_from_u64(seed);
+        let population = (0..settings.population).map(|_| Mlp::random(&settings.hidden, &mut rng)).collect();
+        NeuroEvolution {
+            scenario,
+            fitness: Box::new(fitness),
+            settings,
+            rng,
+            generation: 0,
+            population,
+            scores: None,
+        }
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn population(&self) -> &[Mlp] {
+        &self.population
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn settings(&self) -> &NeuroSettings {
+        &self.settings
+    }
+
+    // Runs everyone in this generation that hasn't been yet and returns their scores
+    pub fn evaluate(&mut self) -> &[f32] {
+        let scores = self.scores.get_or_insert_with(|| {
+            self.population.iter().map(|m| score(self.fitness.as_ref(), &self.scenario.run_brain(m))).collect()
+        });
+        scores
+    }
+
+    // The fittest network of this generation and its score
+    pub fn best(&mut self) -> (&Mlp, f32) {
+        let scores = self.evaluate();
+        let (i, score) = scores.iter().copied().enumerate().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        (&self.population[i], score)
+    }
+
+    // Scores this generation and breeds the next one
+    pub fn step(&mut self) {
+        let scores = self.evaluate().to_vec();
+        let mut next: Vec<Mlp> = ranked(&scores).iter().take(self.settings.elites).map(|&i| self.population[i].clone()).collect();
+
+        while next.len() < self.settings.population {
+            let a = tournament(&mut self.rng, &scores, self.settings.tournament);
+            let mut child = self.population[a].clone();
+            if self.rng.gen_bool(self.settings.crossover) {
+                let b = tournament(&mut self.rng, &scores, self.settings.tournament);
+                for (w, other) in child.weights.iter_mut().zip(&self.population[b].weights) {
+                    if self.rng.gen_bool(0.5) {
+                        *w = *other;
+                    }
+                }
+            }
+            for w in child.weights.iter_mut() {
+                if self.rng.gen_bool(self.settings.mutation) {
+                    *w += self.rng.gen_range(-1.0..=1.0) * self.settings.mutation_size;
+                }
+            }
+            next.push(child);
+        }
+
+        self.population = next;
+        self.scores = None;
+        self.generation += 1;
+    }
+
+    // Breeds this many generations
+    pub fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec2;
+    use crate::{ant::{Ant, Pheromones}, evolve::FoodDelivered, world::Environment};
+
+    #[test]
+    fn forward() {
+        let mlp = Mlp::new(&[4, 2]);
+        assert_eq!(mlp.sizes(), &[5, 4, 2, 3]);
+        assert_eq!(mlp.weights().len(), 6 * 4 + 5 * 2 + 3 * 3);
+        assert_eq!(mlp.forward(&[1.0; INPUTS]), [0.0, 0.5, 0.5]);
+
+        // straight from the inputs: turn towards the stronger side, always emit with food
+        let mut weights = vec![0.0; 3 * 6];
+        weights[1] = -1.0;
+        weights[3] = 1.0;
+        weights[6] = 3.0;
+        weights[12] = -10.0;
+        weights[17] = 20.0;
+        let mlp = Mlp::from_weights(vec![5, 3], weights).unwrap();
+        let [turn, speed, emit] = mlp.forward(&[0.0, 0.0, 2.0, 0.0, 1.0]);
+        assert!(turn > 0.9 && speed > 0.9 && emit > 0.99);
+        let [turn, _, emit] = mlp.forward(&[2.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(turn < -0.9 && emit < 0.01);
+
+        assert!(Mlp::from_weights(vec![5, 3], vec![0.0; 17]).is_none());
+        assert!(Mlp::from_weights(vec![4, 3], vec![0.0; 15]).is_none());
+        assert!(Mlp::from_weights(vec![5, 0, 3], vec![0.0; 3]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one neuron")]
+    fn empty_layers() {
+        Mlp::random(&[4, 0], &mut StdRng::seed_from_u64(1));
+    }
+
+    #[test]
+    fn serializing() {
+        let mlp = Mlp::random(&[3], &mut StdRng::seed_from_u64(1));
+        let text = mlp.to_string();
+        assert!(text.starts_with("mlp 5 3 3\n"));
+        assert_eq!(text.lines().count(), 1 + 3 + 3);
+        assert_eq!(text.parse::<Mlp>().unwrap(), mlp);
+
+        let e = "mlp 5 3\n0 0 0 0 0 x".parse::<Mlp>().unwrap_err();
+        assert_eq!((e.line, e.col, e.message.as_str()), (2, 11, "bad weight `x`"));
+        let e = "# a comment\n  net 5 3".parse::<Mlp>().unwrap_err();
+        assert_eq!((e.line, e.col), (2, 3));
+        let e = "mlp 5 3\n0 0 0".parse::<Mlp>().unwrap_err();
+        assert!(e.message.ends_with("with 18 weights for them, found 3"));
+    }
+
+    #[test]
+    fn steering() {
+        let mut env = Environment::new();
+        env.add(Pheromones::new(vec2(3.0, 3.0), 5.0), 2);
+
+        // turns towards the smell on the right, and never emits
+        let mut weights = vec![0.0; 3 * 6];
+        weights[1] = -1.0;
+        weights[3] = 1.0;
+        weights[6] = 3.0;
+        weights[12] = -10.0;
+        let mut a = Ant::new(vec2(0.0, 0.0), Mlp::from_weights(vec![5, 3], weights).unwrap());
+        a.set_turn_rate(90.0);
+        env.think(&mut a);
+        let (turn, speed) = a.steering().unwrap();
+        assert!(turn > 0.5 && speed > 0.9);
+        assert_eq!(env.things().len(), 1);
+    }
+
+    #[test]
+    fn evolving() {
+        let mut scenario = Scenario::new(vec2(0.0, 0.0), 3, 30);
+        scenario.add_food(vec2(0.0, 6.0));
+        let settings = NeuroSettings { population: 8, ..Default::default() };
+
+        let run = |seed| {
+            let mut evolution = NeuroEvolution::new(scenario.clone(), FoodDelivered, settings.clone(), seed);
+            let first = evolution.best().1;
+            evolution.run(3);
+            assert_eq!(evolution.generation(), 3);
+            assert_eq!(evolution.population().len(), 8);
+            // the elite carries over, and the same scenario scores it the same
+            assert!(evolution.best().1 >= first);
+            evolution.best().0.clone()
+        };
+        let best = run(4);
+        assert_eq!(best, run(4));
+        assert!(best.weights().iter().all(|w| w.is_finite()));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one network")]
+    fn empty_populations() {
+        let settings = NeuroSettings { population: 0, elites: 0, ..Default::default() };
+        NeuroEvolution::new(Scenario::new(vec2(0.0, 0.0), 1, 1), FoodDelivered, settings, 1);
+    }
+}
@@ -5,32 +5,778 @@
 */
 
 use std::vec::Vec;
-use glm::Vec2;
+use glm::{vec2, Vec2};
 use downcast_rs::{impl_downcast, Downcast};
 
+use crate::{geometry::{self, point_in_polygon, Contact}, world::square_dist};
+
 #[derive(Clone, Copy)]
 pub enum ShapeType {
     Circle,
     Rect,
     RegularPolygon,
     Star,
+    Polygon,
+    Polyline,
     Other
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum BasicShape {
     Circle(Vec2, f32),
     Rect(Vec2, Vec2),
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>, f32),
     Other,
 }
 
+// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Aabb { min, max }
+    }
+
+    // an empty list of points gives a box that overlaps nothing
+    pub fn from_points(points: &[Vec2]) -> Self {
+        points.iter().fold(Aabb::new(vec2(f32::MAX, f32::MAX), vec2(f32::MIN, f32::MIN)), |acc, p| {
+            Aabb::new(vec2(acc.min.x.min(p.x), acc.min.y.min(p.y)), vec2(acc.max.x.max(p.x), acc.max.y.max(p.y)))
+        })
+    }
+
+    pub fn around(c: Vec2, r: f32) -> Self {
+        Aabb::new(vec2(c.x - r, c.y - r), vec2(c.x + r, c.y + r))
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x &&
+        self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+
+    pub fn contains_point(&self, p: Vec2) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            vec2(self.min.x.min(other.min.x), self.min.y.min(other.min.y)), 
+            vec2(self.max.x.max(other.max.x), self.max.y.max(other.max.y))
+        )
+    }
+
+    pub fn expand(&self, r: f32) -> Aabb {
+        Aabb::new(vec2(self.min.x - r, self.min.y - r), vec2(self.max.x + r, self.max.y + r))
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+}
+
+#[allow(clippy::wrong_self_convention)]
 pub trait Shape: Downcast {
     fn collides(&self, shape: &dyn Shape) -> bool;
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact>;
+    // signed, negative inside the shape
+    fn distance_to(&self, p: Vec2) -> f32;
+    // closest point on the boundary of the shape
+    fn closest_point(&self, p: Vec2) -> Vec2;
     fn contains_point(&self, p: Vec2) -> bool;
     fn into_points(&self) -> Vec<Vec2>;
     fn get_center(&self) -> Vec2;
     fn get_shape_type(&self) -> ShapeType;
     fn into_basic_shape(&self) -> BasicShape;
+
+    fn aabb(&self) -> Aabb {
+        Aabb::from_points(&self.into_points())
+    }
+
+    fn convex_hull(&self) -> Vec<Vec2> {
+        convex_hull(&self.into_points())
+    }
+
+    fn triangulate(&self) -> Vec<[Vec2; 3]> {
+        triangulate(&self.into_points())
+    }
+
+    // the shape grown by r in every direction, e.g. an obstacle inflated by the radius of an ant
+    fn inflate(&self, r: f32) -> Polygon {
+        Polygon::new(offset(&self.into_points(), r))
+    }
+}
+
+impl_downcast!(Shape);
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    geometry::edges(points).map(|(a, b)| geometry::cross(a, b)).sum::<f32>() / 2.0
+}
+
+// The same outline, wound counter clockwise
+fn counter_clockwise(points: &[Vec2]) -> Vec<Vec2> {
+    if signed_area(points) < 0.0 {
+        points.iter().rev().copied().collect()
+    } else {
+        points.to_vec()
+    }
+}
+
+// Andrew's monotone chain, the result is counter clockwise without collinear points
+pub fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut hull: Vec<Vec2> = Vec::with_capacity(sorted.len() * 2);
+    for pass in 0..2 {
+        let start = hull.len();
+        for p in sorted.iter() {
+            while hull.len() >= start + 2 && geometry::cross(hull[hull.len() - 1] - hull[hull.len() - 2], *p - hull[hull.len() - 2]) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(*p);
+        }
+        // the last point of each half is the first of the other
+        hull.pop();
+        if pass == 0 {
+            sorted.reverse();
+        }
+    }
+
+    hull
+}
+
+// Ear clipping, works on any simple polygon no matter which way it's wound
+pub fn triangulate(points: &[Vec2]) -> Vec<[Vec2; 3]> {
+    let mut poly = counter_clockwise(points);
+    let mut tris = Vec::with_capacity(poly.len().saturating_sub(2));
+
+    while poly.len() > 3 {
+        let n = poly.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (poly[(i + n - 1) % n], poly[i], poly[(i + 1) % n]);
+            geometry::cross(b - a, c - b) > 0.0 && !poly.iter().enumerate().any(|(j, p)| {
+                j != i && j != (i + n - 1) % n && j != (i + 1) % n && point_in_polygon(&[a, b, c], *p)
+            })
+        // degenerate outlines might not have a proper ear, clip something anyway so this ends
+        }).unwrap_or(0);
+
+        tris.push([poly[(ear + n - 1) % n], poly[ear], poly[(ear + 1) % n]]);
+        poly.remove(ear);
+    }
+
+    if poly.len() == 3 {
+        tris.push([poly[0], poly[1], poly[2]]);
+    }
+
+    tris
+}
+
+// Corners of an offset outline get rounded off with this many points
+const OFFSET_ARC_STEPS: usize = 8;
+
+// Minkowski sum of an outline and a circle of radius r.
+// Convex corners get rounded, reflex ones get mitered, large r on spiky shapes can self intersect.
+pub fn offset(points: &[Vec2], r: f32) -> Vec<Vec2> {
+    let poly = counter_clockwise(points);
+    let n = poly.len();
+    if n < 3 {
+        // a point or a segment, grow the hull of its circles instead
+        let circles: Vec<Vec2> = poly.iter().flat_map(|p| (0..OFFSET_ARC_STEPS * 4).map(move |i| {
+            let a = (i as f32 * 360.0 / (OFFSET_ARC_STEPS * 4) as f32).to_radians();
+            *p + vec2(a.cos(), a.sin()) * r
+        })).collect();
+        return convex_hull(&circles);
+    }
+
+    let outward = |a: Vec2, b: Vec2| {
+        let e = b - a;
+        let len = (e.x * e.x + e.y * e.y).sqrt();
+        if len == 0.0 { vec2(0.0, 0.0) } else { vec2(e.y, -e.x) / len }
+    };
+
+    let mut out = Vec::new();
+    for i in 0..n {
+        let (p, v, q) = (poly[(i + n - 1) % n], poly[i], poly[(i + 1) % n]);
+        let (n1, n2) = (outward(p, v), outward(v, q));
+
+        if n1.x * n2.x + n1.y * n2.y > 1.0 - 1e-6 {
+            // straight on, there's no corner to round
+            out.push(v + n1 * r);
+        } else if geometry::cross(v - p, q - v) >= 0.0 {
+            // convex, sweep an arc from one edge normal to the other
+            let a1 = n1.y.atan2(n1.x);
+            let mut a2 = n2.y.atan2(n2.x);
+            while a2 < a1 {
+                a2 += std::f32::consts::TAU;
+            }
+            for s in 0..=OFFSET_ARC_STEPS {
+                let a = a1 + (a2 - a1) * (s as f32 / OFFSET_ARC_STEPS as f32);
+                out.push(v + vec2(a.cos(), a.sin()) * r);
+            }
+        } else {
+            let d = 1.0 + n1.x * n2.x + n1.y * n2.y;
+            out.push(v + (n1 + n2) * (r / d.max(0.1)));
+        }
+    }
+
+    out
+}
+
+// A plain circle, for food piles, nests and round obstacles.
+#[derive(Clone, Copy)]
+pub struct Circle {
+    pub(crate) pos: Vec2,
+    pub(crate) r: f32,
+}
+
+impl Circle {
+    pub fn new(pos: Vec2, r: f32) -> Self {
+        Circle { pos, r }
+    }
 }
 
-impl_downcast!(Shape);
\ No newline at end of file
+impl Shape for Circle {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        square_dist(self.pos, p) <= self.r.powi(2)
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        (0..128).map(|i| vec2(
+            self.pos.x + self.r * (2.8125 * (i as f32)).to_radians().sin(), 
+            self.pos.y + self.r * (2.8125 * (i as f32)).to_radians().cos()
+        )).collect()
+    }
+
+    fn get_center(&self) -> Vec2 {
+        self.pos
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        ShapeType::Circle
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        BasicShape::Circle(self.pos, self.r)
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::around(self.pos, self.r)
+    }
+}
+
+// An axis aligned rectangle, cl is the top left corner and the rect extends right and down (-y) by wh
+#[derive(Clone, Copy)]
+pub struct Rect {
+    pub(crate) cl: Vec2,
+    pub(crate) wh: Vec2,
+}
+
+impl Rect {
+    pub fn new(cl: Vec2, wh: Vec2) -> Self {
+        Rect { cl, wh }
+    }
+}
+
+impl Shape for Rect {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        p.x >= self.cl.x && p.x <= self.cl.x + self.wh.x &&
+        p.y <= self.cl.y && p.y >= self.cl.y - self.wh.y
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        vec![
+            self.cl, vec2(self.cl.x, self.cl.y - self.wh.y), 
+            vec2(self.cl.x + self.wh.x, self.cl.y - self.wh.y), vec2(self.cl.x + self.wh.x, self.cl.y),
+        ]
+    }
+
+    fn get_center(&self) -> Vec2 {
+        vec2(self.cl.x + (self.wh.x/2.0), self.cl.y - (self.wh.y/2.0))
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        ShapeType::Rect
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        BasicShape::Rect(self.cl, self.wh)
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::new(vec2(self.cl.x, self.cl.y - self.wh.y), vec2(self.cl.x + self.wh.x, self.cl.y))
+    }
+}
+
+// A regular polygon with its vertices on a circle of radius r, rotation is in degrees
+#[derive(Clone, Copy)]
+pub struct RegularPolygon {
+    pub(crate) pos: Vec2,
+    pub(crate) r: f32,
+    pub(crate) sides: u32,
+    pub(crate) rotation: f32,
+}
+
+impl RegularPolygon {
+    pub fn new(pos: Vec2, r: f32, sides: u32, rotation: f32) -> Self {
+        RegularPolygon { pos, r, sides: sides.max(3), rotation }
+    }
+}
+
+impl Shape for RegularPolygon {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        point_in_polygon(&self.into_points(), p)
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        let step = 360.0 / (self.sides as f32);
+        (0..self.sides).map(|i| vec2(
+            self.pos.x + self.r * (self.rotation + step * (i as f32)).to_radians().sin(), 
+            self.pos.y + self.r * (self.rotation + step * (i as f32)).to_radians().cos()
+        )).collect()
+    }
+
+    fn get_center(&self) -> Vec2 {
+        self.pos
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        ShapeType::RegularPolygon
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        BasicShape::Polygon(self.into_points())
+    }
+}
+
+// A star with its tips on a circle of radius outer and the dents between them on a circle of radius inner
+#[derive(Clone, Copy)]
+pub struct Star {
+    pub(crate) pos: Vec2,
+    pub(crate) outer: f32,
+    pub(crate) inner: f32,
+    pub(crate) points: u32,
+    pub(crate) rotation: f32,
+}
+
+impl Star {
+    pub fn new(pos: Vec2, outer: f32, inner: f32, points: u32, rotation: f32) -> Self {
+        Star { pos, outer, inner, points: points.max(2), rotation }
+    }
+}
+
+impl Shape for Star {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        point_in_polygon(&self.into_points(), p)
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        let step = 180.0 / (self.points as f32);
+        (0..(self.points * 2)).map(|i| {
+            let r = if i % 2 == 0 { self.outer } else { self.inner };
+            vec2(
+                self.pos.x + r * (self.rotation + step * (i as f32)).to_radians().sin(), 
+                self.pos.y + r * (self.rotation + step * (i as f32)).to_radians().cos()
+            )
+        }).collect()
+    }
+
+    fn get_center(&self) -> Vec2 {
+        self.pos
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        ShapeType::Star
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        BasicShape::Polygon(self.into_points())
+    }
+}
+
+// Any simple polygon, concave is fine, the last point connects back to the first
+#[derive(Clone)]
+pub struct Polygon {
+    pub(crate) points: Vec<Vec2>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        Polygon { points }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+}
+
+impl Shape for Polygon {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        point_in_polygon(&self.points, p)
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        self.points.clone()
+    }
+
+    fn get_center(&self) -> Vec2 {
+        geometry::centroid(&self.points)
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        ShapeType::Polygon
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        BasicShape::Polygon(self.points.clone())
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::from_points(&self.points)
+    }
+}
+
+// A wall following a chain of points that doesn't loop back, r is half of its thickness
+#[derive(Clone)]
+pub struct Polyline {
+    pub(crate) points: Vec<Vec2>,
+    pub(crate) r: f32,
+}
+
+impl Polyline {
+    pub fn new(points: Vec<Vec2>, r: f32) -> Self {
+        Polyline { points, r }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    // The outline of the wall grown by r on top of its own thickness. Going along the line and
+    // back again makes an outline with nothing inside, which offsetting turns into a sausage.
+    fn outline(&self, r: f32) -> Vec<Vec2> {
+        let n = self.points.len();
+        let there_and_back: Vec<Vec2> = self.points.iter().chain(self.points[1..n.saturating_sub(1)].iter().rev()).copied().collect();
+        offset(&there_and_back, self.r + r)
+    }
+}
+
+impl Shape for Polyline {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        !self.points.is_empty() && square_dist(geometry::closest_point_on_polyline(&self.points, p), p) <= self.r.powi(2)
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        self.points.clone()
+    }
+
+    fn get_center(&self) -> Vec2 {
+        geometry::centroid(&self.points)
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        ShapeType::Polyline
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        BasicShape::Polyline(self.points.clone(), self.r)
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::from_points(&self.points).expand(self.r)
+    }
+
+    fn triangulate(&self) -> Vec<[Vec2; 3]> {
+        triangulate(&self.outline(0.0))
+    }
+
+    fn inflate(&self, r: f32) -> Polygon {
+        Polygon::new(self.outline(r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(tris: &[[Vec2; 3]]) -> f32 {
+        tris.iter().map(|t| signed_area(t).abs()).sum()
+    }
+
+    fn near(a: Vec2, b: Vec2) -> bool {
+        square_dist(a, b) < 1e-8
+    }
+
+    #[test]
+    fn circles() {
+        let c = Circle::new(vec2(3.0, -1.0), 2.0);
+        assert!(c.contains_point(vec2(3.0, -1.0)) && c.contains_point(vec2(5.0, -1.0)));
+        assert!(!c.contains_point(vec2(4.5, 0.5)));
+
+        let points = c.into_points();
+        assert_eq!(points.len(), 128);
+        assert!(near(points[0], vec2(3.0, 1.0)));
+        assert!(near(points[32], vec2(5.0, -1.0)));
+        assert!(points.iter().all(|p| (square_dist(*p, c.get_center()).sqrt() - 2.0).abs() < 1e-5));
+
+        let aabb = c.aabb();
+        assert_eq!((aabb.min, aabb.max), (vec2(1.0, -3.0), vec2(5.0, 1.0)));
+    }
+
+    #[test]
+    fn rects() {
+        // the corner is the top left, and it goes down from there
+        let r = Rect::new(vec2(1.0, 4.0), vec2(3.0, 2.0));
+        assert!(r.contains_point(vec2(2.0, 3.0)) && r.contains_point(vec2(1.0, 2.0)) && r.contains_point(vec2(4.0, 4.0)));
+        assert!(!r.contains_point(vec2(2.0, 4.5)) && !r.contains_point(vec2(2.0, 1.5)) && !r.contains_point(vec2(0.5, 3.0)));
+        assert_eq!(r.into_points(), [vec2(1.0, 4.0), vec2(1.0, 2.0), vec2(4.0, 2.0), vec2(4.0, 4.0)]);
+        assert_eq!(r.get_center(), vec2(2.5, 3.0));
+
+        let aabb = r.aabb();
+        assert_eq!((aabb.min, aabb.max), (vec2(1.0, 2.0), vec2(4.0, 4.0)));
+        assert_eq!(aabb, Aabb::from_points(&r.into_points()));
+    }
+
+    #[test]
+    fn regular_polygons() {
+        // a square standing on one corner, the first vertex straight up
+        let diamond = RegularPolygon::new(vec2(0.0, 0.0), 2.0, 4, 0.0);
+        let points = diamond.into_points();
+        assert_eq!(points.len(), 4);
+        for (p, q) in points.iter().zip([vec2(0.0, 2.0), vec2(2.0, 0.0), vec2(0.0, -2.0), vec2(-2.0, 0.0)]) {
+            assert!(near(*p, q), "{:?} {:?}", p, q);
+        }
+        assert!(diamond.contains_point(vec2(0.9, 0.9)));
+        assert!(!diamond.contains_point(vec2(1.1, 1.1)));
+
+        let aabb = diamond.aabb();
+        assert!(near(aabb.min, vec2(-2.0, -2.0)) && near(aabb.max, vec2(2.0, 2.0)));
+
+        // turned 45 degrees it's a plain square, and anything under 3 sides is a triangle
+        let square = RegularPolygon::new(vec2(0.0, 0.0), 2.0, 4, 45.0);
+        assert!(square.contains_point(vec2(1.4, 1.4)));
+        assert!(!square.contains_point(vec2(0.0, 1.5)));
+        assert_eq!(RegularPolygon::new(vec2(0.0, 0.0), 1.0, 1, 0.0).into_points().len(), 3);
+    }
+
+    #[test]
+    fn stars() {
+        let star = Star::new(vec2(1.0, 1.0), 4.0, 1.0, 5, 0.0);
+        let points = star.into_points();
+        assert_eq!(points.len(), 10);
+        assert!(near(points[0], vec2(1.0, 5.0)));
+        for (i, p) in points.iter().enumerate() {
+            let r = if i % 2 == 0 { 4.0 } else { 1.0 };
+            assert!((square_dist(*p, star.get_center()).sqrt() - r).abs() < 1e-5);
+        }
+
+        // the tips are in, the gaps between them aren't
+        assert!(star.contains_point(vec2(1.0, 1.0)));
+        assert!(star.contains_point(vec2(1.0, 4.5)));
+        assert!(!star.contains_point(vec2(1.0 + 3.0 * 36f32.to_radians().sin(), 1.0 + 3.0 * 36f32.to_radians().cos())));
+
+        let aabb = star.aabb();
+        assert!(near(aabb.max, vec2(1.0 + 4.0 * 72f32.to_radians().sin(), 5.0)));
+        assert!(star.into_points().iter().all(|p| aabb.contains_point(*p)));
+    }
+
+    #[test]
+    fn hull() {
+        let points = vec![
+            vec2(0.0, 0.0), vec2(4.0, 0.0), vec2(4.0, 4.0), vec2(0.0, 4.0),
+            vec2(2.0, 2.0), vec2(1.0, 3.0), vec2(2.0, 0.0), vec2(4.0, 4.0),
+        ];
+        let h = convex_hull(&points);
+        assert_eq!(h.len(), 4);
+        assert!(signed_area(&h) > 0.0);
+        assert!((signed_area(&h) - 16.0).abs() < 1e-5);
+
+        let star = Star::new(vec2(0.0, 0.0), 5.0, 2.0, 5, 0.0);
+        let h = star.convex_hull();
+        assert_eq!(h.len(), 5);
+        assert!(geometry::is_convex(&h));
+        assert!(star.into_points().iter().all(|p| point_in_polygon(&h, *p)));
+    }
+
+    #[test]
+    fn triangles() {
+        let l = vec![
+            vec2(0.0, 0.0), vec2(6.0, 0.0), vec2(6.0, 2.0),
+            vec2(2.0, 2.0), vec2(2.0, 6.0), vec2(0.0, 6.0),
+        ];
+        let tris = triangulate(&l);
+        assert_eq!(tris.len(), 4);
+        assert!((area(&tris) - 20.0).abs() < 1e-4);
+        // nothing sticks out into the notch
+        assert!(!tris.iter().any(|t| point_in_polygon(t, vec2(4.0, 4.0))));
+
+        let cw: Vec<_> = l.iter().rev().copied().collect();
+        assert!((area(&triangulate(&cw)) - 20.0).abs() < 1e-4);
+
+        let star = Star::new(vec2(0.0, 0.0), 5.0, 2.0, 5, 0.0);
+        let tris = star.triangulate();
+        assert_eq!(tris.len(), 8);
+        assert!((area(&tris) - signed_area(&star.into_points()).abs()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn offsets() {
+        let square = Rect::new(vec2(0.0, 4.0), vec2(4.0, 4.0));
+        let grown = square.inflate(1.0);
+
+        assert!(grown.contains_point(vec2(-0.9, 2.0)));
+        assert!(grown.contains_point(vec2(2.0, 4.9)));
+        assert!(!grown.contains_point(vec2(-1.1, 2.0)));
+        // corners are rounded, not square
+        assert!(!grown.contains_point(vec2(-0.8, 4.8)));
+        assert!(grown.contains_point(vec2(-0.6, 4.6)));
+
+        // the notch of an L only gets a miter, and the outside corners stay a radius away
+        let l = Polygon::new(vec![
+            vec2(0.0, 0.0), vec2(6.0, 0.0), vec2(6.0, 2.0),
+            vec2(2.0, 2.0), vec2(2.0, 6.0), vec2(0.0, 6.0),
+        ]);
+        let grown = l.inflate(0.5);
+        assert!(grown.contains_point(vec2(2.4, 2.4)));
+        assert!(!grown.contains_point(vec2(2.6, 2.6)));
+        assert!(grown.into_points().iter().all(|p| l.distance_to(*p) > 0.49));
+
+        // going straight on doesn't pile up points
+        let flat = Polygon::new(vec![vec2(0.0, 0.0), vec2(2.0, 0.0), vec2(4.0, 0.0), vec2(4.0, 4.0), vec2(0.0, 4.0)]);
+        let points = flat.inflate(1.0).into_points();
+        assert!(points.windows(2).all(|w| square_dist(w[0], w[1]) > 1e-6));
+        assert_eq!(points.iter().filter(|p| p.y == -1.0).count(), 3);
+    }
+
+    #[test]
+    fn thick_polylines() {
+        // an L shaped wall 1 thick, grown by an ant's radius on both sides and around the ends
+        let wall = Polyline::new(vec![vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0)], 1.0);
+        let grown = wall.inflate(2.0);
+        for p in [vec2(5.0, 2.9), vec2(5.0, -2.9), vec2(-2.9, 0.0), vec2(12.9, 5.0), vec2(7.1, 5.0), vec2(10.0, 12.9)] {
+            assert!(grown.contains_point(p), "{:?}", p);
+        }
+        for p in [vec2(5.0, 3.1), vec2(5.0, -3.1), vec2(-3.1, 0.0), vec2(13.1, 5.0), vec2(6.9, 5.0), vec2(5.0, 5.0)] {
+            assert!(!grown.contains_point(p), "{:?}", p);
+        }
+        assert!(grown.into_points().iter().all(|p| (wall.distance_to(*p) - 2.0).abs() < 1e-3));
+
+        // the triangles cover the wall itself, thickness and all
+        let tris = wall.triangulate();
+        let area = area(&tris);
+        assert!(area > 38.0 && area < 20.0 * 2.0 + std::f32::consts::PI + 0.1, "{}", area);
+        assert!(tris.iter().any(|t| point_in_polygon(t, vec2(5.0, 0.9))));
+
+        // a single segment still works
+        let stick = Polyline::new(vec![vec2(0.0, 0.0), vec2(4.0, 0.0)], 0.5);
+        assert!(stick.inflate(1.0).contains_point(vec2(2.0, 1.4)));
+        assert!(!stick.inflate(1.0).contains_point(vec2(2.0, 1.6)));
+    }
+}
@@ -0,0 +1,173 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc, vec::Vec};
+use glm::{vec2, Vec2};
+
+use crate::shape::{Aabb, Shape};
+
+type Obj = Rc<RefCell<dyn Shape>>;
+type CellRange = (i32, i32, i32, i32);
+
+// A uniform grid, every object is stored in each cell its bounding box touches
+#[derive(Clone)]
+pub struct Grid {
+    cell: f32,
+    cells: HashMap<(i32, i32), Vec<Obj>>,
+    entries: HashMap<*const (), CellRange>,
+}
+
+impl Grid {
+    pub fn new(cell: f32) -> Self {
+        Grid {
+            cell,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn key(obj: &Obj) -> *const () {
+        Rc::as_ptr(obj) as *const ()
+    }
+
+    fn cell_range(&self, lo: Vec2, hi: Vec2) -> CellRange {
+        // keep huge or broken shapes from turning into billions of cells
+        let clamp = |v: f32| (v / self.cell).floor().clamp(-4096.0, 4096.0) as i32;
+        (clamp(lo.x), clamp(lo.y), clamp(hi.x), clamp(hi.y))
+    }
+
+    fn link(&mut self, obj: &Obj, range: CellRange) {
+        for x in range.0..=range.2 {
+            for y in range.1..=range.3 {
+                self.cells.entry((x, y)).or_default().push(Rc::clone(obj));
+            }
+        }
+    }
+
+    fn unlink(&mut self, obj: &Obj, range: CellRange) {
+        for x in range.0..=range.2 {
+            for y in range.1..=range.3 {
+                if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                    cell.retain(|o| !Rc::ptr_eq(o, obj));
+                    if cell.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, obj: &Obj) {
+        let b = obj.borrow().aabb();
+        let range = self.cell_range(b.min, b.max);
+
+        if let Some(old) = self.entries.insert(Self::key(obj), range) {
+            self.unlink(obj, old);
+        }
+        self.link(obj, range);
+    }
+
+    pub fn remove(&mut self, obj: &Obj) {
+        if let Some(old) = self.entries.remove(&Self::key(obj)) {
+            self.unlink(obj, old);
+        }
+    }
+
+    // Moves an object to the right cells after it changed, does nothing if it isn't in the grid
+    pub fn update(&mut self, obj: &Obj) {
+        let Some(old) = self.entries.get(&Self::key(obj)).copied() else {
+            return;
+        };
+
+        let b = obj.borrow().aabb();
+        let range = self.cell_range(b.min, b.max);
+        if range != old {
+            self.unlink(obj, old);
+            self.link(obj, range);
+            self.entries.insert(Self::key(obj), range);
+        }
+    }
+
+    // Everything whose cells overlap the box, this is a superset of what actually overlaps it
+    pub fn query(&self, lo: Vec2, hi: Vec2) -> Vec<Obj> {
+        let range = self.cell_range(lo, hi);
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+
+        for x in range.0..=range.2 {
+            for y in range.1..=range.3 {
+                for o in self.cells.get(&(x, y)).into_iter().flatten() {
+                    if seen.insert(Self::key(o)) {
+                        found.push(Rc::clone(o));
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
+    pub fn query_aabb(&self, b: &Aabb) -> Vec<Obj> {
+        self.query(b.min, b.max)
+    }
+
+    pub fn query_point(&self, p: Vec2) -> Vec<Obj> {
+        self.query(p, p)
+    }
+
+    pub fn query_radius(&self, p: Vec2, r: f32) -> Vec<Obj> {
+        self.query(vec2(p.x - r, p.y - r), vec2(p.x + r, p.y + r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{Circle, Rect};
+
+    fn obj<T: Shape>(s: T) -> Obj {
+        Rc::new(RefCell::new(s))
+    }
+
+    #[test]
+    fn insert_and_query() {
+        let mut grid = Grid::new(4.0);
+        let a = obj(Circle::new(vec2(1.0, 1.0), 1.0));
+        let b = obj(Rect::new(vec2(10.0, 30.0), vec2(20.0, 5.0)));
+        grid.insert(&a);
+        grid.insert(&b);
+
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.query_point(vec2(1.0, 1.0)).len(), 1);
+        assert!(Rc::ptr_eq(&grid.query_point(vec2(25.0, 27.0))[0], &b));
+        assert!(grid.query_point(vec2(100.0, 100.0)).is_empty());
+        assert_eq!(grid.query(vec2(0.0, 0.0), vec2(30.0, 30.0)).len(), 2);
+    }
+
+    #[test]
+    fn update_and_remove() {
+        let mut grid = Grid::new(4.0);
+        let a = obj(Circle::new(vec2(1.0, 1.0), 1.0));
+        grid.insert(&a);
+
+        a.borrow_mut().downcast_mut::<Circle>().unwrap().pos = vec2(50.0, 50.0);
+        grid.update(&a);
+        assert!(grid.query_point(vec2(1.0, 1.0)).is_empty());
+        assert_eq!(grid.query_point(vec2(50.0, 50.0)).len(), 1);
+
+        grid.remove(&a);
+        assert!(grid.is_empty());
+        assert!(grid.query_point(vec2(50.0, 50.0)).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+use std::vec::Vec;
+use glm::{vec2, Vec2};
+
+use crate::{geometry::{self, rect_points, Contact}, shape::{Aabb, BasicShape, Shape, ShapeType}};
+
+// Scale, then rotation, then translation, all around the center of whatever shape it's applied to.
+// Rotation is clockwise in degrees, the same as RegularPolygon and Star.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec2,
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+fn rotate(p: Vec2, deg: f32) -> Vec2 {
+    let (sin, cos) = deg.to_radians().sin_cos();
+    vec2(p.x * cos + p.y * sin, p.y * cos - p.x * sin)
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform { translation: vec2(0.0, 0.0), rotation: 0.0, scale: 1.0 }
+    }
+
+    pub fn new(translation: Vec2, rotation: f32, scale: f32) -> Self {
+        Transform { translation, rotation, scale }
+    }
+
+    pub fn translated(translation: Vec2) -> Self {
+        Transform { translation, ..Self::identity() }
+    }
+
+    pub fn rotated(rotation: f32) -> Self {
+        Transform { rotation, ..Self::identity() }
+    }
+
+    pub fn scaled(scale: f32) -> Self {
+        Transform { scale, ..Self::identity() }
+    }
+
+    // Applying the result is the same as applying self and then other
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            translation: rotate(self.translation * other.scale, other.rotation) + other.translation,
+            rotation: self.rotation + other.rotation,
+            scale: self.scale * other.scale,
+        }
+    }
+
+    pub fn apply(&self, pivot: Vec2, p: Vec2) -> Vec2 {
+        pivot + self.translation + rotate((p - pivot) * self.scale, self.rotation)
+    }
+
+    pub fn inverse_apply(&self, pivot: Vec2, p: Vec2) -> Vec2 {
+        pivot + rotate(p - pivot - self.translation, -self.rotation) / self.scale
+    }
+}
+
+// Any shape, moved around by a transform
+#[derive(Clone)]
+pub struct Transformed<S: Shape> {
+    pub(crate) shape: S,
+    pub(crate) transform: Transform,
+}
+
+impl<S: Shape> Transformed<S> {
+    pub fn new(shape: S, transform: Transform) -> Self {
+        Transformed { shape, transform }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.shape
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    pub fn translate(&mut self, d: Vec2) {
+        self.transform = self.transform.then(&Transform::translated(d));
+    }
+
+    pub fn rotate(&mut self, deg: f32) {
+        self.transform = self.transform.then(&Transform::rotated(deg));
+    }
+
+    pub fn scale(&mut self, s: f32) {
+        self.transform = self.transform.then(&Transform::scaled(s));
+    }
+
+    fn apply(&self, p: Vec2) -> Vec2 {
+        self.transform.apply(self.shape.get_center(), p)
+    }
+}
+
+impl<S: Shape> Shape for Transformed<S> {
+    fn collides(&self, shape: &dyn Shape) -> bool {
+        geometry::collides(self, shape)
+    }
+
+    fn contact(&self, shape: &dyn Shape) -> Option<Contact> {
+        geometry::contact(self, shape)
+    }
+
+    fn distance_to(&self, p: Vec2) -> f32 {
+        geometry::signed_distance(self, p)
+    }
+
+    fn closest_point(&self, p: Vec2) -> Vec2 {
+        geometry::closest_point(self, p)
+    }
+
+    fn contains_point(&self, p: Vec2) -> bool {
+        self.shape.contains_point(self.transform.inverse_apply(self.shape.get_center(), p))
+    }
+
+    fn into_points(&self) -> Vec<Vec2> {
+        self.shape.into_points().into_iter().map(|p| self.apply(p)).collect()
+    }
+
+    fn get_center(&self) -> Vec2 {
+        self.apply(self.shape.get_center())
+    }
+
+    fn get_shape_type(&self) -> ShapeType {
+        self.shape.get_shape_type()
+    }
+
+    fn into_basic_shape(&self) -> BasicShape {
+        match self.shape.into_basic_shape() {
+            BasicShape::Circle(c, r) => BasicShape::Circle(self.apply(c), r * self.transform.scale),
+            BasicShape::Rect(cl, wh) if self.transform.rotation % 360.0 == 0.0 => {
+                let a = self.apply(cl);
+                BasicShape::Rect(a, wh * self.transform.scale)
+            },
+            // a rotated rect is just a polygon
+            BasicShape::Rect(cl, wh) => BasicShape::Polygon(rect_points(cl, wh).into_iter().map(|p| self.apply(p)).collect()),
+            BasicShape::Polygon(points) => BasicShape::Polygon(points.into_iter().map(|p| self.apply(p)).collect()),
+            BasicShape::Polyline(points, r) => BasicShape::Polyline(points.into_iter().map(|p| self.apply(p)).collect(), r * self.transform.scale),
+            BasicShape::Other => BasicShape::Polygon(self.into_points()),
+        }
+    }
+
+    fn aabb(&self) -> Aabb {
+        match self.into_basic_shape() {
+            BasicShape::Circle(c, r) => Aabb::around(c, r),
+            BasicShape::Rect(cl, wh) => Aabb::new(vec2(cl.x, cl.y - wh.y), vec2(cl.x + wh.x, cl.y)),
+            BasicShape::Polyline(points, r) => Aabb::from_points(&points).expand(r),
+            BasicShape::Polygon(points) => Aabb::from_points(&points),
+            BasicShape::Other => Aabb::from_points(&self.into_points()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{Circle, Rect};
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        (a.x - b.x).abs() < 1e-4 && (a.y - b.y).abs() < 1e-4
+    }
+
+    #[test]
+    fn apply_and_inverse() {
+        let t = Transform::new(vec2(3.0, -1.0), 90.0, 2.0);
+        let pivot = vec2(1.0, 1.0);
+        let p = vec2(2.0, 1.0);
+
+        // one unit right of the pivot, scaled to two, rotated clockwise to two units down, then moved
+        assert!(close(t.apply(pivot, p), vec2(4.0, -2.0)));
+        assert!(close(t.inverse_apply(pivot, t.apply(pivot, p)), p));
+    }
+
+    #[test]
+    fn compose() {
+        let a = Transform::new(vec2(1.0, 0.0), 30.0, 2.0);
+        let b = Transform::new(vec2(0.0, 5.0), 60.0, 0.5);
+        let pivot = vec2(-2.0, 3.0);
+        let p = vec2(4.0, 4.0);
+
+        assert!(close(a.then(&b).apply(pivot, p), b.apply(pivot, a.apply(pivot, p))));
+    }
+
+    #[test]
+    fn rotated_rect() {
+        // a long thin bar from x=0 to x=10, centered on (5, 0)
+        let bar = Rect::new(vec2(0.0, 0.5), vec2(10.0, 1.0));
+        let mut t = Transformed::new(bar, Transform::identity());
+        let probe = Circle::new(vec2(5.0, 4.0), 0.5);
+
+        assert!(t.contains_point(vec2(9.0, 0.0)));
+        assert!(!t.collides(&probe));
+
+        t.rotate(90.0);
+        assert!(!t.contains_point(vec2(9.0, 0.0)));
+        assert!(t.contains_point(vec2(5.0, 4.0)));
+        assert!(t.collides(&probe));
+        assert!(probe.collides(&t));
+        assert!(close(t.get_center(), vec2(5.0, 0.0)));
+
+        t.translate(vec2(10.0, 0.0));
+        assert!(!t.collides(&probe));
+        assert!(t.contains_point(vec2(15.0, -4.0)));
+    }
+
+    #[test]
+    fn scaled_circle() {
+        let t = Transformed::new(Circle::new(vec2(0.0, 0.0), 1.0), Transform::new(vec2(2.0, 0.0), 0.0, 3.0));
+        assert!(t.contains_point(vec2(4.5, 0.0)));
+        assert!(!t.contains_point(vec2(-1.5, 0.0)));
+        assert!(t.collides(&Circle::new(vec2(-1.5, 0.0), 0.6)));
+    }
+}
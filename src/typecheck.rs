@@ -0,0 +1,524 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// Catches brains that would misbehave before they ever run: comparisons between numbers
+// and positions, vector ops on numbers, and memory reads that could be out of range.
+
+use std::{collections::BTreeMap, fmt, mem, vec::Vec};
+
+use crate::{ant::{Ant, Condition, Decision, Location, Memories, Memory, Policy, Program, Source, Then}, fsm::Fsm};
+
+// Ticks only compare with ticks, but arithmetic on them gives numbers, like the time between two
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Type {
+    Number,
+    Position,
+    Bool,
+    Tick,
+    Entity,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "a number"),
+            Type::Position => write!(f, "a position"),
+            Type::Bool => write!(f, "true or false"),
+            Type::Tick => write!(f, "a tick"),
+            Type::Entity => write!(f, "a thing"),
+        }
+    }
+}
+
+impl Type {
+    pub fn of(m: &Memory) -> Type {
+        match m {
+            Memory::Number(_) => Type::Number,
+            Memory::Position(_) => Type::Position,
+            Memory::Bool(_) => Type::Bool,
+            Memory::Tick(_) => Type::Tick,
+            Memory::Entity(_) => Type::Entity,
+        }
+    }
+}
+
+fn add_type(types: &mut Vec<Type>, t: Type) {
+    if !types.contains(&t) {
+        types.push(t);
+    }
+}
+
+fn list_types(types: &[Type]) -> String {
+    types.iter().map(Type::to_string).collect::<Vec<_>>().join(" or ")
+}
+
+// Something wrong with one decision of a program, or one state of a state machine
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub state: Option<String>, // which state it's in, for state machines
+    pub decision: usize, // index into the program, or into the state's transitions
+    pub transition: bool,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(state) = &self.state {
+            write!(f, "state {}, ", state)?;
+        }
+        let what = if self.transition { "transition" } else { "decision" };
+        write!(f, "{} {}: {}", what, self.decision, self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[derive(Clone)]
+struct Checker {
+    memory: Vec<Type>, // everything that might be in the memory list
+    registers: BTreeMap<String, Vec<Type>>, // and in each register
+    capacity: usize,
+    state: Option<String>,
+    decision: usize,
+    transition: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    // what the memories hold, going by what the ant starts with and whatever the programs remember
+    fn new<'p>(programs: impl IntoIterator<Item = &'p Program>, memory: &Memories) -> Checker {
+        let mut c = Checker {
+            memory: Vec::new(),
+            registers: BTreeMap::new(),
+            capacity: memory.capacity().unwrap_or(usize::MAX),
+            state: None,
+            decision: 0,
+            transition: false,
+            diagnostics: Vec::new(),
+        };
+        memory.iter().for_each(|m| add_type(&mut c.memory, Type::of(m)));
+        for (name, m) in memory.registers() {
+            add_type(c.registers.entry(name.clone()).or_default(), Type::of(m));
+        }
+
+        // what gets remembered can depend on what else was, so go until nothing new turns up
+        let thens: Vec<&Then> = programs.into_iter().flat_map(Program::decisions).map(|d| match d {
+            Decision::If(_, t) | Decision::IfHaveFood(t) | Decision::Always(t) => t,
+        }).collect();
+        loop {
+            let known = c.known();
+            for t in &thens {
+                let (list, registers) = c.remembered(t);
+                list.into_iter().for_each(|t| add_type(&mut c.memory, t));
+                for (name, t) in registers {
+                    add_type(c.registers.entry(name).or_default(), t);
+                }
+            }
+            if c.known() == known {
+                return c;
+            }
+        }
+    }
+
+    fn known(&self) -> usize {
+        self.memory.len() + self.registers.values().map(Vec::len).sum::<usize>()
+    }
+
+    // what an action might remember, in the list and under names, without any of its own mistakes reported
+    fn remembered(&self, t: &Then) -> (Vec<Type>, Vec<(String, Type)>) {
+        let mut quiet = self.clone();
+        let (mut list, mut registers) = (Vec::new(), Vec::new());
+        let mut t = t;
+        loop {
+            match t {
+                Then::Remember(s, next) => {
+                    list.extend(quiet.source(s, usize::MAX));
+                    t = next;
+                },
+                Then::Store(name, s, next) => {
+                    registers.extend(quiet.source(s, usize::MAX).map(|ty| (name.clone(), ty)));
+                    t = next;
+                },
+                Then::Forget(next) | Then::Clear(_, next) => t = next,
+                _ => return (list, registers),
+            }
+        }
+    }
+
+    fn report(&mut self, message: String) {
+        // the same mistake can be reached more than once when working out memory lengths
+        let d = Diagnostic { state: self.state.clone(), decision: self.decision, transition: self.transition, message };
+        if !self.diagnostics.contains(&d) {
+            self.diagnostics.push(d);
+        }
+    }
+
+    // the type of a source, or None if it doesn't have one, which has already been reported.
+    // len is how many memories there are guaranteed to be when it's evaluated.
+    fn source(&mut self, s: &Source, len: usize) -> Option<Type> {
+        match s {
+            Source::Dist(_) | Source::Food | Source::Number(_) |
+            Source::PheromoneStrength => Some(Type::Number),
+            Source::State => {
+                if self.state.is_none() {
+                    self.report("state is only something inside a state machine".to_string());
+                    return None;
+                }
+                Some(Type::Number)
+            },
+            Source::Loc(_) => Some(Type::Position),
+            Source::Bool(_) => Some(Type::Bool),
+            Source::Tick => Some(Type::Tick),
+            Source::Thing(l) => {
+                if !matches!(l, Location::NearestWall | Location::PheromoneSrc) {
+                    self.report(format!("thing({}) is never anything, only nearest_wall and pheromone_src are things", l));
+                    return None;
+                }
+                Some(Type::Entity)
+            },
+            Source::Where(a) => match self.source(a, len)? {
+                Type::Entity => Some(Type::Position),
+                t => {
+                    self.report(format!("where needs a thing, not {}", t));
+                    None
+                },
+            },
+            Source::Register(name) => {
+                let types = self.registers.get(name).cloned().unwrap_or_default();
+                match types[..] {
+                    [] => self.report(format!("recall({}) is read, but nothing is ever remembered as {}", name, name)),
+                    [t] => return Some(t),
+                    _ => self.report(format!("recall({}) could be {}, they all get remembered", name, list_types(&types))),
+                }
+                None
+            },
+            Source::Memory(i) => {
+                if *i < 0 {
+                    self.report(format!("memory({}) has a negative index", i));
+                    return None;
+                }
+                if *i as usize >= len {
+                    self.report(format!("memory({}) might not exist, only {} memories are certain to be there", i, len));
+                }
+
+                match self.memory[..] {
+                    [] => self.report(format!("memory({}) is read, but nothing is ever remembered", i)),
+                    [t] => return Some(t),
+                    _ => {
+                        let types = list_types(&self.memory);
+                        self.report(format!("memory({}) could be {}, they all get remembered", i, types));
+                    },
+                }
+                None
+            },
+            Source::Add(a, b) | Source::Sub(a, b) | Source::Mul(a, b) |
+            Source::Div(a, b) | Source::Min(a, b) | Source::Max(a, b) => {
+                // a number with a position gets applied to both components
+                match (self.source(a, len)?, self.source(b, len)?) {
+                    (Type::Number | Type::Tick, Type::Number | Type::Tick) => Some(Type::Number),
+                    (Type::Position, Type::Number | Type::Tick | Type::Position) |
+                    (Type::Number | Type::Tick, Type::Position) => Some(Type::Position),
+                    (ta, tb) => {
+                        self.report(format!("{} does arithmetic with {} and {}", s, ta, tb));
+                        None
+                    },
+                }
+            },
+            Source::Abs(a) => match self.source(a, len)? {
+                Type::Number | Type::Tick => Some(Type::Number),
+                Type::Position => Some(Type::Position),
+                t => {
+                    self.report(format!("abs needs a number or a position, not {}", t));
+                    None
+                },
+            },
+            Source::Dot(a, b) => {
+                let (ta, tb) = (self.source(a, len), self.source(b, len));
+                self.expect_position("dot", ta)?;
+                self.expect_position("dot", tb)?;
+                Some(Type::Number)
+            },
+            Source::Length(a) => {
+                let t = self.source(a, len);
+                self.expect_position("length", t).map(|_| Type::Number)
+            },
+            Source::Normalize(a) => {
+                let t = self.source(a, len);
+                self.expect_position("normalize", t)
+            },
+        }
+    }
+
+    fn expect_position(&mut self, op: &str, t: Option<Type>) -> Option<Type> {
+        match t? {
+            Type::Position => Some(Type::Position),
+            t => {
+                self.report(format!("{} needs a position, not {}", op, t));
+                None
+            },
+        }
+    }
+
+    // sources that have to be the same type, and one that can be put in order unless it's only equal
+    fn same(&mut self, what: &str, sources: &[&Source], len: usize, ordered: bool) {
+        let types: Vec<_> = sources.iter().map(|s| self.source(s, len)).collect();
+        let types: Option<Vec<Type>> = types.into_iter().collect();
+        if let Some(types) = types {
+            if types.iter().any(|t| *t != types[0]) {
+                let list: Vec<String> = sources.iter().zip(&types).map(|(s, t)| format!("{} is {}", s, t)).collect();
+                self.report(format!("{} compares different types, {}", what, list.join(" and ")));
+            } else if ordered && matches!(types[0], Type::Bool | Type::Entity) {
+                self.report(format!("{} doesn't work on {}, only equal does", what, types[0]));
+            }
+        }
+    }
+
+    fn condition(&mut self, c: &Condition, len: usize) {
+        match c {
+            Condition::GreaterThan(a, b) => self.same("greater than", &[a, b], len, true),
+            Condition::LessThan(a, b) => self.same("less than", &[a, b], len, true),
+            Condition::Equal(a, b) => self.same("equal", &[a, b], len, false),
+            Condition::ApproxEqual(a, b, eps) => {
+                if *eps < 0.0 {
+                    self.report(format!("approximately equal has a negative epsilon of {}", eps));
+                }
+                self.same("approximately equal", &[a, b], len, true);
+            },
+            Condition::Between(x, lo, hi) => self.same("between", &[x, lo, hi], len, true),
+            Condition::Not(c) => self.condition(c, len),
+            Condition::And(a, b) | Condition::Or(a, b) | Condition::Xor(a, b) => {
+                self.condition(a, len);
+                self.condition(b, len);
+            },
+        }
+    }
+
+    // checks an action and returns how many memories there are certain to be after it
+    fn then(&mut self, t: &Then, mut len: usize) -> usize {
+        let mut t = t;
+        loop {
+            match t {
+                Then::Nothing | Then::SetDest(_) => return len,
+                Then::EmitPheromone(s) => {
+                    if let Some(t) = self.source(s, len).filter(|t| *t != Type::Number) {
+                        self.report(format!("pheromones can only be emitted with a number for strength, not {}", t));
+                    }
+                    return len;
+                },
+                Then::Remember(s, next) => {
+                    self.source(s, len);
+                    // a full memory makes room, or refuses it
+                    len = (len + 1).min(self.capacity);
+                    t = next;
+                },
+                Then::Forget(next) => {
+                    len = len.saturating_sub(1);
+                    t = next;
+                },
+                Then::Store(_, s, next) => {
+                    self.source(s, len);
+                    t = next;
+                },
+                Then::Clear(_, next) => t = next,
+            }
+        }
+    }
+
+    // checks a decision and returns how many memories there are certain to be after it,
+    // whether it fires or not
+    fn decision(&mut self, d: &Decision, len: usize) -> usize {
+        match d {
+            Decision::Always(t) => self.then(t, len),
+            Decision::IfHaveFood(t) => self.then(t, len).min(len),
+            Decision::If(c, t) => {
+                self.condition(c, len);
+                self.then(t, len).min(len)
+            },
+        }
+    }
+
+    // one step of the program, returning how many memories there are certain to be after it
+    fn step(&mut self, program: &Program, len: usize) -> usize {
+        match program.policy() {
+            Policy::All => program.decisions().iter().enumerate().fold(len, |len, (i, d)| {
+                self.decision = i;
+                self.decision(d, len)
+            }),
+            // at most one fires, and it sees the memory as the step started
+            Policy::First | Policy::WeightedRandom => program.decisions().iter().enumerate().fold(len, |least, (i, d)| {
+                self.decision = i;
+                least.min(self.decision(d, len))
+            }),
+        }
+    }
+}
+
+// memory can shrink from step to step, so keep going until the fewest memories
+// there could be at the start of a step settles, it only goes down so this ends
+fn settle(c: &mut Checker, mut len: usize, mut step: impl FnMut(&mut Checker, usize) -> usize) -> Vec<Diagnostic> {
+    loop {
+        let next = step(c, len).min(len);
+        if next == len {
+            break;
+        }
+        len = next;
+    }
+    mem::take(&mut c.diagnostics)
+}
+
+// Every problem with a program, run by an ant starting out with the given memories
+pub fn check(program: &Program, memory: &Memories) -> Vec<Diagnostic> {
+    let mut c = Checker::new([program], memory);
+    let mut diagnostics = settle(&mut c, memory.len(), |c, len| c.step(program, len));
+    diagnostics.sort_by_key(|d| d.decision);
+    diagnostics
+}
+
+// Every problem with a state machine. Any state could come after any other, so every step
+// starts with the fewest memories any state could leave behind.
+pub fn check_fsm(fsm: &Fsm, memory: &Memories) -> Vec<Diagnostic> {
+    let mut c = Checker::new(fsm.states().iter().map(|s| s.program()), memory);
+    let mut diagnostics = settle(&mut c, memory.len(), |c, len| {
+        fsm.states().iter().fold(len, |least, s| {
+            c.state = Some(s.name().to_string());
+            // the transitions are tried first, then the state decides with the same memories
+            c.transition = true;
+            for (i, (when, _)) in s.transitions().iter().enumerate() {
+                c.decision = i;
+                c.condition(when, len);
+            }
+            c.transition = false;
+            least.min(c.step(s.program(), len))
+        })
+    });
+
+    let state = |d: &Diagnostic| d.state.as_deref().and_then(|s| fsm.find(s));
+    diagnostics.sort_by_key(|d| (state(d), !d.transition, d.decision));
+    diagnostics
+}
+
+impl Ant {
+    // Everything wrong with this ant's brain, given what it remembers right now
+    pub fn check(&self) -> Vec<Diagnostic> {
+        self.brain.check(&self.memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ant::Eviction, dsl::parse_program};
+
+    fn diagnostics(src: &str) -> Vec<Diagnostic> {
+        check(&parse_program(src).unwrap(), &Memories::new())
+    }
+
+    #[test]
+    fn well_typed() {
+        assert!(diagnostics("
+            if food > 0 and dist(home) > 5 then set_dest(home)
+            if approx(normalize(loc(here) - loc(home)), loc((0, 1)), 0.1) then emit_pheromone(food * 2)
+            if between(loc(here) * 2, loc(home), loc(home) + 10) then nothing
+            if length(loc(here)) / 2 == dot(loc(here), loc(dest)) then nothing
+            always remember(food)
+            if memory(0) > 1 then forget
+        ").is_empty());
+    }
+
+    #[test]
+    fn mismatched_types() {
+        let d = diagnostics("
+            always nothing
+            if food > loc(home) then nothing
+            if between(food, 0, loc(here)) then nothing
+            if length(food) > 1 then nothing
+            if dot(loc(here), 1) == 0 then nothing
+            always emit_pheromone(loc(here))
+            if (food + loc(here)) == 1 then nothing
+            if approx(food, 1, -1) then nothing
+        ");
+        let at: Vec<usize> = d.iter().map(|d| d.decision).collect();
+        assert_eq!(at, [1, 2, 3, 4, 5, 6, 7], "{:?}", d);
+        assert!(d[0].to_string().starts_with("decision 1: greater than compares different types"));
+        assert!(d[2].message.contains("length needs a position"));
+    }
+
+    #[test]
+    fn memory_bounds() {
+        // nothing is ever remembered
+        let d = diagnostics("if memory(0) > 1 then nothing");
+        assert_eq!(d.len(), 2);
+
+        let d = diagnostics("if memory(-1) > 1 then nothing\nalways remember(food)");
+        assert_eq!(d.len(), 1);
+        assert!(d[0].message.contains("negative"));
+
+        // remembering first makes the read safe, unless it only sometimes happens
+        assert!(diagnostics("always remember(food)\nif memory(0) > 1 then nothing").is_empty());
+        assert_eq!(diagnostics("if food > 1 then remember(food)\nif memory(0) > 1 then nothing").len(), 1);
+        assert_eq!(diagnostics("always remember(food)\nif memory(1) > 1 then nothing").len(), 1);
+        assert!(diagnostics("always remember(food)\nalways remember(food), remember(memory(1))\nif memory(2) > 1 then nothing").is_empty());
+
+        // the first step is fine, but memory runs out after a few
+        let program = parse_program("always forget\nif memory(0) > 1 then nothing").unwrap();
+        let start: Memories = [Memory::Number(1.0); 3].into_iter().collect();
+        assert_eq!(check(&program, &start).len(), 1);
+        let program = parse_program("always forget, remember(food)\nif memory(2) > 1 then nothing").unwrap();
+        assert!(check(&program, &start).is_empty());
+
+        // with only one decision firing, the one before doesn't count
+        assert_eq!(diagnostics("policy first\nalways remember(food)\nif memory(0) > 1 then nothing").len(), 1);
+
+        let d = diagnostics("always remember(food)\nalways remember(loc(here))\nif memory(0) > 0 then nothing");
+        assert_eq!(d.len(), 1);
+        assert!(d[0].message.contains("number or a position"));
+    }
+
+    #[test]
+    fn registers_and_capacity() {
+        assert!(diagnostics("
+            always remember(thing(nearest_wall) as wall), remember(where(recall(wall)) as wall_at)
+            if dist(home) < length(recall(wall_at) - loc(home)) then remember(tick as seen)
+            if recall(seen) < tick and true == recall(busy) then forget(busy)
+            always remember(false as busy)
+        ").is_empty());
+
+        let d = diagnostics("
+            if recall(spot) == loc(here) then nothing
+            always remember(1 as n), remember(loc(here) as n)
+            if recall(n) > 0 then nothing
+            if thing(home) == thing(home) then nothing
+            if true > false then nothing
+            if tick + true > 1 then nothing
+            if where(food) == loc(here) then nothing
+        ");
+        let messages: Vec<&str> = d.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, [
+            "recall(spot) is read, but nothing is ever remembered as spot",
+            "recall(n) could be a number or a position, they all get remembered",
+            "thing(home) is never anything, only nearest_wall and pheromone_src are things",
+            "greater than doesn't work on true or false, only equal does",
+            "tick + true does arithmetic with a tick and true or false",
+            "where needs a thing, not a number",
+        ]);
+
+        let d = diagnostics("if state == 1 then nothing");
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].message, "state is only something inside a state machine");
+
+        // a full memory doesn't get any longer, so the oldest reads run out
+        let program = parse_program("always remember(food), remember(food)
+if memory(1) > 0 then nothing").unwrap();
+        assert!(check(&program, &Memories::new()).is_empty());
+        assert_eq!(check(&program, &Memories::bounded(1, Eviction::Oldest)).len(), 1);
+
+        // but registers are there from the start, if the ant already has them
+        let mut start = Memories::new();
+        start.store("spot", Memory::Position(glm::vec2(1.0, 2.0)));
+        let program = parse_program("if recall(spot) == loc(here) then nothing").unwrap();
+        assert!(check(&program, &start).is_empty());
+    }
+}
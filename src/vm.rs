@@ -0,0 +1,553 @@
+/*
+*   SPDX-License-Identifier: GPL-3.0-only
+*   A very dumb little project simulating ants and complex behavior
+*   Copyright (C) 2024 Teresa Maria Rivera
+*/
+
+// Ant brains compiled down to a flat list of instructions for a little stack machine,
+// so running them doesn't mean chasing and cloning boxes every step.
+// Every decision gets two pieces of code, one leaving whether its condition holds on the stack
+// and one doing its action, both ending in Return. It does exactly what the tree walker does.
+
+use std::{fmt, vec::Vec};
+use crate::{ant::{Condition, Decision, Location, Memory, Source, Then}, brain::Scratch};
+
+// how many values the machine can hold at once, deeper programs don't compile
+pub const STACK_SIZE: usize = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Op {
+    // push a value
+    Number(f32),
+    Food,
+    Pheromone,
+    State,
+    Loc(Location),
+    Dist(Location),
+    Memory(i32),
+    Bool(bool),
+    Tick,
+    Thing(Location),
+    Register(usize), // registers are numbered by where their name is in the names
+    // pop two, push one
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Min,
+    Max,
+    Dot,
+    // pop one, push one
+    Abs,
+    Length,
+    Normalize,
+    Where,
+    // conditions leave true or false
+    True,
+    HaveFood,
+    Greater,
+    Less,
+    Equal,
+    Approx(f32),
+    Between, // pops the value, then the low end, then the high end
+    Not,
+    And,
+    Or,
+    Xor,
+    // actions
+    SetDest(Location),
+    Emit, // pops the strength
+    Remember, // pops what to remember
+    Forget,
+    Store(usize), // pops what to remember
+    Clear(usize),
+    Return,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    TooDeep { decision: usize, depth: usize }, // needs more stack than STACK_SIZE
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::TooDeep { decision, depth } => {
+                write!(f, "decision {} needs a stack of {}, there's only room for {}", decision, depth, STACK_SIZE)
+            },
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bytecode {
+    code: Vec<Op>,
+    entries: Vec<(usize, usize)>, // where each decision's condition and action start
+    names: Vec<String>, // the registers it uses
+}
+
+impl Bytecode {
+    pub fn code(&self) -> &[Op] {
+        &self.code
+    }
+
+    pub fn condition(&self, decision: usize) -> usize {
+        self.entries[decision].0
+    }
+
+    pub fn action(&self, decision: usize) -> usize {
+        self.entries[decision].1
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+struct Compiler {
+    code: Vec<Op>,
+    depth: usize,
+    deepest: usize,
+    names: Vec<String>,
+}
+
+impl Compiler {
+    // emits an op that pops some values and pushes some, keeping track of how deep the stack gets
+    fn op(&mut self, op: Op, pops: usize, pushes: usize) {
+        self.code.push(op);
+        self.depth = self.depth - pops + pushes;
+        self.deepest = self.deepest.max(self.depth);
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        self.names.iter().position(|n| n == name).unwrap_or_else(|| {
+            self.names.push(name.to_string());
+            self.names.len() - 1
+        })
+    }
+
+    fn source(&mut self, s: &Source) {
+        let binary = |c: &mut Compiler, a: &Source, b: &Source, op: Op| {
+            c.source(a);
+            c.source(b);
+            c.op(op, 2, 1);
+        };
+
+        match s {
+            Source::Number(n) => self.op(Op::Number(*n), 0, 1),
+            Source::Food => self.op(Op::Food, 0, 1),
+            Source::PheromoneStrength => self.op(Op::Pheromone, 0, 1),
+            Source::State => self.op(Op::State, 0, 1),
+            Source::Loc(l) => self.op(Op::Loc(*l), 0, 1),
+            Source::Dist(l) => self.op(Op::Dist(*l), 0, 1),
+            Source::Memory(i) => self.op(Op::Memory(*i), 0, 1),
+            Source::Bool(b) => self.op(Op::Bool(*b), 0, 1),
+            Source::Tick => self.op(Op::Tick, 0, 1),
+            Source::Thing(l) => self.op(Op::Thing(*l), 0, 1),
+            Source::Register(name) => {
+                let i = self.name(name);
+                self.op(Op::Register(i), 0, 1);
+            },
+            Source::Add(a, b) => binary(self, a, b, Op::Add),
+            Source::Sub(a, b) => binary(self, a, b, Op::Sub),
+            Source::Mul(a, b) => binary(self, a, b, Op::Mul),
+            Source::Div(a, b) => binary(self, a, b, Op::Div),
+            Source::Min(a, b) => binary(self, a, b, Op::Min),
+            Source::Max(a, b) => binary(self, a, b, Op::Max),
+            Source::Dot(a, b) => binary(self, a, b, Op::Dot),
+            Source::Abs(a) => {
+                self.source(a);
+                self.op(Op::Abs, 1, 1);
+            },
+            Source::Length(a) => {
+                self.source(a);
+                self.op(Op::Length, 1, 1);
+            },
+            Source::Normalize(a) => {
+                self.source(a);
+                self.op(Op::Normalize, 1, 1);
+            },
+            Source::Where(a) => {
+                self.source(a);
+                self.op(Op::Where, 1, 1);
+            },
+        }
+    }
+
+    fn condition(&mut self, c: &Condition) {
+        let compare = |c: &mut Compiler, a: &Source, b: &Source, op: Op| {
+            c.source(a);
+            c.source(b);
+            c.op(op, 2, 1);
+        };
+        let logic = |c: &mut Compiler, a: &Condition, b: &Condition, op: Op| {
+            c.condition(a);
+            c.condition(b);
+            c.op(op, 2, 1);
+        };
+
+        match c {
+            Condition::GreaterThan(a, b) => compare(self, a, b, Op::Greater),
+            Condition::LessThan(a, b) => compare(self, a, b, Op::Less),
+            Condition::Equal(a, b) => compare(self, a, b, Op::Equal),
+            Condition::ApproxEqual(a, b, eps) => compare(self, a, b, Op::Approx(*eps)),
+            Condition::Between(x, lo, hi) => {
+                // backwards, so x comes off the stack first
+                self.source(hi);
+                self.source(lo);
+                self.source(x);
+                self.op(Op::Between, 3, 1);
+            },
+            Condition::Not(c) => {
+                self.condition(c);
+                self.op(Op::Not, 1, 1);
+            },
+            Condition::And(a, b) => logic(self, a, b, Op::And),
+            Condition::Or(a, b) => logic(self, a, b, Op::Or),
+            Condition::Xor(a, b) => logic(self, a, b, Op::Xor),
+        }
+    }
+
+    fn then(&mut self, t: &Then) {
+        let mut t = t;
+        loop {
+            match t {
+                Then::Nothing => break,
+                Then::SetDest(l) => {
+                    self.op(Op::SetDest(*l), 0, 0);
+                    break;
+                },
+                Then::EmitPheromone(s) => {
+                    self.source(s);
+                    self.op(Op::Emit, 1, 0);
+                    break;
+                },
+                Then::Remember(s, next) => {
+                    self.source(s);
+                    self.op(Op::Remember, 1, 0);
+                    t = next;
+                },
+                Then::Forget(next) => {
+                    self.op(Op::Forget, 0, 0);
+                    t = next;
+                },
+                Then::Store(name, s, next) => {
+                    self.source(s);
+                    let i = self.name(name);
+                    self.op(Op::Store(i), 1, 0);
+                    t = next;
+                },
+                Then::Clear(name, next) => {
+                    let i = self.name(name);
+                    self.op(Op::Clear(i), 0, 0);
+                    t = next;
+                },
+            }
+        }
+        self.op(Op::Return, 0, 0);
+    }
+}
+
+pub fn compile(decisions: &[Decision]) -> Result<Bytecode, CompileError> {
+    let mut c = Compiler { code: Vec::new(), depth: 0, deepest: 0, names: Vec::new() };
+    let mut entries = Vec::with_capacity(decisions.len());
+
+    for (i, d) in decisions.iter().enumerate() {
+        c.deepest = 0;
+        let condition = c.code.len();
+        let t = match d {
+            Decision::Always(t) => {
+                c.op(Op::True, 0, 1);
+                t
+            },
+            Decision::IfHaveFood(t) => {
+                c.op(Op::HaveFood, 0, 1);
+                t
+            },
+            Decision::If(cond, t) => {
+                c.condition(cond);
+                t
+            },
+        };
+        c.op(Op::Return, 1, 0);
+
+        let action = c.code.len();
+        c.then(t);
+        entries.push((condition, action));
+
+        if c.deepest > STACK_SIZE {
+            return Err(CompileError::TooDeep { decision: i, depth: c.deepest });
+        }
+    }
+
+    Ok(Bytecode { code: c.code, entries, names: c.names })
+}
+
+fn truth(b: bool) -> Memory {
+    Memory::Bool(b)
+}
+
+fn is_true(m: Memory) -> bool {
+    m == Memory::Bool(true)
+}
+
+impl Scratch<'_, '_> {
+    // Runs code from start to the next Return, for a condition that's whether it held
+    pub(crate) fn run(&mut self, code: &Bytecode, start: usize) -> bool {
+        let mut stack = [Memory::Number(0.0); STACK_SIZE];
+        let mut sp = 0;
+
+        macro_rules! push {
+            ($v:expr) => {{
+                let v = $v;
+                stack[sp] = v;
+                sp += 1;
+            }};
+        }
+        macro_rules! pop {
+            () => {{
+                sp -= 1;
+                stack[sp]
+            }};
+        }
+        macro_rules! binary {
+            ($f:expr) => {{
+                let b = pop!();
+                let a = pop!();
+                push!($f(a, b))
+            }};
+        }
+
+        for op in &code.code[start..] {
+            match *op {
+                Op::Number(n) => push!(Memory::Number(n)),
+                Op::Food => push!(Memory::Number(self.senses().food())),
+                Op::Pheromone => push!(Memory::Number(self.senses().pheromone())),
+                Op::State => push!(Memory::Number(self.state())),
+                Op::Loc(l) => push!(self.position(l)),
+                Op::Dist(l) => push!(Memory::Number(self.dist(l))),
+                Op::Memory(i) => push!(self.recall(i)),
+                Op::Bool(b) => push!(Memory::Bool(b)),
+                Op::Tick => push!(Memory::Tick(self.senses().tick())),
+                Op::Thing(l) => push!(self.thing(l)),
+                Op::Register(i) => push!(self.register(&code.names[i])),
+                Op::Add => binary!(|a: Memory, b| a.arith(b, |x, y| x + y)),
+                Op::Sub => binary!(|a: Memory, b| a.arith(b, |x, y| x - y)),
+                Op::Mul => binary!(|a: Memory, b| a.arith(b, |x, y| x * y)),
+                Op::Div => binary!(|a: Memory, b| a.arith(b, |x, y| x / y)),
+                Op::Min => binary!(|a: Memory, b| a.arith(b, f32::min)),
+                Op::Max => binary!(|a: Memory, b| a.arith(b, f32::max)),
+                Op::Dot => binary!(Memory::dot),
+                Op::Abs => push!(pop!().abs()),
+                Op::Length => push!(pop!().length()),
+                Op::Normalize => push!(pop!().normalize()),
+                Op::Where => {
+                    let m = pop!();
+                    push!(self.where_is(m))
+                },
+                Op::True => push!(truth(true)),
+                Op::HaveFood => push!(truth(self.senses().has_food())),
+                Op::Greater => binary!(|a: Memory, b| truth(a.greater_than(b))),
+                Op::Less => binary!(|a: Memory, b| truth(a.less_than(b))),
+                Op::Equal => binary!(|a: Memory, b| truth(a == b)),
+                Op::Approx(eps) => binary!(|a: Memory, b| truth(a.approx_eq(b, eps))),
+                Op::Between => {
+                    let x = pop!();
+                    let lo = pop!();
+                    let hi = pop!();
+                    push!(truth(x.between(lo, hi)))
+                },
+                Op::Not => push!(truth(!is_true(pop!()))),
+                Op::And => binary!(|a, b| truth(is_true(a) && is_true(b))),
+                Op::Or => binary!(|a, b| truth(is_true(a) || is_true(b))),
+                Op::Xor => binary!(|a, b| truth(is_true(a) != is_true(b))),
+                Op::SetDest(l) => self.set_dest(l),
+                Op::Emit => {
+                    let m = pop!();
+                    self.emit(m);
+                },
+                Op::Remember => {
+                    let m = pop!();
+                    self.remember(m);
+                },
+                Op::Forget => self.forget(),
+                Op::Store(i) => {
+                    let m = pop!();
+                    self.store(&code.names[i], m);
+                },
+                Op::Clear(i) => self.clear(&code.names[i]),
+                Op::Return => return sp > 0 && is_true(stack[sp - 1]),
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+    use glm::vec2;
+    use crate::{ant::{Ant, Pheromones, Program}, brain::{Action, Brain}, dsl::parse_program, shape::{Rect, Shape}, world::Environment};
+
+    // Runs the same ants in two copies of the same world, one with compiled programs
+    fn both(src: &str, steps: usize) -> (Environment, Environment) {
+        let program = parse_program(src).unwrap();
+        let mut compiled = program.clone();
+        compiled.compile().unwrap();
+
+        let setup = |program: &Program| {
+            let mut env = Environment::new();
+            env.set_seed(7);
+            env.add(Rect::new(vec2(20.0, 30.0), vec2(4.0, 40.0)), 3);
+            env.add(Pheromones::new(vec2(5.0, 5.0), 3.0), 2);
+            for i in 0..4 {
+                let mut a = Ant::new(vec2(i as f32 * 3.0, -(i as f32) * 2.0), program.clone());
+                a.set_home(vec2(0.0, 0.0));
+                a.set_food((i % 3) as f32);
+                env.add(a, 5);
+            }
+            for _ in 0..steps {
+                env.step();
+            }
+            env
+        };
+
+        (setup(&program), setup(&compiled))
+    }
+
+    fn ants(env: &Environment) -> Vec<Ant> {
+        env.ants().iter().map(|a| a.borrow().downcast_ref::<Ant>().unwrap().clone()).collect()
+    }
+
+    fn same(a: &Environment, b: &Environment) {
+        for (x, y) in ants(a).iter().zip(ants(b)) {
+            assert_eq!(x.pos, y.pos);
+            assert_eq!(x.heading, y.heading);
+            assert_eq!(x.dest, y.dest);
+            assert_eq!(x.path, y.path);
+            assert_eq!(format!("{:?}", x.memory), format!("{:?}", y.memory));
+        }
+
+        let strengths = |e: &Environment| e.things().iter()
+            .filter_map(|t| t.borrow().downcast_ref::<Pheromones>().map(|p| (p.pos, p.strength)))
+            .collect::<Vec<_>>();
+        assert_eq!(strengths(a), strengths(b));
+    }
+
+    const FORAGER: &str = "
+        if food > 0 and dist(home) > 4 then set_dest(home)
+        if have_food then remember(food)
+        if not food > 0 and pheromone > 0.5 then remember(loc(here)), set_dest(pheromone_src)
+        if food > 0 xor dist(nearest_wall) < 6 then emit_pheromone(food * 2 + 0.5)
+        if between(loc(here), loc((-5, -20)), loc(here) + 1) or approx(dist(home), 3, 0.5) then remember(length(loc(here)))
+        if memory(0) == memory(0) then forget, forget, remember(min(food, max(abs(-2), dot(normalize(loc(here) - loc(home)), loc((1, 0))))))
+        always remember(dist(dest) / 2 - 1)
+        if memory(1) < 3 then nothing
+    ";
+
+    #[test]
+    fn same_as_tree_walker() {
+        let (a, b) = both(FORAGER, 12);
+        same(&a, &b);
+
+        let (a, b) = both(&format!("policy first\n{}", FORAGER), 12);
+        same(&a, &b);
+
+        let (a, b) = both(&format!("policy random\nweight 2 {}", FORAGER.trim()), 12);
+        same(&a, &b);
+    }
+
+    #[test]
+    fn registers_same_as_tree_walker() {
+        let src = "
+            if not recall(smelt) == true then remember(thing(pheromone_src) as smell), remember(true as smelt)
+            always remember(where(recall(smell)) as smell_at), remember(tick)
+            if dist(nearest_wall) < 8 then remember(thing(nearest_wall) as wall), forget(smelt)
+            if between(recall(smell_at), loc(here) - 10, loc(here) + 10) then emit_pheromone(abs(tick - memory(0)) + 1)
+            if recall(wall) == thing(nearest_wall) then remember(tick - 1 as bumped)
+        ";
+        let (a, b) = both(src, 12);
+        same(&a, &b);
+        assert!(ants(&a).iter().all(|ant| ant.memory.register("smell_at").is_some()));
+
+        let mut program = parse_program(src).unwrap();
+        program.compile().unwrap();
+        assert_eq!(program.bytecode().unwrap().names(), ["smelt", "smell", "smell_at", "wall", "bumped"]);
+    }
+
+    #[test]
+    fn conditions_agree() {
+        let conditions = [
+            "food > 1", "food < 1", "food == 1", "loc(here) > loc(home)", "loc(here) == food",
+            "approx(loc(here), loc((3, -2)), 0.1)", "between(food, 1, 2)", "between(loc(here), 0, 5)",
+            "not (food > 1 or food < 1)", "food > 1 and food > 0", "length(food) == length(food)",
+            "dot(loc(here), loc(here)) > 12", "normalize(loc(home)) == loc(home)", "memory(-1) < 1",
+            "true == false", "tick > tick - 1", "between(tick, tick, tick)", "where(thing(here)) == loc(here)",
+            "recall(nothing) == recall(nothing)",
+        ];
+
+        let env = Environment::new();
+        for c in conditions {
+            let mut program = parse_program(&format!("if {} then nothing", c)).unwrap();
+            let Decision::If(cond, _) = program.decisions()[0].clone() else { unreachable!() };
+            program.compile().unwrap();
+            let code = program.bytecode().unwrap().clone();
+
+            for (pos, food) in [(vec2(3.0, -2.0), 1.0), (vec2(0.0, 0.0), 2.0), (vec2(-1.0, 7.0), 0.0)] {
+                let mut a = Ant::new(pos, program.clone());
+                a.set_home(vec2(0.0, 0.0));
+                a.set_food(food);
+                let senses = env.senses(&a);
+                let ran = Scratch::new(&senses).run(&code, code.condition(0));
+                assert_eq!(senses.holds(&cond), ran, "{} at {:?}", c, pos);
+            }
+        }
+    }
+
+    #[test]
+    fn stack_depth() {
+        // each nesting of a + (b + (c + ...)) holds one more value
+        let mut s = Source::Number(1.0);
+        for _ in 0..STACK_SIZE {
+            s = Source::Add(Box::new(Source::Number(1.0)), Box::new(s));
+        }
+        let deep = vec![
+            Decision::Always(Then::Nothing),
+            Decision::If(Condition::GreaterThan(s, Source::Food), Then::Nothing),
+        ];
+        assert_eq!(compile(&deep), Err(CompileError::TooDeep { decision: 1, depth: STACK_SIZE + 1 }));
+
+        // left leaning chains don't need any more room however long they get
+        let mut s = Source::Number(1001.0);
+        for _ in 0..1000 {
+            s = Source::Sub(Box::new(s), Box::new(Source::Number(1.0)));
+        }
+        let mut program = Program::from(vec![Decision::Always(Then::EmitPheromone(s))]);
+        program.compile().unwrap();
+        assert_eq!(program.bytecode().unwrap().code().len(), 2 + 2001 + 2);
+
+        let env = Environment::new();
+        let a = Ant::new(vec2(0.0, 0.0), Program::default());
+        assert_eq!(program.think(&env.senses(&a)), [Action::EmitPheromone(1.0)]);
+    }
+
+    #[test]
+    fn changes_drop_bytecode() {
+        let mut program = parse_program("always forget").unwrap();
+        program.compile().unwrap();
+        assert!(program.bytecode().is_some());
+
+        let copy = program.clone();
+        program.push(Decision::Always(Then::Nothing));
+        assert!(program.bytecode().is_none());
+        assert!(copy.bytecode().is_some());
+        assert_eq!(copy.len(), 1);
+
+        let shape: Rc<RefCell<dyn Shape>> = Rc::new(RefCell::new(Ant::new(vec2(0.0, 0.0), copy)));
+        assert!(shape.borrow().downcast_ref::<Ant>().unwrap().program().unwrap().bytecode().is_some());
+    }
+}
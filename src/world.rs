@@ -4,11 +4,12 @@
 *   Copyright (C) 2024 Teresa Maria Rivera
 */
 
-use crate::ant::{Ant, Condition, Decision, Location, Memory, Pheromones, Source, Then};
+use crate::{ant::{Ant, Pheromones}, brain::{Action, Brain, Senses}, loader::Obstacle};
 
-use super::shape::Shape;
-use std::{any::TypeId, cell::RefCell, collections::{BinaryHeap, VecDeque}, rc::Rc};
-use glm::{distance, greaterThan, lessThan, vec2, Vec2};
+use super::{geometry, shape::{Aabb, Shape}, spatial::Grid, typecheck::Diagnostic};
+use std::{any::TypeId, cell::RefCell, collections::{BinaryHeap, HashMap}, mem, rc::Rc};
+use glm::{distance, normalize, vec2, Vec2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 #[derive(Clone)]
 pub struct Environment {
@@ -17,14 +18,38 @@ pub struct Environment {
     colliders: Vec<Rc<RefCell<dyn Shape>>>,
     renderers: Vec<Rc<RefCell<dyn Shape>>>,
     ants:      Vec<Rc<RefCell<dyn Shape>>>, // these guys are special
+    collider_index:  Grid,
+    pheromone_index: Grid,
+    dt: f32, // how much time passes every step
+    tick: u64, // how many steps have been taken
+    rng: RefCell<StdRng>, // so brains can roll dice while looking at the world
+    // everything gets an id when it's added, so brains can remember things without holding onto them
+    ids: HashMap<*const (), EntityId>,
+    entities: HashMap<EntityId, Rc<RefCell<dyn Shape>>>,
+    next_id: u64,
 }
 
-#[derive(Clone)]
-struct AStar(Vec2, f32, f32, Vec<usize>, bool, usize, usize);
+// Something in the world, never reused even once it's gone
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(pub(crate) u64);
+
+// stands in for an ant's brain while it's busy thinking
+#[derive(Clone, Debug)]
+struct Thinking;
+
+impl Brain for Thinking {
+    fn think(&mut self, _senses: &Senses) -> Vec<Action> {
+        Vec::new()
+    }
+}
+
+// an entry in the open set of chart_path, the estimated total cost and which point it is
+#[derive(Clone, Copy)]
+struct AStar(f32, usize);
 
 impl PartialEq for AStar {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0 && self.1 == other.1 && self.2 == other.2 && self.3 == other.3
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
@@ -32,20 +57,54 @@ impl Eq for AStar {}
 
 impl PartialOrd for AStar {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other.2.partial_cmp(&other.2).and_then(|a| Some(a.then_with(|| self.2.partial_cmp(&other.2).unwrap())))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for AStar {
+    // reversed, BinaryHeap pops the largest and we want the cheapest
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        other.0.total_cmp(&self.0).then_with(|| other.1.cmp(&self.1))
     }
 }
 
+// size of a cell in the spatial indices, a few ants wide
+const GRID_CELL: f32 = 8.0;
+
+// how far off the straight line chart_path looks for a way around things,
+// and how far it will jump straight to the destination
+const PATH_MARGIN: f32 = 10.0;
+
+// the furthest chart_path will plan a route to
+const MAX_PATH_DIST: f32 = 512.0;
+
+// pheromones emitted closer than this to an existing one reinforce it instead
+const PHEROMONE_MERGE: f32 = 1.0;
+
+// how much strength pheromones lose every step
+const PHEROMONE_DECAY: f32 = 0.1;
+
+// pheromones reach as far as they're strong, so they can't get stronger than this
+const MAX_PHEROMONE: f32 = 100.0;
+
+// a waypoint counts as reached once an ant is this close to it
+const WAYPOINT_REACH: f32 = 0.5;
+
+// Ants move around, so paths are planned around everything else
+fn is_wall(shape: &dyn Shape) -> bool {
+    !shape.is::<Ant>()
+}
+
 pub fn square_dist(a: Vec2, b: Vec2) -> f32 {
     (a.x - b.x).powi(2) + (a.y - b.y).powi(2)
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Environment {
     pub fn new() -> Self {
         Environment {
@@ -53,14 +112,66 @@ impl Environment {
             colliders: Vec::new(),
             renderers: Vec::new(),
             ants: Vec::new(),
+            collider_index: Grid::new(GRID_CELL),
+            pheromone_index: Grid::new(GRID_CELL),
+            dt: 1.0,
+            tick: 0,
+            rng: RefCell::new(StdRng::seed_from_u64(0)),
+            ids: HashMap::new(),
+            entities: HashMap::new(),
+            next_id: 0,
         }
     }
 
-    pub fn add<T: Shape + Clone + 'static>(&mut self, obj: T, flags: i32) {
+    pub fn things(&self) -> &[Rc<RefCell<dyn Shape>>] {
+        &self.things
+    }
+
+    pub fn ants(&self) -> &[Rc<RefCell<dyn Shape>>] {
+        &self.ants
+    }
+
+    pub fn time_step(&self) -> f32 {
+        self.dt
+    }
+
+    pub fn set_time_step(&mut self, dt: f32) {
+        self.dt = dt.max(0.0);
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn id(&self, obj: &Rc<RefCell<dyn Shape>>) -> Option<EntityId> {
+        self.ids.get(&(Rc::as_ptr(obj) as *const ())).copied()
+    }
+
+    // Whatever has the id, if it's still around
+    pub fn entity(&self, id: EntityId) -> Option<Rc<RefCell<dyn Shape>>> {
+        self.entities.get(&id).cloned()
+    }
+
+    // wandering is random, this makes it the same random every time
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = RefCell::new(StdRng::seed_from_u64(seed));
+    }
+
+    // Ants added here don't go through the type checker, a broken brain just misbehaves when it runs.
+    // Anything loaded from text should use add_ant, which hands back what's wrong with it instead.
+    pub fn add<T: Shape + Clone + 'static>(&mut self, obj: T, flags: i32) -> Rc<RefCell<dyn Shape>> {
         let tmp = Rc::new(RefCell::new(obj.clone()));
         self.things.push(Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>);
-        if (flags >> 0) & 1 == 1 {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.ids.insert(Rc::as_ptr(&tmp) as *const (), id);
+        self.entities.insert(id, Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>);
+        if TypeId::of::<T>() == TypeId::of::<Pheromones>() {
+            self.pheromone_index.insert(&(Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>));
+        }
+        if flags & 1 == 1 {
             self.colliders.push(Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>);
+            self.collider_index.insert(&(Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>));
         }
         if (flags >> 1) & 1 == 1 {
             self.renderers.push(Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>);
@@ -68,146 +179,272 @@ impl Environment {
         if (flags >> 2) & 1 == 1 {
             self.ants.push(Rc::clone(&tmp) as Rc<RefCell<dyn Shape>>);
         }
+
+        tmp
+    }
+
+    // Adds everything a loader returned, flags work the same as in add
+    pub fn add_obstacles(&mut self, obstacles: Vec<Obstacle>, flags: i32) -> Vec<Rc<RefCell<dyn Shape>>> {
+        obstacles.into_iter().map(|o| o.add_to(self, flags)).collect()
+    }
+
+    // Adds an ant, but only if its program passes the type checker
+    pub fn add_ant(&mut self, ant: Ant, flags: i32) -> Result<Rc<RefCell<dyn Shape>>, Vec<Diagnostic>> {
+        let diagnostics = ant.check();
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        Ok(self.add(ant, flags | 4))
     }
 
     pub fn rm(&mut self, obj: Rc<RefCell<dyn Shape>>) {
-        self.things = self.things.clone().into_iter().filter(|t| Rc::ptr_eq(t, &obj)).collect::<Vec<_>>();
-        self.colliders = self.colliders.clone().into_iter().filter(|t| Rc::ptr_eq(t, &obj)).collect::<Vec<_>>();
-        self.renderers = self.renderers.clone().into_iter().filter(|t| Rc::ptr_eq(t, &obj)).collect::<Vec<_>>();
-        self.ants = self.ants.clone().into_iter().filter(|t| Rc::ptr_eq(t, &obj)).collect::<Vec<_>>();
+        if let Some(id) = self.ids.remove(&(Rc::as_ptr(&obj) as *const ())) {
+            self.entities.remove(&id);
+        }
+        self.things.retain(|t| !Rc::ptr_eq(t, &obj));
+        self.colliders.retain(|t| !Rc::ptr_eq(t, &obj));
+        self.renderers.retain(|t| !Rc::ptr_eq(t, &obj));
+        self.ants.retain(|t| !Rc::ptr_eq(t, &obj));
+        self.collider_index.remove(&obj);
+        self.pheromone_index.remove(&obj);
     }
 
-    fn process_point<'a>(&'a self, src: Vec2, point: Vec2, walker: &mut Ant) -> Option<(Vec2, f32)> {
+    // Everything in renderers whose bounding box overlaps the view
+    pub fn visible(&self, view: &Aabb) -> Vec<Rc<RefCell<dyn Shape>>> {
+        self.renderers.iter().filter(|r| r.borrow().aabb().overlaps(view)).cloned().collect()
+    }
+
+    // colliders whose bounding box might overlap the given box
+    fn colliders_near(&self, lo: Vec2, hi: Vec2) -> Vec<Rc<RefCell<dyn Shape>>> {
+        self.collider_index.query(lo, hi)
+    }
+
+    // The first collider a ray hits and how far along the ray it is,
+    // shapes the ray starts inside of are ignored so an ant doesn't see itself
+    pub fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<(Rc<RefCell<dyn Shape>>, f32)> {
+        let dir = normalize(dir);
+        let end = origin + dir * max_dist;
+        let near = self.colliders_near(vec2(origin.x.min(end.x), origin.y.min(end.y)), vec2(origin.x.max(end.x), origin.y.max(end.y)));
+        near.iter().filter_map(|c| {
+            let shape = c.borrow();
+            if shape.contains_point(origin) {
+                return None;
+            }
+
+            geometry::raycast(origin, dir, &*shape).filter(|t| *t <= max_dist).map(|t| (Rc::clone(c), t))
+        }).min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    // Whether nothing in colliders is between a and b, shapes containing either end don't count
+    pub fn line_of_sight(&self, a: Vec2, b: Vec2) -> bool {
+        if a == b {
+            return true;
+        }
+
+        let dist = distance(a, b);
+        let dir = normalize(b - a);
+        let near = self.colliders_near(vec2(a.x.min(b.x), a.y.min(b.y)), vec2(a.x.max(b.x), a.y.max(b.y)));
+        near.iter().all(|c| {
+            let shape = c.borrow();
+            shape.contains_point(a) || shape.contains_point(b) ||
+            geometry::raycast(a, dir, &*shape).is_none_or(|t| t > dist)
+        })
+    }
+
+    // The closest collider that isn't an ant, and the signed distance to it
+    pub fn nearest_wall(&self, p: Vec2) -> Option<(Rc<RefCell<dyn Shape>>, f32)> {
+        let closest = |near: &[Rc<RefCell<dyn Shape>>]| near.iter().filter_map(|c| {
+            let shape = c.borrow();
+            if shape.type_id() == TypeId::of::<Ant>() {
+                return None;
+            }
+            Some((Rc::clone(c), shape.distance_to(p)))
+        }).min_by(|a, b| a.1.total_cmp(&b.1));
+
+        // grow the search until something is found that nothing outside the searched area could beat
+        let mut r = GRID_CELL;
+        while r < GRID_CELL * 64.0 {
+            if let Some(found) = closest(&self.collider_index.query_radius(p, r)) {
+                if found.1 <= r {
+                    return Some(found);
+                }
+            }
+            r *= 2.0;
+        }
+
+        closest(&self.colliders)
+    }
+
+    // The pheromone whose middle is closest, searched for the same way as nearest_wall
+    pub fn nearest_pheromone(&self, p: Vec2) -> Option<Rc<RefCell<dyn Shape>>> {
+        if self.pheromone_index.is_empty() {
+            return None;
+        }
+
+        let closest = |near: &[Rc<RefCell<dyn Shape>>]| near.iter()
+            .map(|t| (Rc::clone(t), distance(t.borrow().get_center(), p)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut r = GRID_CELL;
+        while r < GRID_CELL * 64.0 {
+            if let Some(found) = closest(&self.pheromone_index.query_radius(p, r)) {
+                if found.1 <= r {
+                    return Some(found.0);
+                }
+            }
+            r *= 2.0;
+        }
+
+        let pheromones: Vec<_> = self.things.iter().filter(|t| t.borrow().is::<Pheromones>()).cloned().collect();
+        closest(&pheromones).map(|(t, _)| t)
+    }
+
+    // The first collider hit by a circle moving from c to c + delta, and how far along delta that happens.
+    // Like raycast, shapes that already contain c are ignored.
+    pub fn sweep(&self, c: Vec2, r: f32, delta: Vec2) -> Option<(Rc<RefCell<dyn Shape>>, f32)> {
+        self.sweep_where(c, r, delta, |_| true)
+    }
+
+    // sweep, but only against colliders f accepts
+    fn sweep_where<F: Fn(&dyn Shape) -> bool>(&self, c: Vec2, r: f32, delta: Vec2, f: F) -> Option<(Rc<RefCell<dyn Shape>>, f32)> {
+        let end = c + delta;
+        let area = Aabb::new(vec2(c.x.min(end.x), c.y.min(end.y)), vec2(c.x.max(end.x), c.y.max(end.y))).expand(r);
+        self.collider_index.query_aabb(&area).into_iter().filter_map(|o| {
+            let t = {
+                let shape = o.borrow();
+                if !f(&*shape) || shape.contains_point(c) {
+                    return None;
+                }
+                geometry::sweep_circle(c, r, delta, &*shape)
+            };
+            t.map(|t| (o, t))
+        }).min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    // Where a circle moving from c by delta ends up if it stops at the first thing in its way
+    pub fn advance(&self, c: Vec2, r: f32, delta: Vec2) -> Vec2 {
+        match self.sweep(c, r, delta) {
+            Some((_, t)) => c + delta * t,
+            None => c + delta,
+        }
+    }
+
+    fn process_point(&self, src: Vec2, point: Vec2, walker: &mut Ant) -> Option<(Vec2, f32)> {
+        // walk in from the previous ring, so there's no hopping over thin walls
+        let prev = if distance(point, src) > 1.0 { point - normalize(point - src) } else { src };
         walker.pos = point;
-        if self.colliders.iter().all(|o| walker.collides(&*o.borrow())) ||
-           self.colliders.iter().all(|o| o.borrow().contains_point(point)) 
+        if self.sweep_where(prev, 2.0, point - prev, is_wall).is_some() ||
+           self.collider_index.query_radius(point, 2.0).iter().any(|o| is_wall(&*o.borrow()) && walker.collides(&*o.borrow())) 
         {
             None
+        } else if !self.pheromone_index.is_empty() {
+            Some((point, self.pheromone_strength_at_pos(point) + 2.0))
         } else {
-            if self.things.iter().any(|o| o.borrow().type_id() == TypeId::of::<Pheromones>()) {
-                Some((point, self.pheromone_strength_at_pos(point) + 2.0))
-            } else {
-                Some((point, 1.0f32))
-            }
+            Some((point, 1.0f32))
         }
     }
 
     pub fn pheromone_strength_at_pos(&self, pos: Vec2) -> f32 {
-        self.things.clone().into_iter().filter_map(|r| {
+        self.pheromone_index.query_point(pos).into_iter().filter_map(|r| {
             if  r.borrow().type_id() != TypeId::of::<Pheromones>() ||
                 !r.borrow().contains_point(pos) 
             {
                 return None;
             }
 
-            let p = (*r.borrow()).downcast_ref::<Pheromones>().unwrap();
+            let b = r.borrow();
+            let p = b.downcast_ref::<Pheromones>().unwrap();
             Some(p.strength/distance(p.pos, pos))
         }).fold(0f32, |acc, s| acc + s)
     }
 
+    // Plans a route for an ant from where it is to dest, starting at the ant and ending at dest
     pub fn chart_path(&self, src: &Ant, dest: Vec2) -> Option<Vec<Vec2>> {
-        // this function is highly inefficient with memory, partially due to Rust's rules
-        // TODO: improve memory efficiency
+        // anything too far off (or not a real place at all) would take forever to plan for
+        let d = distance(dest, src.pos);
+        if !d.is_finite() || d > MAX_PATH_DIST {
+            return None;
+        }
 
         let mut walker = src.clone(); // Used to check for collisions
-        let mut grid: Vec<(Vec2,f32)> = vec![(src.pos, 0.0)];
+        let mut grid: Vec<(Vec2, f32)> = vec![(src.pos, 1.0)];
 
-        // first, generate a grid (well, list of points and scores), 
+        // first, generate a grid (well, list of points and scores),
         // containing all points along a series of concentric circles
-        // with the radius of each circle increasing by 1, until being within 
-        // 2 units of the destination
-        let mut r = 1;
-        loop {
-            let p: Vec<_> = (0..((360*r)/60)).map(|j| vec2(
-                ((r as f32) * (((r as f32)/60.0) * (j as f32)).to_radians().sin()) + src.pos.x, 
-                ((r as f32) * (((r as f32)/60.0) * (j as f32)).to_radians().cos()) + src.pos.y
-            )).collect();
-
-            grid.append(&mut p.into_iter().filter_map(|c| self.process_point(src.pos, c, &mut walker)).collect::<Vec<_>>());
-            if distance(dest, src.pos) - (r as f32) <= 10.0 {
-                break;
-            }
-            
-            r += 1;
+        // with the radius of each circle increasing by 1 and the points on it about 1 apart,
+        // reaching a bit past the destination so there's room to go around things
+        let reach = (distance(dest, src.pos) + PATH_MARGIN).ceil() as i32;
+        for r in 1..=reach {
+            let step = 60.0 / (r as f32);
+            grid.extend((0..(6 * r)).filter_map(|j| {
+                let a = (step * (j as f32)).to_radians();
+                let p = vec2((r as f32) * a.sin() + src.pos.x, (r as f32) * a.cos() + src.pos.y);
+                self.process_point(src.pos, p, &mut walker)
+            }));
         }
-        
-        // trust dest
-        grid.push((dest, f32::MAX/20.0));
-
-        // next, we run A* (instead of just dist we do dist/val)
-
-        // these are the points processed for A*
-        let mut astar = grid.clone().into_iter().filter_map(|a| {
-            let mut r = AStar(vec2(0.0,0.0), 0.0f32, 0.0f32, Vec::new(), false, 0, 0);
-            r.0 = a.0;
 
-            if a.1.is_nan() || a.1.is_infinite() {
-                return None;
-            }
-
-            r.3.append(&mut grid.clone().into_iter().enumerate().filter_map(|o| {
-                if square_dist(o.1.0, a.0) <= 2.25f32 {
-                    Some(o.0)
-                } else if o.1.0 == dest && square_dist(a.0, o.1.0) <= 100f32 {
-                    Some(o.0)
-                } else {
-                    None
-                }
-            }).collect());
-            
-            if square_dist(a.0, dest) <= 100f32 {
-                r.3.push(grid.len() - 1);
-            }
+        // trust dest
+        grid.push((dest, 1.0));
+        let end = grid.len() - 1;
 
-            // calculate h
-            r.1 = ((a.0.x - dest.x).abs() + (a.0.y - dest.y).abs())/a.1;
+        // bucket the points so finding neighbours isn't quadratic
+        let key = |p: Vec2| ((p.x / 1.5).floor() as i32, (p.y / 1.5).floor() as i32);
+        let mut buckets: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, (p, _)) in grid.iter().enumerate().take(end) {
+            buckets.entry(key(*p)).or_default().push(i);
+        }
 
-            if r.0 == src.pos {
-                r.2 = r.1;
-            } else {
-                r.2 = f32::INFINITY;
-            }
+        let neighbours = |i: usize| {
+            let p = grid[i].0;
+            let (kx, ky) = key(p);
+            let mut found: Vec<usize> = (kx - 1..=kx + 1).flat_map(|x| (ky - 1..=ky + 1).map(move |y| (x, y)))
+                .filter_map(|k| buckets.get(&k))
+                .flatten()
+                .copied()
+                .filter(|&o| o != i && square_dist(grid[o].0, p) <= 2.25)
+                .collect();
 
-            if r.1.is_nan() || r.1.is_infinite() {
-                None
-            } else {
-                Some(r)
+            // the jump to dest is long enough to skip over a wall, so it has to be swept
+            if i != end && square_dist(p, dest) <= PATH_MARGIN.powi(2) && self.sweep_where(p, 2.0, dest - p, is_wall).is_none() {
+                found.push(end);
             }
-        }).enumerate().map(|a| { let mut r = a.1; r.5 = a.0; r}).collect::<Vec<_>>();
+            found
+        };
 
-        // actually do A*
-        let mut open = BinaryHeap::from([astar[0].clone()]);
-        while open.is_empty() == false {
-            let cur = open.pop().unwrap();
+        // next, we run A*, a step costs dist/val of where it lands so pheromone trails pull the path towards them.
+        // nothing costs less than dist over the best val there is, which keeps the estimate from overshooting.
+        let best = grid.iter().map(|(_, v)| *v).fold(1.0, f32::max);
+        let h = |i: usize| distance(grid[i].0, dest) / best;
+        let mut g = vec![f32::INFINITY; grid.len()];
+        let mut from: Vec<Option<usize>> = vec![None; grid.len()];
+        let mut open = BinaryHeap::from([AStar(h(0), 0)]);
+        g[0] = 0.0;
 
-            if cur.0 == dest {
-                let mut path = Vec::new();
+        while let Some(AStar(f, cur)) = open.pop() {
+            if cur == end {
+                let mut path = vec![grid[cur].0];
                 let mut this = cur;
-                
-                path.push(this.0);
-                while this.0 != src.pos {
-                    this = astar[this.6].clone();
-                    path.push(this.0);
+                while let Some(prev) = from[this] {
+                    path.push(grid[prev].0);
+                    this = prev;
                 }
 
+                path.reverse();
                 return Some(path);
             }
 
-            for i in cur.3.clone().into_iter() {
-                let n = &mut astar[i];
-                let o = n.clone();
-                let g = cur.1 + distance(cur.0, n.0);
-
-                if g < (n.2 - n.1) {
-                    n.2 = g + n.1;
-                    n.6 = cur.5;
+            // stale entry, a cheaper way here was already found
+            if f > g[cur] + h(cur) {
+                continue;
+            }
 
-                    if open.iter().any(|a| *a == o) {
-                        open.retain(|a| *a != o);
-                        open.push(n.clone());
-                    } else if open.iter().any(|a| a != n) {
-                        open.push(n.clone());
-                    }
+            for n in neighbours(cur) {
+                let cost = g[cur] + distance(grid[cur].0, grid[n].0) / grid[n].1;
+                if cost < g[n] {
+                    g[n] = cost;
+                    from[n] = Some(cur);
+                    open.push(AStar(cost + h(n), n));
                 }
             }
         }
@@ -215,136 +452,780 @@ impl Environment {
         None
     }
 
-    fn get_location(&self, src: &Ant, loc: Location) -> Vec2 {
-        match loc {
-            Location::Here => src.pos,
-            Location::Home | Location::Dest => todo!(),
-            Location::Pos(p) => p,
-            Location::PheromoneSrc => {
-                let a = self.things.clone().into_iter().filter_map(|a| {
-                    if a.borrow().type_id() != TypeId::of::<Pheromones>() {
-                        return None;
-                    }
+    // What an ant can sense of the world right now
+    pub fn senses<'a>(&'a self, ant: &'a Ant) -> Senses<'a> {
+        Senses::new(self, ant)
+    }
 
-                    let p = (*a.borrow()).downcast_ref::<Pheromones>().unwrap();
-                    Some(p.pos)
-                });
-                a.fold(vec2(f32::MAX, f32::MAX), |acc, p| {
-                    if square_dist(acc, src.pos) < square_dist(p, src.pos) {
-                        p
-                    } else {
-                        acc
-                    }
-                })                
-            },
+    // a number in [0, 1) from the world's dice, so the same seed always rolls the same
+    pub(crate) fn random(&self) -> f32 {
+        self.rng.borrow_mut().gen()
+    }
+
+    // Lets an ant's brain decide what to do, then does it
+    pub fn think(&mut self, ant: &mut Ant) {
+        // the brain is taken out while it thinks, so the ant's senses can look at the rest of it
+        let mut brain = mem::replace(&mut ant.brain, Box::new(Thinking));
+        let actions = brain.think(&Senses::new(self, ant));
+        ant.brain = brain;
+
+        for action in actions {
+            self.act(ant, action);
         }
     }
 
-    fn evaluate_src(&self, src: &Ant, source: Source) -> Memory {
-        match source {
-            Source::Number(n) => Memory::Number(n),
-            Source::Dist(a) => {
-                let target = match a {
-                    Location::Here => return Memory::Number(0.0),
-                    _ => self.get_location(src, a),
-                };
+    // Adds strength to the pheromones at pos, or puts new ones there if there aren't any,
+    // up to MAX_PHEROMONE
+    pub fn emit_pheromone(&mut self, pos: Vec2, strength: f32) {
+        let existing = self.pheromone_index.query_radius(pos, PHEROMONE_MERGE).into_iter().filter_map(|r| {
+            let d = r.borrow().downcast_ref::<Pheromones>().map(|p| distance(p.pos, pos))?;
+            (d <= PHEROMONE_MERGE).then_some((r, d))
+        }).min_by(|a, b| a.1.total_cmp(&b.1));
 
-                Memory::Number(distance(target, src.pos))
+        match existing {
+            Some((r, _)) => {
+                let mut b = r.borrow_mut();
+                let p = b.downcast_mut::<Pheromones>().unwrap();
+                p.strength = (p.strength + strength).min(MAX_PHEROMONE);
+                drop(b);
+                self.pheromone_index.update(&r);
+            },
+            None => {
+                self.add(Pheromones::new(pos, strength.min(MAX_PHEROMONE)), 2);
             },
-            Source::Memory(i) => src.memory[src.memory.len() - (i as usize + 1)],
-            Source::PheromoneStrength => Memory::Number(self.pheromone_strength_at_pos(src.pos)),
-            Source::Food => todo!(),
-            Source::Loc(l) => Memory::Position(self.get_location(src, l)),
         }
     }
 
-    fn evaluate_cond(&self, src: &Ant, cond: Condition) -> bool {
-        match cond {
-            Condition::Equal(a, b) => {
-                let aa = self.evaluate_src(src, a);
-                let bb = self.evaluate_src(src, b);
-                aa == bb
-            },
-            Condition::Not(cond) => !self.evaluate_cond(src, *cond),
-            Condition::LessThan(a, b) => {
-                match self.evaluate_src(src, a) {
-                    Memory::Number(aa) => {
-                        match self.evaluate_src(src, b) {
-                            Memory::Number(bb) => aa < bb,
-                            _ => false,
-                        }
-                    },
-                    Memory::Position(aa) => {
-                        match self.evaluate_src(src, b) {
-                            Memory::Position(bb) => lessThan(aa, bb).x && lessThan(aa, bb).y,
-                            _ => false,
-                        }
-                    }
+    fn act(&mut self, ant: &mut Ant, action: Action) {
+        match action {
+            Action::SetDest(dest) => {
+                // planning is slow, so an ant that keeps being told the same place keeps its route,
+                // unless it's used up or something got in the way of the next leg
+                let blocked = |p: &Vec2| self.sweep_where(ant.pos, 2.0, *p - ant.pos, is_wall).is_some();
+                if ant.dest != Some(dest) || ant.path.first().is_none_or(blocked) {
+                    ant.path = self.chart_path(ant, dest).unwrap_or_default();
                 }
+                ant.dest = Some(dest);
+                ant.steering = None;
             },
-            Condition::GreaterThan(a, b) => {
-                match self.evaluate_src(src, a) {
-                    Memory::Number(aa) => {
-                        match self.evaluate_src(src, b) {
-                            Memory::Number(bb) => aa > bb,
-                            _ => false,
-                        }
-                    },
-                    Memory::Position(aa) => {
-                        match self.evaluate_src(src, b) {
-                            Memory::Position(bb) => greaterThan(aa, bb).x && greaterThan(aa, bb).y,
-                            _ => false,
-                        }
-                    }
+            Action::Steer(turn, speed) => {
+                ant.path.clear();
+                ant.dest = None;
+                ant.steering = Some((turn.clamp(-1.0, 1.0), speed.clamp(0.0, 1.0)));
+            },
+            Action::EmitPheromone(strength) => {
+                if strength > 0.0 {
+                    self.emit_pheromone(ant.pos, strength);
                 }
-            }
+            },
+            Action::Remember(m) => ant.memory.remember(m),
+            Action::Forget => {
+                let _ = ant.memory.forget();
+            },
+            Action::Store(name, m) => ant.memory.store(&name, m),
+            Action::Clear(name) => ant.memory.clear(&name),
         }
     }
 
-    fn make_decision<F: FnMut(Then)>(&self, src: &Ant, d: Decision, mut f: F) {
-        match d {
-            Decision::Always(t) => f(t.clone()),
-            Decision::If(c, t) => {
-                if self.evaluate_cond(src, c) {
-                    f(t.clone())
-                }
-            }
-            Decision::IfHaveFood(t) => {
-                if src.has_food {
-                    f(t.clone())
-                }
-            }
+    // Turns an ant towards where it's going and moves it forward, stopping at anything in the way.
+    // With a path it follows it, dropping waypoints as they're reached, without one it goes
+    // wherever its brain steers it, or wanders.
+    fn move_ant(&mut self, ant: &mut Ant, dt: f32) {
+        while ant.path.first().is_some_and(|p| distance(*p, ant.pos) <= WAYPOINT_REACH) {
+            ant.path.remove(0);
+        }
+
+        let max_turn = ant.turn_rate * dt;
+        let (want, reach, throttle) = match (ant.path.first(), ant.steering) {
+            (Some(p), _) => {
+                let d = *p - ant.pos;
+                (d.x.atan2(d.y).to_degrees(), distance(*p, ant.pos), 1.0)
+            },
+            (None, Some((turn, speed))) => (ant.heading + turn * max_turn, f32::MAX, speed),
+            (None, None) => (ant.heading + self.rng.get_mut().gen_range(-1.0..=1.0) * max_turn, f32::MAX, 1.0),
+        };
+
+        // turn the short way round, no faster than the ant can
+        let diff = (want - ant.heading + 180.0).rem_euclid(360.0) - 180.0;
+        ant.set_heading(ant.heading + diff.clamp(-max_turn, max_turn));
+
+        // slow down while still facing the wrong way, so it doesn't circle around waypoints
+        let off = (want - ant.heading + 180.0).rem_euclid(360.0) - 180.0;
+        let speed = ant.max_speed * throttle * off.to_radians().cos().max(0.0);
+        let delta = ant.facing() * (speed * dt).min(reach);
+        if delta != vec2(0.0, 0.0) {
+            ant.pos = self.advance(ant.pos, 2.0, delta);
         }
     }
 
     pub fn step(&mut self) {
-        // first, process each pheromone
+        // first, process each pheromone, the ones that fade away completely are gone
+        let mut faded = Vec::new();
         self.things.iter_mut().for_each(|t| {
             if (*t.borrow()).type_id() == TypeId::of::<Pheromones>() {
-                t.borrow_mut().downcast_mut::<Pheromones>().unwrap().strength -= 0.1;
+                let mut b = t.borrow_mut();
+                let p = b.downcast_mut::<Pheromones>().unwrap();
+                p.strength -= PHEROMONE_DECAY;
+                if p.strength <= 0.0 {
+                    faded.push(Rc::clone(t));
+                }
+                drop(b);
+                self.pheromone_index.update(t);
             }
         });
+        faded.into_iter().for_each(|t| self.rm(t));
 
         // now, actual ant behaior.
-        for a in &self.ants {
-            let mut tmp = a.borrow_mut();
-            let ant = (*tmp).downcast_mut::<Ant>().unwrap();
+        // each ant is worked on as a copy, so the rest of the world can be looked at and changed meanwhile
+        let ants = self.ants.clone();
+        for a in &ants {
+            let Some(mut ant) = a.borrow().downcast_ref::<Ant>().cloned() else {
+                continue;
+            };
 
             // their brains, ants have simple brains
-            for d in &ant.decisions {
-                self.make_decision(&ant.clone(), d.clone(), |mut t| {
-                    loop {
-                        match t {
-                            Then::Forget(b) => { 
-                                let _ = ant.memory.pop_front();
-                                t = *b; 
-                                continue; 
-                            },
-                            _ => todo!(),
-                        }
+            self.think(&mut ant);
+
+            let dt = self.dt;
+            self.move_ant(&mut ant, dt);
+
+            *a.borrow_mut().downcast_mut::<Ant>().unwrap() = ant;
+            // the ants after this one have to bump into where it is now
+            self.collider_index.update(a);
+        }
+
+        self.resolve_collisions();
+        self.tick += 1;
+    }
+
+    // push every ant back out of whatever it ended up overlapping
+    fn resolve_collisions(&mut self) {
+        for a in &self.ants {
+            let b = a.borrow().aabb();
+            for c in &self.collider_index.query_aabb(&b) {
+                if Rc::ptr_eq(a, c) {
+                    continue;
+                }
+
+                let contact = a.borrow().contact(&*c.borrow());
+                if let Some(contact) = contact {
+                    if let Some(ant) = a.borrow_mut().downcast_mut::<Ant>() {
+                        ant.pos = ant.pos - contact.normal * contact.depth;
                     }
-                });
+                }
+            }
+
+            self.collider_index.update(a);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ant::{Condition, Decision, Eviction, Location, Memories, Memory, Policy, Program, Source, Then}, dsl::parse_program, shape::Rect};
+
+    // an ant that stands still, so decisions can be checked without it wandering off
+    fn ant(pos: Vec2, first: Decision) -> Ant {
+        let mut a = Ant::new(pos, Program::from(vec![first]));
+        a.set_max_speed(0.0);
+        a
+    }
+
+    fn walker(pos: Vec2, speed: f32, turn_rate: f32) -> Ant {
+        let mut a = ant(pos, Decision::Always(Then::Nothing));
+        a.set_max_speed(speed);
+        a.set_turn_rate(turn_rate);
+        a
+    }
+
+    fn only_ant(env: &Environment) -> Ant {
+        env.ants[0].borrow().downcast_ref::<Ant>().unwrap().clone()
+    }
+
+    fn pheromones(env: &Environment) -> Vec<Pheromones> {
+        env.things.iter().filter_map(|t| t.borrow().downcast_ref::<Pheromones>().cloned()).collect()
+    }
+
+    #[test]
+    fn remember_and_forget() {
+        let mut env = Environment::new();
+        env.add(ant(vec2(3.0, 4.0), Decision::Always(Then::Remember(Source::Number(7.0),
+            Box::new(Then::Remember(Source::Dist(Location::Pos(vec2(0.0, 0.0))), Box::new(Then::Nothing)))))), 5);
+
+        env.step();
+        let a = only_ant(&env);
+        assert!(a.memory.iter().eq(&[Memory::Number(7.0), Memory::Number(5.0)]));
+
+        let mut env = Environment::new();
+        env.add(ant(vec2(3.0, 4.0), Decision::Always(Then::Remember(Source::Loc(Location::Here),
+            Box::new(Then::Forget(Box::new(Then::Remember(Source::Number(1.0), Box::new(Then::Nothing)))))))), 5);
+
+        env.step();
+        env.step();
+        // every step remembers where it is, forgets the oldest thing and remembers a one
+        let a = only_ant(&env);
+        assert!(a.memory.iter().eq(&[Memory::Position(vec2(3.0, 4.0)), Memory::Number(1.0)]));
+    }
+
+    #[test]
+    fn bounded_memory_and_registers() {
+        let remembering = |eviction| {
+            let mut env = Environment::new();
+            let mut a = ant(vec2(0.0, 0.0), Decision::Always(Then::Remember(Source::Tick, Box::new(Then::Nothing))));
+            *a.memory_mut() = Memories::bounded(2, eviction);
+            env.add(a, 5);
+            for _ in 0..4 {
+                env.step();
+            }
+            only_ant(&env).memory.iter().copied().collect::<Vec<_>>()
+        };
+        assert_eq!(remembering(Eviction::Oldest), [Memory::Tick(2), Memory::Tick(3)]);
+        assert_eq!(remembering(Eviction::Newest), [Memory::Tick(0), Memory::Tick(3)]);
+        assert_eq!(remembering(Eviction::Refuse), [Memory::Tick(0), Memory::Tick(1)]);
+
+        let mut env = Environment::new();
+        let wall = env.add(Rect::new(vec2(10.0, 0.0), vec2(2.0, 2.0)), 3);
+        let mut a = ant(vec2(0.0, 0.0), Decision::Always(Then::Nothing));
+        a.set_brain(parse_program("
+            if not recall(seen) == true then remember(thing(nearest_wall) as wall), remember(true as seen)
+            always remember(where(recall(wall)) as wall_at), forget(unused)
+        ").unwrap());
+        env.add(a, 5);
+        env.step();
+
+        let id = env.id(&wall).unwrap();
+        let a = only_ant(&env);
+        assert_eq!(a.memory.register("wall"), Some(Memory::Entity(id)));
+        assert_eq!(a.memory.register("wall_at"), Some(Memory::Position(wall.borrow().get_center())));
+        assert_eq!(a.memory.registers().len(), 3);
+        assert!(a.memory.is_empty());
+
+        // the ant still knows the wall once it's gone, but not where it is
+        env.rm(wall);
+        assert!(env.entity(id).is_none());
+        env.step();
+        assert_eq!(env.tick(), 2);
+        let a = only_ant(&env);
+        assert_eq!(a.memory.register("wall"), Some(Memory::Entity(id)));
+        assert!(matches!(a.memory.register("wall_at"), Some(Memory::Number(n)) if n.is_nan()));
+    }
+
+    #[test]
+    fn emit_pheromone() {
+        let mut env = Environment::new();
+        env.add(ant(vec2(10.0, 10.0), Decision::Always(Then::EmitPheromone(Source::Number(5.0)))), 5);
+
+        env.step();
+        let p = pheromones(&env);
+        assert_eq!(p.len(), 1);
+        assert_eq!(p[0].pos, vec2(10.0, 10.0));
+        assert_eq!(p[0].strength(), 5.0);
+        assert!(env.pheromone_strength_at_pos(vec2(12.0, 10.0)) > 0.0);
+
+        // staying put reinforces the same pheromones
+        env.step();
+        let p = pheromones(&env);
+        assert_eq!(p.len(), 1);
+        assert!((p[0].strength() - 9.9).abs() < 1e-4);
+
+        // moving away leaves a new one behind
+        env.ants[0].borrow_mut().downcast_mut::<Ant>().unwrap().pos = vec2(20.0, 10.0);
+        env.step();
+        assert_eq!(pheromones(&env).len(), 2);
+
+        // however strong they're emitted, they only get so strong
+        env.emit_pheromone(vec2(50.0, 50.0), f32::INFINITY);
+        env.emit_pheromone(vec2(50.0, 50.0), 1.0);
+        assert!(pheromones(&env).iter().any(|p| p.strength() == MAX_PHEROMONE));
+    }
+
+    #[test]
+    fn ants_bump_into_each_other() {
+        // the first one moves, then the second has to stop where the first one is now, not where it was
+        let mut env = Environment::new();
+        // far enough that where it was isn't in any grid cell near the second
+        let mut a = walker(vec2(-20.0, 0.0), 25.0, 0.0);
+        a.set_heading(90.0);
+        a.set_path(vec![vec2(50.0, 0.0)]);
+        // and fast enough to go straight through it if it didn't see it
+        let mut b = walker(vec2(20.0, 0.0), 25.0, 0.0);
+        b.set_heading(-90.0);
+        b.set_path(vec![vec2(-50.0, 0.0)]);
+        let (a, b) = (env.add(a, 5), env.add(b, 5));
+        let pos = |t: &Rc<RefCell<dyn Shape>>| t.borrow().downcast_ref::<Ant>().unwrap().pos;
+
+        env.step();
+        assert!(distance(pos(&a), vec2(5.0, 0.0)) < 1e-3, "{:?}", pos(&a));
+        assert!(distance(pos(&b), vec2(9.0, 0.0)) < 1e-3, "{:?}", pos(&b));
+
+        // and they stay out of each other after that
+        for _ in 0..5 {
+            env.step();
+            assert!(distance(pos(&a), pos(&b)) >= 4.0 - 1e-3);
+        }
+    }
+
+    #[test]
+    fn nearest_pheromones() {
+        let mut env = Environment::new();
+        assert!(env.nearest_pheromone(vec2(0.0, 0.0)).is_none());
+
+        // past where the index gets searched, it still turns up
+        env.add(Pheromones::new(vec2(2000.0, 0.0), 1.0), 2);
+        let center = |p: Option<Rc<RefCell<dyn Shape>>>| p.unwrap().borrow().get_center();
+        assert_eq!(center(env.nearest_pheromone(vec2(0.0, 0.0))), vec2(2000.0, 0.0));
+
+        // a strong one's box reaching further doesn't make it nearer
+        env.add(Pheromones::new(vec2(-30.0, 0.0), 9.0), 2);
+        env.add(Pheromones::new(vec2(25.0, 0.0), 0.5), 2);
+        assert_eq!(center(env.nearest_pheromone(vec2(0.0, 0.0))), vec2(25.0, 0.0));
+        assert_eq!(center(env.nearest_pheromone(vec2(-10.0, 3.0))), vec2(-30.0, 0.0));
+    }
+
+    #[test]
+    fn pheromones_fade() {
+        let mut env = Environment::new();
+        env.add(Pheromones::new(vec2(0.0, 0.0), 0.15), 2);
+
+        env.step();
+        assert_eq!(pheromones(&env).len(), 1);
+        env.step();
+        assert!(pheromones(&env).is_empty());
+        assert!(env.pheromone_index.is_empty());
+        assert!(env.renderers.is_empty());
+    }
+
+    #[test]
+    fn indexes_follow_the_world() {
+        // each index holds exactly its list, and finds everything where it is now
+        fn consistent(env: &Environment) {
+            let everywhere = Aabb { min: vec2(-300.0, -300.0), max: vec2(300.0, 300.0) };
+            let found = env.collider_index.query_aabb(&everywhere);
+            assert_eq!(env.collider_index.len(), env.colliders.len());
+            assert_eq!(found.len(), env.colliders.len());
+            for c in &env.colliders {
+                assert!(found.iter().any(|f| Rc::ptr_eq(f, c)));
+                assert!(env.collider_index.query_aabb(&c.borrow().aabb()).iter().any(|f| Rc::ptr_eq(f, c)));
             }
+
+            let smells: Vec<_> = env.things.iter().filter(|t| t.borrow().is::<Pheromones>()).collect();
+            assert_eq!(env.pheromone_index.len(), smells.len());
+            assert_eq!(env.pheromone_index.query_aabb(&everywhere).len(), smells.len());
+            for p in smells {
+                let pos = p.borrow().downcast_ref::<Pheromones>().unwrap().pos;
+                assert!(env.pheromone_index.query_point(pos).iter().any(|f| Rc::ptr_eq(f, p)));
+            }
+        }
+
+        let mut env = Environment::new();
+        env.set_seed(3);
+        let wall = env.add(Rect::new(vec2(40.0, 60.0), vec2(4.0, 120.0)), 3);
+        env.add(Rect::new(vec2(-60.0, -40.0), vec2(120.0, 4.0)), 3);
+        env.add(Pheromones::new(vec2(-20.0, 20.0), 0.5), 2);
+        let mut ants = Vec::new();
+        for i in 0..4 {
+            let mut a = ant(vec2(i as f32 * 5.0, 0.0), Decision::Always(Then::EmitPheromone(Source::Number(0.3))));
+            a.set_max_speed(2.0);
+            a.set_turn_rate(20.0);
+            ants.push(env.add(a, 5));
+        }
+        consistent(&env);
+
+        let start: Vec<Vec2> = ants.iter().map(|a| a.borrow().downcast_ref::<Ant>().unwrap().pos).collect();
+        for _ in 0..15 {
+            env.step();
+            consistent(&env);
+        }
+        // far enough that a stale index would be looking in the wrong cells
+        assert!(ants.iter().zip(start).all(|(a, s)| distance(a.borrow().downcast_ref::<Ant>().unwrap().pos, s) > GRID_CELL));
+        assert!(pheromones(&env).len() > 4);
+
+        env.rm(wall);
+        env.rm(Rc::clone(&ants[0]));
+        consistent(&env);
+        assert_eq!(env.colliders.len(), 4);
+
+        // once nothing emits anymore, everything fades and the index empties out with it
+        for a in &ants[1..] {
+            env.rm(Rc::clone(a));
+        }
+        for _ in 0..10 {
+            env.step();
+            consistent(&env);
+        }
+        assert!(pheromones(&env).is_empty());
+        assert_eq!(env.colliders.len(), 1);
+    }
+
+    #[test]
+    fn set_dest() {
+        let mut env = Environment::new();
+        let wall = Rect::new(vec2(14.0, 8.0), vec2(2.0, 16.0));
+        env.add(wall, 3);
+        env.add(ant(vec2(0.0, 0.0), Decision::If(
+            Condition::Equal(Source::Number(0.0), Source::Number(0.0)),
+            Then::SetDest(Location::Pos(vec2(30.0, 0.0))),
+        )), 5);
+
+        env.step();
+        let a = only_ant(&env);
+        assert_eq!(a.dest(), Some(vec2(30.0, 0.0)));
+
+        // where it started was already reached and dropped
+        let path = a.path();
+        assert!(distance(path[0], vec2(0.0, 0.0)) <= 1.5);
+        assert_eq!(path.last(), Some(&vec2(30.0, 0.0)));
+        // it goes around the wall, not through it
+        assert!(path.windows(2).all(|w| geometry::sweep_circle(w[0], 2.0, w[1] - w[0], &wall).is_none()));
+        assert!(path.iter().any(|p| p.y.abs() > 8.0));
+
+        // told the same place again, it keeps the route it has instead of planning a new one
+        let kept = vec![vec2(0.0, -15.0), vec2(30.0, -15.0), vec2(30.0, 0.0)];
+        env.ants[0].borrow_mut().downcast_mut::<Ant>().unwrap().set_path(kept.clone());
+        env.step();
+        assert_eq!(only_ant(&env).path(), kept);
+
+        // unless the way to the next point is blocked now
+        env.add(Rect::new(vec2(-10.0, -6.0), vec2(20.0, 2.0)), 3);
+        env.step();
+        let path = only_ant(&env).path().to_vec();
+        assert_ne!(path, kept);
+        assert_eq!(path.last(), Some(&vec2(30.0, 0.0)));
+    }
+
+    #[test]
+    fn shortest_paths() {
+        let length = |path: &[Vec2]| path.windows(2).map(|w| distance(w[0], w[1])).sum::<f32>();
+
+        // with nothing in the way it's about a straight line, whichever way that goes
+        let env = Environment::new();
+        let a = ant(vec2(0.0, 0.0), Decision::Always(Then::Nothing));
+        for dest in [vec2(20.0, 0.0), vec2(14.0, 14.0), vec2(-5.0, 18.0)] {
+            let path = env.chart_path(&a, dest).unwrap();
+            assert!(length(&path) < distance(dest, a.pos) * 1.1, "{:?} {}", dest, length(&path));
         }
+
+        // a trail off to the side is worth going out of the way for
+        let mut env = Environment::new();
+        for x in 0..10 {
+            env.add(Pheromones::new(vec2(x as f32 * 2.0, 6.0), 8.0), 2);
+        }
+        let path = env.chart_path(&a, vec2(20.0, 0.0)).unwrap();
+        assert!(path.iter().any(|p| p.y > 3.0), "{:?}", path);
+    }
+
+    #[test]
+    fn home_dest_and_food() {
+        let go_home = Decision::If(
+            Condition::GreaterThan(Source::Food, Source::Number(0.0)),
+            Then::SetDest(Location::Home),
+        );
+
+        let mut env = Environment::new();
+        let mut a = ant(vec2(10.0, 0.0), go_home.clone());
+        a.set_home(vec2(0.0, 0.0));
+        let a = env.add(a, 5);
+
+        // nothing to carry, so it stays put
+        env.step();
+        assert_eq!(only_ant(&env).dest(), None);
+        assert!(only_ant(&env).food() == 0.0);
+
+        a.borrow_mut().downcast_mut::<Ant>().unwrap().set_food(2.0);
+        env.step();
+        let b = only_ant(&env);
+        assert_eq!(b.dest(), Some(vec2(0.0, 0.0)));
+        assert_eq!(b.path().last(), Some(&vec2(0.0, 0.0)));
+
+        assert_eq!(env.senses(&b).evaluate(&Source::Dist(Location::Dest)), Memory::Number(10.0));
+        assert_eq!(env.senses(&b).evaluate(&Source::Loc(Location::Home)), Memory::Position(vec2(0.0, 0.0)));
+        assert_eq!(env.senses(&b).evaluate(&Source::Food), Memory::Number(2.0));
+        assert!(env.senses(&b).holds(&Condition::LessThan(Source::Dist(Location::Home), Source::Number(11.0))));
+
+        // no destination yet is neither near nor far
+        let c = ant(vec2(0.0, 0.0), go_home);
+        assert!(!env.senses(&c).holds(&Condition::GreaterThan(Source::Dist(Location::Dest), Source::Number(1e6))));
+        assert!(!env.senses(&c).holds(&Condition::LessThan(Source::Dist(Location::Dest), Source::Number(1e6))));
+        assert!(!c.has_food());
+
+        // and going to somewhere that doesn't exist does nothing
+        let mut env = Environment::new();
+        env.add(ant(vec2(0.0, 0.0), Decision::Always(Then::SetDest(Location::Dest))), 5);
+        env.add(ant(vec2(5.0, 0.0), Decision::Always(Then::SetDest(Location::PheromoneSrc))), 5);
+        env.add(ant(vec2(-5.0, 0.0), Decision::Always(Then::SetDest(Location::NearestWall))), 5);
+        env.step();
+        assert!(env.ants.iter().all(|a| a.borrow().downcast_ref::<Ant>().unwrap().dest().is_none()));
+    }
+
+    #[test]
+    fn unreachable_dest() {
+        let mut env = Environment::new();
+        // a box around the destination
+        for wall in [
+            Rect::new(vec2(20.0, 10.0), vec2(20.0, 2.0)),
+            Rect::new(vec2(20.0, -8.0), vec2(20.0, 2.0)),
+            Rect::new(vec2(20.0, 10.0), vec2(2.0, 20.0)),
+            Rect::new(vec2(38.0, 10.0), vec2(2.0, 20.0)),
+        ] {
+            env.add(wall, 1);
+        }
+
+        let a = ant(vec2(0.0, 0.0), Decision::Always(Then::Nothing));
+        assert!(env.chart_path(&a, vec2(30.0, 0.0)).is_none());
+        assert!(env.chart_path(&a, vec2(0.0, 20.0)).is_some());
+    }
+
+    #[test]
+    fn follow_path() {
+        let mut env = Environment::new();
+        let mut a = walker(vec2(0.0, 0.0), 1.0, 360.0);
+        a.set_path(vec![vec2(0.0, 0.0), vec2(5.0, 0.0), vec2(5.0, 5.0)]);
+        env.add(a, 5);
+
+        env.step();
+        let a = only_ant(&env);
+        assert!((a.heading() - 90.0).abs() < 1e-3);
+        assert!(distance(a.pos(), vec2(1.0, 0.0)) < 1e-3);
+        assert_eq!(a.path().len(), 2);
+
+        // it lands right on the last waypoint instead of overshooting
+        let arrived = (0..15).any(|_| {
+            env.step();
+            let a = only_ant(&env);
+            a.path().len() == 1 && distance(a.pos(), vec2(5.0, 5.0)) < 1e-3
+        });
+        assert!(arrived);
+
+        // and once there's nowhere left to go it wanders off
+        env.step();
+        assert!(only_ant(&env).path().is_empty());
+    }
+
+    #[test]
+    fn turn_rate_and_time_step() {
+        let mut env = Environment::new();
+        let mut a = walker(vec2(0.0, 0.0), 2.0, 30.0);
+        a.set_path(vec![vec2(100.0, 0.0)]);
+        env.add(a, 5);
+
+        // facing up with the target to the right, so it can only turn a bit and barely moves
+        env.step();
+        let a = only_ant(&env);
+        assert!((a.heading() - 30.0).abs() < 1e-3);
+        assert!(distance(a.pos(), vec2(0.0, 0.0)) < 2.0);
+
+        let mut env = Environment::new();
+        let mut a = walker(vec2(0.0, 0.0), 2.0, 30.0);
+        a.set_path(vec![vec2(0.0, 100.0)]);
+        env.add(a, 5);
+        env.set_time_step(0.5);
+        env.step();
+        assert!(distance(only_ant(&env).pos(), vec2(0.0, 1.0)) < 1e-4);
+    }
+
+    #[test]
+    fn sweeping_off_walls() {
+        let mut env = Environment::new();
+        env.add(Rect::new(vec2(10.0, 20.0), vec2(2.0, 40.0)), 3);
+
+        // stopped against the wall, then free to go anywhere but further in
+        let touching = env.advance(vec2(0.0, 0.0), 2.0, vec2(20.0, 0.0));
+        assert!((touching.x - 8.0).abs() < 1e-3);
+        assert_eq!(env.advance(touching, 2.0, vec2(-5.0, 0.0)), touching + vec2(-5.0, 0.0));
+        assert_eq!(env.advance(touching, 2.0, vec2(0.0, 3.0)), touching + vec2(0.0, 3.0));
+        assert_eq!(env.advance(touching, 2.0, vec2(1.0, 0.0)), touching);
+        assert!(env.sweep(touching, 2.0, vec2(-5.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn walls_stop_ants() {
+        let mut env = Environment::new();
+        env.add(Rect::new(vec2(10.0, 20.0), vec2(2.0, 40.0)), 3);
+        let mut a = walker(vec2(0.0, 0.0), 1.5, 90.0);
+        a.set_heading(90.0);
+        a.set_path(vec![vec2(20.0, 0.0)]);
+        env.add(a, 5);
+
+        for _ in 0..20 {
+            env.step();
+            assert!(only_ant(&env).pos().x <= 8.0 + 1e-3);
+        }
+        assert!(only_ant(&env).pos().x > 7.5);
+
+        // then it can turn around and leave
+        env.ants[0].borrow_mut().downcast_mut::<Ant>().unwrap().set_path(vec![vec2(-20.0, 0.0)]);
+        for _ in 0..20 {
+            env.step();
+        }
+        assert!(only_ant(&env).pos().x < 0.0);
+
+        // and a wandering ant doesn't get stuck on it either
+        env.ants[0].borrow_mut().downcast_mut::<Ant>().unwrap().set_path(vec![vec2(20.0, 0.0)]);
+        for _ in 0..30 {
+            env.step();
+        }
+        let stuck = only_ant(&env).pos();
+        assert!(stuck.x > 7.5);
+        env.ants[0].borrow_mut().downcast_mut::<Ant>().unwrap().set_path(Vec::new());
+        for _ in 0..20 {
+            env.step();
+        }
+        assert!(distance(only_ant(&env).pos(), stuck) > 1.0);
+    }
+
+    #[test]
+    fn wander() {
+        let run = |seed| {
+            let mut env = Environment::new();
+            env.set_seed(seed);
+            env.add(walker(vec2(0.0, 0.0), 1.0, 45.0), 5);
+            for _ in 0..10 {
+                env.step();
+            }
+            only_ant(&env)
+        };
+
+        let (a, b, c) = (run(1), run(1), run(2));
+        assert!(distance(a.pos(), vec2(0.0, 0.0)) > 1.0);
+        assert_eq!(a.pos(), b.pos());
+        assert!(a.pos() != c.pos());
+    }
+
+    #[test]
+    fn steering() {
+        use crate::brain::FnBrain;
+
+        let mut env = Environment::new();
+        let mut a = walker(vec2(0.0, 0.0), 2.0, 45.0);
+        a.set_brain(FnBrain::new(|_: &Senses| vec![Action::Steer(1.0, 0.5)]));
+        a.set_path(vec![vec2(0.0, 10.0)]);
+        env.add(a, 5);
+        env.step();
+
+        // hard clockwise at half speed, and any path is dropped
+        let dt = env.time_step();
+        let a = only_ant(&env);
+        assert!((a.heading() - 45.0 * dt).abs() < 1e-4);
+        assert!((distance(a.pos(), vec2(0.0, 0.0)) - dt).abs() < 1e-4);
+        assert_eq!((a.steering(), a.dest(), a.path.len()), (Some((1.0, 0.5)), None, 0));
+
+        let mut a = a.clone();
+        env.act(&mut a, Action::SetDest(vec2(5.0, 5.0)));
+        assert_eq!(a.steering(), None);
+    }
+
+    #[test]
+    fn policies() {
+        use crate::ant::Program;
+
+        let remember = |n: f32| Decision::Always(Then::Remember(Source::Number(n), Box::new(Then::Nothing)));
+        let never = Decision::If(Condition::LessThan(Source::Number(1.0), Source::Number(0.0)), Then::Forget(Box::new(Then::Nothing)));
+        let run = |program: Program, steps: usize| {
+            let mut env = Environment::new();
+            let mut a = ant(vec2(0.0, 0.0), never.clone());
+            a.set_brain(program);
+            env.add(a, 5);
+            for _ in 0..steps {
+                env.step();
+            }
+            only_ant(&env).memory.iter().copied().collect::<Vec<_>>()
+        };
+
+        let all = Program::new(Policy::All, vec![remember(1.0), remember(2.0), remember(3.0)]);
+        assert_eq!(run(all, 1), [Memory::Number(1.0), Memory::Number(2.0), Memory::Number(3.0)]);
+
+        let first = Program::new(Policy::First, vec![never.clone(), remember(2.0), remember(3.0)]);
+        assert_eq!(run(first, 2), [Memory::Number(2.0), Memory::Number(2.0)]);
+
+        let mut weighted = Program::new(Policy::WeightedRandom, Vec::new());
+        weighted.push_weighted(remember(1.0), 0.0);
+        weighted.push_weighted(remember(2.0), 1.0);
+        weighted.push_weighted(never.clone(), 100.0);
+        assert_eq!(run(weighted, 5), [Memory::Number(2.0); 5]);
+
+        let mut weighted = Program::new(Policy::WeightedRandom, Vec::new());
+        weighted.push_weighted(remember(1.0), 1.0);
+        weighted.push_weighted(remember(2.0), 3.0);
+        let picked = run(weighted, 400);
+        let twos = picked.iter().filter(|m| **m == Memory::Number(2.0)).count();
+        assert_eq!(picked.len(), 400);
+        assert!((250..350).contains(&twos));
+
+        // an empty program is fine too
+        assert!(run(Program::default(), 3).is_empty());
+    }
+
+    #[test]
+    fn expressions() {
+        let mut a = ant(vec2(3.0, 4.0), Decision::Always(Then::Nothing));
+        a.set_home(vec2(0.0, 0.0));
+        a.set_food(2.0);
+        let env = Environment::new();
+        let b = Box::<Source>::new;
+        let num = |n| b(Source::Number(n));
+        let here = || b(Source::Loc(Location::Here));
+        let eval = |s: Source| env.senses(&a).evaluate(&s);
+        let check = |c: Condition| env.senses(&a).holds(&c);
+
+        assert_eq!(eval(Source::Add(b(Source::Food), num(1.0))), Memory::Number(3.0));
+        assert_eq!(eval(Source::Sub(num(1.0), b(Source::Food))), Memory::Number(-1.0));
+        assert_eq!(eval(Source::Div(b(Source::Mul(b(Source::Food), num(3.0))), num(4.0))), Memory::Number(1.5));
+        assert_eq!(eval(Source::Min(num(1.0), num(-2.0))), Memory::Number(-2.0));
+        assert_eq!(eval(Source::Max(num(1.0), num(-2.0))), Memory::Number(1.0));
+        assert_eq!(eval(Source::Abs(num(-2.0))), Memory::Number(2.0));
+
+        assert_eq!(eval(Source::Mul(here(), num(2.0))), Memory::Position(vec2(6.0, 8.0)));
+        assert_eq!(eval(Source::Sub(here(), b(Source::Loc(Location::Home)))), Memory::Position(vec2(3.0, 4.0)));
+        assert_eq!(eval(Source::Abs(b(Source::Sub(num(0.0), here())))), Memory::Position(vec2(3.0, 4.0)));
+        assert_eq!(eval(Source::Dot(here(), here())), Memory::Number(25.0));
+        assert_eq!(eval(Source::Length(here())), Memory::Number(5.0));
+        assert_eq!(eval(Source::Normalize(here())), Memory::Position(vec2(0.6, 0.8)));
+        assert_eq!(eval(Source::Normalize(b(Source::Loc(Location::Home)))), Memory::Position(vec2(0.0, 0.0)));
+        assert!(matches!(eval(Source::Length(num(1.0))), Memory::Number(n) if n.is_nan()));
+
+        let yes = || Box::new(Condition::Equal(Source::Food, Source::Number(2.0)));
+        let no = || Box::new(Condition::LessThan(Source::Food, Source::Number(2.0)));
+        assert!(check(Condition::And(yes(), yes())));
+        assert!(!check(Condition::And(yes(), no())));
+        assert!(check(Condition::Or(no(), yes())));
+        assert!(!check(Condition::Or(no(), no())));
+        assert!(check(Condition::Xor(no(), yes())));
+        assert!(!check(Condition::Xor(yes(), yes())));
+
+        let third = Source::Div(num(1.0), num(3.0));
+        assert!(check(Condition::ApproxEqual(Source::Mul(b(third.clone()), num(3.0)), Source::Number(1.0), 1e-5)));
+        assert!(!check(Condition::ApproxEqual(third, Source::Number(0.3), 1e-5)));
+        assert!(check(Condition::ApproxEqual(Source::Loc(Location::Here), Source::Loc(Location::Pos(vec2(3.0, 4.1))), 0.2)));
+        assert!(!check(Condition::ApproxEqual(Source::Loc(Location::Here), Source::Number(5.0), 100.0)));
+
+        assert!(check(Condition::Between(Source::Dist(Location::Home), Source::Number(5.0), Source::Number(6.0))));
+        assert!(!check(Condition::Between(Source::Food, Source::Number(2.5), Source::Number(6.0))));
+        assert!(check(Condition::Between(Source::Loc(Location::Here), Source::Loc(Location::Home), Source::Loc(Location::Pos(vec2(5.0, 5.0))))));
+    }
+
+    #[test]
+    fn checked_ants() {
+        let mut env = Environment::new();
+        let mut a = ant(vec2(0.0, 0.0), Decision::If(Condition::GreaterThan(Source::Memory(0), Source::Loc(Location::Home)), Then::Nothing));
+        let Err(diagnostics) = env.add_ant(a.clone(), 1) else { panic!("added a broken ant") };
+        assert_eq!(diagnostics.len(), 2);
+        assert!(env.ants.is_empty() && env.things.is_empty());
+
+        // add doesn't check, the broken one runs and its comparison just never holds
+        let mut unchecked = Environment::new();
+        unchecked.add(a.clone(), 5);
+        unchecked.step();
+        assert_eq!(unchecked.ants.len(), 1);
+
+        a.set_brain(Program::from(vec![Decision::Always(Then::Remember(Source::Loc(Location::Here), Box::new(Then::Nothing)))]));
+        env.add_ant(a, 1).unwrap();
+        assert_eq!(env.ants.len(), 1);
+        assert_eq!(env.colliders.len(), 1);
+
+        // reading memory that isn't there doesn't take everything down with it
+        let b = ant(vec2(0.0, 0.0), Decision::Always(Then::Nothing));
+        assert!(matches!(env.senses(&b).evaluate(&Source::Memory(3)), Memory::Number(n) if n.is_nan()));
+        assert!(matches!(env.senses(&b).evaluate(&Source::Memory(-1)), Memory::Number(n) if n.is_nan()));
     }
 }